@@ -0,0 +1,13 @@
+-- 20241002090000_create_api_tokens_table.sql
+
+CREATE TABLE api_tokens (
+    api_token_id uuid NOT NULL,
+    user_id uuid NOT NULL REFERENCES users(user_id),
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,
+    created_at timestamptz NOT NULL,
+    last_used_at timestamptz NULL,
+    revoked_at timestamptz NULL,
+    PRIMARY KEY(api_token_id)
+);
@@ -0,0 +1,13 @@
+-- 20241005090000_create_issue_deliveries_table.sql
+
+-- `published_at` was stored as TEXT - make it orderable for pagination
+ALTER TABLE newsletter_issues
+    ALTER COLUMN published_at TYPE timestamptz USING published_at::timestamptz;
+
+CREATE TABLE issue_deliveries (
+    newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_email TEXT NOT NULL,
+    outcome TEXT NOT NULL,
+    attempted_at timestamptz NOT NULL,
+    PRIMARY KEY(newsletter_issue_id, subscriber_email)
+);
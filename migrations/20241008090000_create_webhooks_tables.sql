@@ -0,0 +1,22 @@
+-- 20241008090000_create_webhooks_tables.sql
+
+CREATE TABLE webhook_endpoints (
+    webhook_endpoint_id uuid NOT NULL,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    event_types TEXT[] NOT NULL,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY(webhook_endpoint_id)
+);
+
+CREATE TABLE webhook_delivery_queue (
+    webhook_delivery_id uuid NOT NULL,
+    webhook_endpoint_id uuid NOT NULL
+        REFERENCES webhook_endpoints (webhook_endpoint_id) ON DELETE CASCADE,
+    event_id uuid NOT NULL,
+    event_type TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    n_retries SMALLINT NOT NULL DEFAULT 0,
+    execute_after timestamptz NOT NULL,
+    PRIMARY KEY(webhook_delivery_id)
+);
@@ -0,0 +1,16 @@
+-- 20241011090000_create_email_events_table.sql
+
+CREATE TABLE email_events (
+    provider_event_id TEXT NOT NULL,
+    subscriber_id uuid NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    newsletter_issue_id uuid NULL REFERENCES newsletter_issues (newsletter_issue_id),
+    email TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    reason TEXT NULL,
+    occurred_at timestamptz NOT NULL,
+    received_at timestamptz NOT NULL,
+    PRIMARY KEY(provider_event_id)
+);
+
+CREATE INDEX email_events_subscriber_id_idx ON email_events (subscriber_id);
+CREATE INDEX email_events_newsletter_issue_id_idx ON email_events (newsletter_issue_id);
@@ -0,0 +1,10 @@
+-- 20241014090000_create_suppressions_table.sql
+
+CREATE TABLE suppressions (
+    suppression_id uuid NOT NULL,
+    -- Either a full, lowercased email address or a bare domain
+    pattern TEXT NOT NULL UNIQUE,
+    reason TEXT NOT NULL,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY(suppression_id)
+);
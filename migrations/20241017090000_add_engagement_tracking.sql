@@ -0,0 +1,14 @@
+-- 20241017090000_add_engagement_tracking.sql
+
+ALTER TABLE newsletter_issues ADD COLUMN tracking_enabled BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE issue_engagements (
+    newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    -- 'open' or 'click'
+    kind TEXT NOT NULL,
+    url TEXT NULL,
+    occurred_at timestamptz NOT NULL
+);
+
+CREATE INDEX issue_engagements_newsletter_issue_id_idx ON issue_engagements (newsletter_issue_id);
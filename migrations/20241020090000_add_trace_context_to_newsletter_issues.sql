@@ -0,0 +1,4 @@
+-- 20241020090000_add_trace_context_to_newsletter_issues.sql
+
+-- W3C `traceparent` of the request that published the issue
+ALTER TABLE newsletter_issues ADD COLUMN trace_context TEXT NULL;
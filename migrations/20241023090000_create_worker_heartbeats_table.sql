@@ -0,0 +1,6 @@
+-- 20241023090000_create_worker_heartbeats_table.sql
+
+CREATE TABLE worker_heartbeats (
+    worker_name TEXT NOT NULL PRIMARY KEY,
+    last_seen_at timestamptz NOT NULL
+);
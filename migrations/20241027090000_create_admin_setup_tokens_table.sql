@@ -0,0 +1,6 @@
+-- 20241027090000_create_admin_setup_tokens_table.sql
+
+CREATE TABLE admin_setup_tokens (
+    token_hash TEXT PRIMARY KEY,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
@@ -0,0 +1,4 @@
+-- 20241030090000_add_created_at_index_to_idempotency.sql
+
+-- The cleanup task looks for expired records by age
+CREATE INDEX idempotency_created_at_idx ON idempotency (created_at);
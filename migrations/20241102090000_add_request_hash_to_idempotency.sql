@@ -0,0 +1,5 @@
+-- 20241102090000_add_request_hash_to_idempotency.sql
+
+-- A fingerprint of the request body, so a reused key with a different payload can be rejected.
+-- Records saved before this column existed have no fingerprint and match any payload.
+ALTER TABLE idempotency ADD COLUMN request_hash TEXT NULL;
@@ -0,0 +1,10 @@
+-- 20241105090000_allow_anonymous_idempotency_records.sql
+
+-- Anonymous requests (e.g. subscribing) have no user. Postgres 14 cannot treat NULLs
+-- as equal in a unique constraint, so records are keyed by a non-null owner instead:
+-- the user, or the nil UUID for anonymous requests.
+ALTER TABLE idempotency DROP CONSTRAINT idempotency_pkey;
+ALTER TABLE idempotency ALTER COLUMN user_id DROP NOT NULL;
+ALTER TABLE idempotency ADD COLUMN owner_id uuid NOT NULL
+    GENERATED ALWAYS AS (COALESCE(user_id, '00000000-0000-0000-0000-000000000000')) STORED;
+ALTER TABLE idempotency ADD PRIMARY KEY (owner_id, idempotency_key);
@@ -0,0 +1,18 @@
+-- 20241108090000_create_lists_tables.sql
+
+CREATE TABLE lists (
+    list_id uuid NOT NULL,
+    -- Picked by name from the public subscription form
+    name TEXT NOT NULL UNIQUE,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY(list_id)
+);
+
+CREATE TABLE list_memberships (
+    list_id uuid NOT NULL REFERENCES lists (list_id) ON DELETE CASCADE,
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    joined_at timestamptz NOT NULL,
+    PRIMARY KEY(list_id, subscriber_id)
+);
+
+CREATE INDEX list_memberships_subscriber_id_idx ON list_memberships (subscriber_id);
@@ -0,0 +1,62 @@
+-- 20241111090000_create_tags_attributes_and_segments.sql
+
+CREATE TABLE subscriber_tags (
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY(subscriber_id, tag)
+);
+
+CREATE INDEX subscriber_tags_tag_idx ON subscriber_tags (tag);
+
+CREATE TABLE subscriber_attributes (
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY(subscriber_id, key)
+);
+
+CREATE TABLE segments (
+    segment_id uuid NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY(segment_id)
+);
+
+CREATE TABLE segment_rules (
+    segment_id uuid NOT NULL REFERENCES segments (segment_id) ON DELETE CASCADE,
+    position SMALLINT NOT NULL,
+    -- One of 'tag', 'attribute', 'subscribed_after' or 'subscribed_before'
+    kind TEXT NOT NULL,
+    -- Only set for 'attribute' rules
+    attribute_key TEXT NULL,
+    -- A timestamp for the 'subscribed_*' rules
+    value TEXT NOT NULL,
+    PRIMARY KEY(segment_id, position)
+);
+
+-- A subscriber is in a segment when none of the segment's rules fails for them,
+-- i.e. the rules are combined with AND.
+CREATE VIEW segment_members AS
+SELECT segments.segment_id, subscriptions.id AS subscriber_id
+FROM segments
+CROSS JOIN subscriptions
+WHERE NOT EXISTS (
+    SELECT 1 FROM segment_rules r
+    WHERE r.segment_id = segments.segment_id AND NOT (
+        CASE r.kind
+            WHEN 'tag' THEN EXISTS (
+                SELECT 1 FROM subscriber_tags t
+                WHERE t.subscriber_id = subscriptions.id AND t.tag = r.value
+            )
+            WHEN 'attribute' THEN EXISTS (
+                SELECT 1 FROM subscriber_attributes a
+                WHERE a.subscriber_id = subscriptions.id
+                    AND a.key = r.attribute_key
+                    AND a.value = r.value
+            )
+            WHEN 'subscribed_after' THEN subscriptions.subscribed_at > r.value::timestamptz
+            WHEN 'subscribed_before' THEN subscriptions.subscribed_at < r.value::timestamptz
+            ELSE false
+        END
+    )
+);
@@ -0,0 +1,4 @@
+-- 20241114090000_add_paused_until_to_subscriptions.sql
+
+-- Set from the preference center. Issues published before this are not delivered.
+ALTER TABLE subscriptions ADD COLUMN paused_until timestamptz NULL;
@@ -0,0 +1,13 @@
+-- 20241117090000_create_email_change_requests_table.sql
+
+-- Made from the preference center. The address is only swapped once the link sent to
+-- `new_email` is followed.
+CREATE TABLE email_change_requests (
+    token TEXT NOT NULL,
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    new_email TEXT NOT NULL,
+    requested_at timestamptz NOT NULL,
+    PRIMARY KEY (token)
+);
+
+CREATE INDEX email_change_requests_subscriber_id_idx ON email_change_requests (subscriber_id);
@@ -0,0 +1,6 @@
+-- 20241120090000_keep_engagements_of_erased_subscribers.sql
+
+-- Erasing a subscriber swaps their id for a random one, so an issue's unique opens and
+-- clicks still add up. Unsubscribing deletes the engagements explicitly.
+ALTER TABLE issue_engagements DROP CONSTRAINT issue_engagements_subscriber_id_fkey;
+CREATE INDEX issue_engagements_subscriber_id_idx ON issue_engagements (subscriber_id);
@@ -0,0 +1,39 @@
+-- 20241123090000_create_consent_events_table.sql
+
+-- How each subscriber's consent was obtained and every later change of their status.
+-- The record has to outlive the subscriber, so unsubscribing, which deletes them, leaves
+-- it in place. Events carry the address they were recorded for, to be found afterwards.
+CREATE TABLE consent_events (
+    consent_event_id uuid NOT NULL,
+    -- Not a foreign key, the subscriber may be gone
+    subscriber_id uuid NOT NULL,
+    email TEXT NOT NULL,
+    -- The status the subscriber was in after the event
+    status TEXT NOT NULL,
+    -- One of 'form', 'api', 'import', 'confirmation_link', 'preference_center' or
+    -- 'email_provider'
+    source TEXT NOT NULL,
+    -- Only known for events triggered by an HTTP request
+    ip_address TEXT NULL,
+    user_agent TEXT NULL,
+    occurred_at timestamptz NOT NULL,
+    PRIMARY KEY(consent_event_id)
+);
+
+CREATE INDEX consent_events_subscriber_id_idx ON consent_events (subscriber_id, occurred_at);
+CREATE INDEX consent_events_email_idx ON consent_events (email);
+
+-- The history is append-only. Only erasing a subscriber's data removes their events,
+-- and erasure sets `app.erasing_subscriber` for its own transaction to do so.
+CREATE FUNCTION protect_consent_events() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' AND current_setting('app.erasing_subscriber', true) = 'on' THEN
+        RETURN OLD;
+    END IF;
+    RAISE EXCEPTION 'consent_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER consent_events_append_only
+    BEFORE UPDATE OR DELETE ON consent_events
+    FOR EACH ROW EXECUTE FUNCTION protect_consent_events();
@@ -0,0 +1,4 @@
+-- 20241126090000_add_opt_in_to_lists.sql
+
+-- Overrides the configured opt-in mode for subscribers joining the list. NULL keeps it.
+ALTER TABLE lists ADD COLUMN opt_in TEXT NULL CHECK (opt_in IN ('single', 'double'));
@@ -0,0 +1,34 @@
+-- 20241129090000_create_issue_recipient_functions.sql
+-- Shared by the delivery queue, the delivery worker and the recipient counts shown
+-- before an issue is sent, so they cannot drift apart.
+CREATE FUNCTION email_is_suppressed(email TEXT) RETURNS BOOLEAN
+LANGUAGE SQL STABLE AS $$
+    SELECT EXISTS (
+        SELECT 1 FROM suppressions
+        WHERE pattern = lower(email) OR pattern = lower(split_part(email, '@', 2))
+    )
+$$;
+
+-- Confirmed subscribers who have not paused delivery, narrowed down to members of the
+-- lists in $1 when it is not empty and to the members of the segment $2 when it is set.
+CREATE FUNCTION issue_recipients(UUID[], UUID) RETURNS TABLE (subscriber_id UUID, email TEXT)
+LANGUAGE SQL STABLE AS $$
+    SELECT s.id, s.email
+    FROM subscriptions s
+    WHERE s.status = 'confirmed'
+        AND (s.paused_until IS NULL OR s.paused_until <= now())
+        AND (
+            cardinality($1) = 0
+            OR EXISTS (
+                SELECT 1 FROM list_memberships m
+                WHERE m.subscriber_id = s.id AND m.list_id = ANY($1)
+            )
+        )
+        AND (
+            $2 IS NULL
+            OR EXISTS (
+                SELECT 1 FROM segment_members m
+                WHERE m.subscriber_id = s.id AND m.segment_id = $2
+            )
+        )
+$$;
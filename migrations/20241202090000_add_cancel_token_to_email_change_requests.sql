@@ -0,0 +1,6 @@
+-- 20241202090000_add_cancel_token_to_email_change_requests.sql
+
+-- Sent to the current address, so its owner can call off a change they did not ask for.
+-- Requests still pending were made without one and have to be made again.
+DELETE FROM email_change_requests;
+ALTER TABLE email_change_requests ADD COLUMN cancel_token TEXT NOT NULL UNIQUE;
@@ -0,0 +1,14 @@
+-- 20241205090000_create_data_request_tokens_table.sql
+
+-- Exports and erasures asked for from the preference center only go ahead once the link
+-- emailed to the subscriber's address is followed, within an hour and only once.
+CREATE TABLE data_request_tokens (
+    token TEXT NOT NULL,
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    -- 'export' or 'erase'
+    action TEXT NOT NULL,
+    requested_at timestamptz NOT NULL,
+    PRIMARY KEY (token)
+);
+
+CREATE INDEX data_request_tokens_subscriber_id_idx ON data_request_tokens (subscriber_id);
@@ -0,0 +1,125 @@
+//! src/authentication/api_token.rs
+
+use anyhow::Context;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::AuthError;
+
+const API_TOKEN_PREFIX: &str = "z2p_";
+
+/// Permissions that can be granted to an API token.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Scope {
+    SubscribersRead,
+    SubscribersWrite,
+    IssuesRead,
+    IssuesWrite,
+    /// Adds subscribers as confirmed without sending them a confirmation email.
+    SubscribersImport,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 5] = [
+        Scope::SubscribersRead,
+        Scope::SubscribersWrite,
+        Scope::IssuesRead,
+        Scope::IssuesWrite,
+        Scope::SubscribersImport,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            Scope::SubscribersRead => "subscribers:read",
+            Scope::SubscribersWrite => "subscribers:write",
+            Scope::IssuesRead => "issues:read",
+            Scope::IssuesWrite => "issues:write",
+            Scope::SubscribersImport => "subscribers:import",
+        };
+    }
+}
+
+impl TryFrom<String> for Scope {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        return Scope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| format!("{} is not a valid API token scope", s));
+    }
+}
+
+impl std::fmt::Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return self.as_str().fmt(f);
+    }
+}
+
+/// An API token that has been validated against the database.
+#[derive(Clone, Debug)]
+pub struct ApiToken {
+    pub api_token_id: Uuid,
+    pub user_id: Uuid,
+    pub scopes: Vec<Scope>,
+}
+
+impl ApiToken {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        return self.scopes.contains(&scope);
+    }
+}
+
+/// Generate a random API token. Only its hash is ever stored.
+pub fn generate_api_token() -> Secret<String> {
+    let mut rng = thread_rng();
+    let token: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(40)
+        .collect();
+    return Secret::new(format!("{}{}", API_TOKEN_PREFIX, token));
+}
+
+/// API tokens carry enough entropy that a fast hash is sufficient -
+/// unlike passwords they cannot be brute-forced from a dictionary.
+pub fn hash_api_token(token: &Secret<String>) -> String {
+    let digest = Sha256::digest(token.expose_secret().as_bytes());
+    return hex::encode(digest);
+}
+
+#[tracing::instrument(name = "Validate API token", skip(token, pool))]
+pub async fn validate_api_token(
+    token: Secret<String>,
+    pool: &PgPool,
+) -> Result<ApiToken, AuthError> {
+    let token_hash = hash_api_token(&token);
+    let row = sqlx::query!(
+        r#"
+        UPDATE api_tokens
+        SET last_used_at = now()
+        WHERE token_hash = $1 AND revoked_at IS NULL
+        RETURNING api_token_id, user_id, scopes
+        "#,
+        token_hash
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to validate an API token")?
+    .ok_or_else(|| anyhow::anyhow!("Unknown or revoked API token."))
+    .map_err(AuthError::InvalidCredentials)?;
+
+    let scopes = row
+        .scopes
+        .into_iter()
+        .filter_map(|s| Scope::try_from(s).ok())
+        .collect();
+
+    return Ok(ApiToken {
+        api_token_id: row.api_token_id,
+        user_id: row.user_id,
+        scopes,
+    });
+}
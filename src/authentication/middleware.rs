@@ -5,12 +5,18 @@ use std::ops::Deref;
 use actix_web::body::MessageBody;
 use actix_web::dev::{ServiceRequest, ServiceResponse};
 use actix_web::error::InternalError;
-use actix_web::{FromRequest, HttpMessage};
+use actix_web::http::header::{HeaderMap, HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use actix_web::http::StatusCode;
+use actix_web::{web, FromRequest, HttpMessage};
 use actix_web_lab::middleware::Next;
+use anyhow::Context;
+use secrecy::Secret;
+use sqlx::PgPool;
 use uuid::Uuid;
 
+use super::{validate_api_token, AuthError};
 use crate::session_state::TypedSession;
-use crate::utils::{e500, see_other};
+use crate::utils::{e500, json_error, see_other};
 
 #[derive(Copy, Clone, Debug)]
 pub struct UserId(Uuid);
@@ -50,3 +56,49 @@ pub async fn reject_anonymous_users(
         }
     };
 }
+
+pub async fn reject_invalid_api_tokens(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let token = match bearer_token(req.headers()) {
+        Ok(token) => token,
+        Err(e) => return Err(unauthorised(e)),
+    };
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .context("The database pool is not registered")
+        .map_err(e500)?
+        .clone();
+
+    return match validate_api_token(token, &pool).await {
+        Ok(api_token) => {
+            req.extensions_mut().insert(UserId(api_token.user_id));
+            req.extensions_mut().insert(api_token);
+            next.call(req).await
+        }
+        Err(AuthError::InvalidCredentials(e)) => Err(unauthorised(e)),
+        Err(AuthError::UnexpectedError(e)) => Err(e500(e)),
+    };
+}
+
+fn bearer_token(headers: &HeaderMap) -> Result<Secret<String>, anyhow::Error> {
+    let header_value = headers
+        .get(AUTHORIZATION)
+        .context("The 'Authorization' header was missing")?
+        .to_str()
+        .context("The 'Authorization' header was not a valid UTF8 string")?;
+    let token = header_value
+        .strip_prefix("Bearer ")
+        .context("The authorisation scheme was not bearer")?;
+
+    return Ok(Secret::new(token.to_string()));
+}
+
+fn unauthorised(e: anyhow::Error) -> actix_web::Error {
+    let mut response = json_error(StatusCode::UNAUTHORIZED, "Invalid or missing API token");
+    response
+        .headers_mut()
+        .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    return InternalError::from_response(e, response).into();
+}
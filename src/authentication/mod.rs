@@ -1,8 +1,13 @@
 //! src/authentication/mod.rs
 
+mod api_token;
 mod middleware;
 mod password;
 
-pub use middleware::reject_anonymous_users;
+pub use api_token::{generate_api_token, hash_api_token, validate_api_token, ApiToken, Scope};
 pub use middleware::UserId;
-pub use password::{change_password, validate_credentials, AuthError, Credentials};
+pub use middleware::{reject_anonymous_users, reject_invalid_api_tokens};
+pub use password::{
+    change_password, create_user, get_user_id, validate_credentials, validate_new_password,
+    verify_password_hash, AuthError, Credentials, DummyPasswordHash,
+};
@@ -1,5 +1,6 @@
 //! src/authentication/password.rs
 
+use crate::configuration::PasswordHashingSettings;
 use crate::telemetry::spawn_blocking_with_tracing;
 use anyhow::Context;
 use argon2::{
@@ -7,7 +8,7 @@ use argon2::{
     PasswordVerifier, Version,
 };
 use secrecy::{ExposeSecret, Secret};
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 
 #[derive(thiserror::Error, Debug)]
 pub enum AuthError {
@@ -22,18 +23,31 @@ pub struct Credentials {
     pub password: Secret<String>,
 }
 
-#[tracing::instrument(name = "Validate credentials", skip(credentials, pool))]
+/// Verified against when the username is unknown, so a failed login takes as long
+/// as a wrong password.
+///
+/// Hashed with the configured parameters, so the timing still matches after they change.
+pub struct DummyPasswordHash(Secret<String>);
+
+impl DummyPasswordHash {
+    pub async fn new(hashing: &PasswordHashingSettings) -> Result<Self, anyhow::Error> {
+        let password = Secret::new(uuid::Uuid::new_v4().to_string());
+        return Ok(Self(hash_password(password, hashing).await?));
+    }
+}
+
+#[tracing::instrument(
+    name = "Validate credentials",
+    skip(credentials, pool, hashing, dummy_password_hash)
+)]
 pub async fn validate_credentials(
     credentials: Credentials,
     pool: &PgPool,
+    hashing: &PasswordHashingSettings,
+    dummy_password_hash: &DummyPasswordHash,
 ) -> Result<uuid::Uuid, AuthError> {
     let mut user_id = None;
-    let mut expected_password_hash = Secret::new(
-        "$argon2id$v=19$m=15000,t=2,p=1$\
-        gZiV/M1gPc22ElAH/Jh1Hw$\
-        CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
-            .to_string(),
-    );
+    let mut expected_password_hash = dummy_password_hash.0.clone();
 
     if let Some((stored_user_id, stored_password_hash)) =
         get_stored_credentials(&credentials.username, &pool).await?
@@ -42,24 +56,42 @@ pub async fn validate_credentials(
         expected_password_hash = stored_password_hash;
     }
 
-    spawn_blocking_with_tracing(move || {
-        verify_password_hash(expected_password_hash, credentials.password)
+    let params = hashing
+        .params()
+        .context("Invalid password hashing parameters")?;
+    let upgraded_password_hash = spawn_blocking_with_tracing(move || {
+        verify_password_hash(&expected_password_hash, &credentials.password)?;
+        return upgrade_password_hash(&expected_password_hash, credentials.password, params)
+            .map_err(AuthError::UnexpectedError);
     })
     .await
     .context("Failed to spawn blocking task")??;
 
-    return user_id
+    let user_id = user_id
         .ok_or_else(|| anyhow::anyhow!("Unknown username."))
-        .map_err(AuthError::InvalidCredentials);
+        .map_err(AuthError::InvalidCredentials)?;
+
+    // A failed upgrade must not lock the user out - the old hash is still valid
+    if let Some(password_hash) = upgraded_password_hash {
+        if let Err(e) = store_password_hash(user_id, password_hash, pool).await {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to store upgraded password hash"
+            );
+        }
+    }
+
+    return Ok(user_id);
 }
 
 #[tracing::instrument(
     name = "Verify password hash",
     skip(expected_password_hash, password_candidate)
 )]
-fn verify_password_hash(
-    expected_password_hash: Secret<String>,
-    password_candidate: Secret<String>,
+pub fn verify_password_hash(
+    expected_password_hash: &Secret<String>,
+    password_candidate: &Secret<String>,
 ) -> Result<(), AuthError> {
     let expected_password_hash = PasswordHash::new(&expected_password_hash.expose_secret())
         .context("Failed to parse hash in PHC string format.")?;
@@ -73,6 +105,42 @@ fn verify_password_hash(
         .map_err(AuthError::InvalidCredentials);
 }
 
+/// Re-hash the password if the stored hash was computed with weaker parameters
+/// than the ones currently configured.
+#[tracing::instrument(
+    name = "Upgrade password hash",
+    skip(expected_password_hash, password, params)
+)]
+fn upgrade_password_hash(
+    expected_password_hash: &Secret<String>,
+    password: Secret<String>,
+    params: Params,
+) -> Result<Option<Secret<String>>, anyhow::Error> {
+    let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
+        .context("Failed to parse hash in PHC string format.")?;
+    if !needs_rehash(&expected_password_hash, &params) {
+        return Ok(None);
+    }
+
+    let password_hash = compute_password_hash(password, params)?;
+    return Ok(Some(password_hash));
+}
+
+fn needs_rehash(password_hash: &PasswordHash, params: &Params) -> bool {
+    if password_hash.algorithm != Algorithm::Argon2id.ident() {
+        return true;
+    }
+
+    return match Params::try_from(password_hash) {
+        Ok(stored) => {
+            stored.m_cost() < params.m_cost()
+                || stored.t_cost() < params.t_cost()
+                || stored.p_cost() < params.p_cost()
+        }
+        Err(_) => true,
+    };
+}
+
 #[tracing::instrument(name = "Get stored credentials", skip(username, pool))]
 async fn get_stored_credentials(
     username: &str,
@@ -94,16 +162,86 @@ async fn get_stored_credentials(
     return Ok(row);
 }
 
-#[tracing::instrument(name = "Change password", skip(password, pool))]
+/// Length rules for passwords chosen by an admin.
+pub fn validate_new_password(password: &Secret<String>) -> Result<(), String> {
+    if password.expose_secret().len() <= 12 {
+        return Err("New password must be longer than 12 characters".into());
+    }
+    if password.expose_secret().len() >= 129 {
+        return Err("New password must be shorter than 129 characters".into());
+    }
+    return Ok(());
+}
+
+#[tracing::instrument(name = "Change password", skip(password, pool, hashing))]
 pub async fn change_password(
     user_id: uuid::Uuid,
     password: Secret<String>,
     pool: &PgPool,
+    hashing: &PasswordHashingSettings,
 ) -> Result<(), anyhow::Error> {
-    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
+    let password_hash = hash_password(password, hashing).await?;
+
+    return store_password_hash(user_id, password_hash, pool)
+        .await
+        .context("Failed to change user's password in the database");
+}
+
+#[tracing::instrument(name = "Create user", skip(password, executor, hashing))]
+pub async fn create_user(
+    username: &str,
+    password: Secret<String>,
+    executor: impl PgExecutor<'_>,
+    hashing: &PasswordHashingSettings,
+) -> Result<uuid::Uuid, anyhow::Error> {
+    let password_hash = hash_password(password, hashing).await?;
+    let user_id = uuid::Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash)
+        VALUES ($1, $2, $3)
+        "#,
+        user_id,
+        username,
+        password_hash.expose_secret()
+    )
+    .execute(executor)
+    .await
+    .context("Failed to store a new user in the database")?;
+
+    return Ok(user_id);
+}
+
+#[tracing::instrument(name = "Get user id", skip(pool))]
+pub async fn get_user_id(
+    username: &str,
+    pool: &PgPool,
+) -> Result<Option<uuid::Uuid>, anyhow::Error> {
+    let user = sqlx::query!("SELECT user_id FROM users WHERE username = $1", username)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to perform a query to retrieve a user id")?;
+    return Ok(user.map(|u| u.user_id));
+}
+
+async fn hash_password(
+    password: Secret<String>,
+    hashing: &PasswordHashingSettings,
+) -> Result<Secret<String>, anyhow::Error> {
+    let params = hashing
+        .params()
+        .context("Invalid password hashing parameters")?;
+    return spawn_blocking_with_tracing(move || compute_password_hash(password, params))
         .await?
-        .context("Failed to hash password")?;
+        .context("Failed to hash password");
+}
 
+#[tracing::instrument(name = "Store password hash", skip(password_hash, pool))]
+async fn store_password_hash(
+    user_id: uuid::Uuid,
+    password_hash: Secret<String>,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
     sqlx::query!(
         r#"
         UPDATE users
@@ -114,20 +252,18 @@ pub async fn change_password(
         user_id
     )
     .execute(pool)
-    .await
-    .context("Failed to change user's password in the database")?;
+    .await?;
 
     return Ok(());
 }
 
-fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
+fn compute_password_hash(
+    password: Secret<String>,
+    params: Params,
+) -> Result<Secret<String>, anyhow::Error> {
     let salt = SaltString::generate(&mut rand::thread_rng());
-    let password_hash = Argon2::new(
-        Algorithm::Argon2id,
-        Version::V0x13,
-        Params::new(15000, 2, 1, None).unwrap(),
-    )
-    .hash_password(password.expose_secret().as_bytes(), &salt)?
-    .to_string();
+    let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password(password.expose_secret().as_bytes(), &salt)?
+        .to_string();
     return Ok(Secret::new(password_hash));
 }
@@ -0,0 +1,231 @@
+//! src/bootstrap.rs
+
+use anyhow::Context;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::authentication::{create_user, validate_new_password, verify_password_hash};
+use crate::configuration::{InitialAdminSettings, PasswordHashingSettings};
+use crate::routes::error_chain_fmt;
+use crate::telemetry::spawn_blocking_with_tracing;
+
+/// The admin `20240805060145_seed_user.sql` created in every environment. Its password
+/// is public.
+const SEED_ADMIN_ID: Uuid = uuid::uuid!("85fbb63b-2a00-48f8-8e73-8b5e339da33c");
+const SEED_ADMIN_USERNAME: &str = "admin";
+const SEED_ADMIN_PASSWORD: &str = "everythinghastostartsomewhere";
+
+#[derive(thiserror::Error)]
+pub enum SetupError {
+    #[error("The setup token is invalid")]
+    InvalidToken,
+    #[error("An admin account already exists")]
+    AlreadyCompleted,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for SetupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return error_chain_fmt(self, f);
+    }
+}
+
+/// Make sure a fresh deployment can be logged into. While there are no users, either the
+/// configured initial admin is created or a one-time link to `/setup` is logged.
+#[tracing::instrument(name = "Bootstrap admin", skip_all)]
+pub async fn bootstrap_admin(
+    pool: &PgPool,
+    initial_admin: Option<&InitialAdminSettings>,
+    base_url: &str,
+    hashing: &PasswordHashingSettings,
+) -> Result<(), anyhow::Error> {
+    remove_seed_admin(pool).await?;
+    if has_users(pool).await? {
+        // Tokens issued before the first admin was created some other way
+        sqlx::query!("DELETE FROM admin_setup_tokens")
+            .execute(pool)
+            .await
+            .context("Failed to delete stale setup tokens")?;
+        return Ok(());
+    }
+
+    match initial_admin {
+        Some(admin) => {
+            validate_new_password(&admin.password)
+                .map_err(|e| anyhow::anyhow!("Invalid initial admin password: {}", e))?;
+            let Some(mut transaction) = begin_if_no_users(pool).await? else {
+                // Another instance got there first
+                return Ok(());
+            };
+            create_user(
+                &admin.username,
+                admin.password.clone(),
+                &mut *transaction,
+                hashing,
+            )
+            .await?;
+            clear_setup_tokens(&mut transaction).await?;
+            transaction
+                .commit()
+                .await
+                .context("Failed to commit SQL transaction to create the initial admin")?;
+            tracing::info!(username = %admin.username, "Created the initial admin");
+        }
+        None => {
+            let token = issue_setup_token(pool).await?;
+            tracing::warn!(
+                "There are no users yet. Create the first admin at {}/setup?token={} \
+                or with `zero2prod create-user`",
+                base_url,
+                token.expose_secret()
+            );
+        }
+    }
+    return Ok(());
+}
+
+/// Delete the seed admin, and everything it created, unless its password was changed.
+///
+/// Logging in rehashes the password with the configured parameters, so the stored hash
+/// cannot be compared with the seeded one. The public password is verified against it.
+async fn remove_seed_admin(pool: &PgPool) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let Some(password_hash) = sqlx::query_scalar!(
+        "SELECT password_hash FROM users WHERE user_id = $1 AND username = $2 FOR UPDATE",
+        SEED_ADMIN_ID,
+        SEED_ADMIN_USERNAME
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to look up the seed admin")?
+    else {
+        return Ok(());
+    };
+    let is_unchanged = spawn_blocking_with_tracing(move || {
+        let password = Secret::new(SEED_ADMIN_PASSWORD.to_string());
+        return verify_password_hash(&Secret::new(password_hash), &password).is_ok();
+    })
+    .await
+    .context("Failed to spawn blocking task")?;
+    if !is_unchanged {
+        return Ok(());
+    }
+
+    sqlx::query!("DELETE FROM api_tokens WHERE user_id = $1", SEED_ADMIN_ID)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete the seed admin's API tokens")?;
+    sqlx::query!("DELETE FROM idempotency WHERE user_id = $1", SEED_ADMIN_ID)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete the seed admin's saved responses")?;
+    sqlx::query!("DELETE FROM users WHERE user_id = $1", SEED_ADMIN_ID)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete the seed admin")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to delete the seed admin")?;
+    tracing::warn!("Deleted the seed admin, whose password is public");
+    return Ok(());
+}
+
+pub async fn has_users(pool: &PgPool) -> Result<bool, anyhow::Error> {
+    return sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM users) AS "exists!""#)
+        .fetch_one(pool)
+        .await
+        .context("Failed to check whether any users exist");
+}
+
+/// Store a new setup token. Only its hash is kept, and it stops working once an admin exists.
+pub async fn issue_setup_token(pool: &PgPool) -> Result<Secret<String>, anyhow::Error> {
+    let mut rng = thread_rng();
+    let token: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect();
+    let token = Secret::new(token);
+    sqlx::query!(
+        "INSERT INTO admin_setup_tokens (token_hash) VALUES ($1)",
+        hash_setup_token(&token)
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store a setup token")?;
+    return Ok(token);
+}
+
+/// Create the first admin, provided `token` was issued by `issue_setup_token`.
+#[tracing::instrument(name = "Complete setup", skip(token, password, pool, hashing))]
+pub async fn complete_setup(
+    token: &Secret<String>,
+    username: &str,
+    password: Secret<String>,
+    pool: &PgPool,
+    hashing: &PasswordHashingSettings,
+) -> Result<Uuid, SetupError> {
+    let Some(mut transaction) = begin_if_no_users(pool).await? else {
+        return Err(SetupError::AlreadyCompleted);
+    };
+    let is_valid = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM admin_setup_tokens WHERE token_hash = $1) AS "exists!""#,
+        hash_setup_token(token)
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .context("Failed to look up the setup token")?;
+    if !is_valid {
+        return Err(SetupError::InvalidToken);
+    }
+
+    let user_id = create_user(username, password, &mut *transaction, hashing).await?;
+    clear_setup_tokens(&mut transaction).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to create the first admin")?;
+    return Ok(user_id);
+}
+
+/// Locks `users` until the transaction ends, so that concurrent bootstraps cannot both
+/// create a first admin. Returns `None` if there already is a user.
+async fn begin_if_no_users(
+    pool: &PgPool,
+) -> Result<Option<Transaction<'static, Postgres>>, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    sqlx::query!("LOCK TABLE users IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to lock the users table")?;
+    let has_users = sqlx::query_scalar!(r#"SELECT EXISTS (SELECT 1 FROM users) AS "exists!""#)
+        .fetch_one(&mut *transaction)
+        .await
+        .context("Failed to check whether any users exist")?;
+    return Ok((!has_users).then_some(transaction));
+}
+
+async fn clear_setup_tokens(
+    transaction: &mut Transaction<'static, Postgres>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!("DELETE FROM admin_setup_tokens")
+        .execute(&mut **transaction)
+        .await
+        .context("Failed to delete the setup tokens")?;
+    return Ok(());
+}
+
+fn hash_setup_token(token: &Secret<String>) -> String {
+    let digest = Sha256::digest(token.expose_secret().as_bytes());
+    return hex::encode(digest);
+}
@@ -0,0 +1,221 @@
+//! src/cli.rs
+
+use std::io::BufRead;
+
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::{change_password, create_user, get_user_id, validate_new_password};
+use crate::configuration::PasswordHashingSettings;
+use crate::startup::{count_pending_migrations, MIGRATOR};
+
+/// Running `zero2prod` without a subcommand starts the API and the workers in one process.
+#[derive(clap::Parser)]
+#[command(name = "zero2prod", about = "A newsletter delivery service")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// Serve the HTTP API without running the background workers
+    Serve,
+    /// Run the background workers without serving the HTTP API. Metrics are still served
+    /// on the metrics port
+    Worker,
+    /// Apply any pending database migrations
+    Migrate,
+    /// Create an admin user
+    CreateUser {
+        username: String,
+        #[command(flatten)]
+        password: PasswordSource,
+    },
+    /// Set a new password for an admin user
+    ResetPassword {
+        username: String,
+        #[command(flatten)]
+        password: PasswordSource,
+    },
+    /// Show how much work is waiting in the delivery queues
+    QueueStats,
+}
+
+#[derive(clap::Args)]
+pub struct PasswordSource {
+    /// Read the password from the first line of stdin instead of prompting for it
+    #[arg(long)]
+    password_stdin: bool,
+}
+
+impl PasswordSource {
+    pub fn read(&self) -> Result<Secret<String>, anyhow::Error> {
+        let password = if self.password_stdin {
+            let mut line = String::new();
+            std::io::stdin()
+                .lock()
+                .read_line(&mut line)
+                .context("Failed to read the password from stdin")?;
+            Secret::new(line.trim_end_matches(['\r', '\n']).to_string())
+        } else {
+            let password = Secret::new(rpassword::prompt_password("Password: ")?);
+            let password_check = Secret::new(rpassword::prompt_password("Repeat password: ")?);
+            if password.expose_secret() != password_check.expose_secret() {
+                anyhow::bail!("The passwords do not match");
+            }
+            password
+        };
+        validate_new_password(&password).map_err(|e| anyhow::anyhow!(e))?;
+        return Ok(password);
+    }
+}
+
+/// Returns the number of migrations that were applied.
+pub async fn migrate(pool: &PgPool) -> Result<usize, anyhow::Error> {
+    // A brand new database has no `_sqlx_migrations` table to count from yet
+    let pending = count_pending_migrations(pool)
+        .await
+        .unwrap_or(MIGRATOR.iter().count());
+    MIGRATOR
+        .run(pool)
+        .await
+        .context("Failed to migrate the database")?;
+    return Ok(pending);
+}
+
+pub async fn add_user(
+    username: &str,
+    password: Secret<String>,
+    pool: &PgPool,
+    hashing: &PasswordHashingSettings,
+) -> Result<Uuid, anyhow::Error> {
+    if get_user_id(username, pool).await?.is_some() {
+        anyhow::bail!("A user called {} already exists", username);
+    }
+    return create_user(username, password, pool, hashing).await;
+}
+
+pub async fn reset_password(
+    username: &str,
+    password: Secret<String>,
+    pool: &PgPool,
+    hashing: &PasswordHashingSettings,
+) -> Result<(), anyhow::Error> {
+    let user_id = get_user_id(username, pool)
+        .await?
+        .with_context(|| format!("There is no user called {}", username))?;
+    return change_password(user_id, password, pool, hashing).await;
+}
+
+pub struct QueueStats {
+    pub issues: Vec<IssueQueueStats>,
+    pub webhook_deliveries: i64,
+    pub webhook_retries: i64,
+}
+
+pub struct IssueQueueStats {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub pending: i64,
+}
+
+impl std::fmt::Display for QueueStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pending: i64 = self.issues.iter().map(|i| i.pending).sum();
+        writeln!(f, "issue_delivery_queue: {} pending", pending)?;
+        for issue in &self.issues {
+            writeln!(
+                f,
+                "  {} {:>6}  {}",
+                issue.newsletter_issue_id, issue.pending, issue.title
+            )?;
+        }
+        return writeln!(
+            f,
+            "webhook_delivery_queue: {} pending ({} retrying)",
+            self.webhook_deliveries, self.webhook_retries
+        );
+    }
+}
+
+pub async fn queue_stats(pool: &PgPool) -> Result<QueueStats, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        IssueQueueStats,
+        r#"
+        SELECT q.newsletter_issue_id, i.title, COUNT(*) AS "pending!"
+        FROM issue_delivery_queue q
+        JOIN newsletter_issues i ON i.newsletter_issue_id = q.newsletter_issue_id
+        GROUP BY q.newsletter_issue_id, i.title, i.published_at
+        ORDER BY i.published_at
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to perform a query to count queued issue deliveries")?;
+    let webhooks = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(*) AS "pending!",
+            COUNT(*) FILTER (WHERE n_retries > 0) AS "retrying!"
+        FROM webhook_delivery_queue
+        "#
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to perform a query to count queued webhook deliveries")?;
+
+    return Ok(QueueStats {
+        issues,
+        webhook_deliveries: webhooks.pending,
+        webhook_retries: webhooks.retrying,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cli, Command, IssueQueueStats, QueueStats};
+    use clap::Parser;
+    use uuid::Uuid;
+
+    #[test]
+    fn no_subcommand_runs_everything() {
+        let cli = Cli::try_parse_from(["zero2prod"]).unwrap();
+        assert!(cli.command.is_none());
+    }
+
+    #[test]
+    fn create_user_takes_a_username() {
+        let cli =
+            Cli::try_parse_from(["zero2prod", "create-user", "alice", "--password-stdin"]).unwrap();
+        match cli.command {
+            Some(Command::CreateUser { username, password }) => {
+                assert_eq!(username, "alice");
+                assert!(password.password_stdin);
+            }
+            _ => panic!("Expected the create-user command"),
+        }
+        assert!(Cli::try_parse_from(["zero2prod", "create-user"]).is_err());
+    }
+
+    #[test]
+    fn queue_stats_are_printed_per_issue() {
+        let newsletter_issue_id = Uuid::new_v4();
+        let stats = QueueStats {
+            issues: vec![IssueQueueStats {
+                newsletter_issue_id,
+                title: "Hello".into(),
+                pending: 3,
+            }],
+            webhook_deliveries: 2,
+            webhook_retries: 1,
+        };
+
+        let output = stats.to_string();
+        assert!(output.starts_with("issue_delivery_queue: 3 pending\n"));
+        assert!(output.contains(&format!("{}      3  Hello", newsletter_issue_id)));
+        assert!(output.ends_with("webhook_delivery_queue: 2 pending (1 retrying)\n"));
+    }
+}
@@ -0,0 +1,82 @@
+//! src/client_ip.rs
+
+use std::net::IpAddr;
+
+use actix_web::{web, HttpRequest};
+
+/// Reverse proxies in front of the application whose `X-Forwarded-For` header is believed.
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies(pub Vec<IpAddr>);
+
+/// The address of the client behind the request.
+///
+/// Anyone can send `X-Forwarded-For`, so it is only consulted when the connection comes
+/// from a trusted proxy. Proxies append to the header, so it is read from the right and
+/// the first address that is not a trusted proxy itself is the client.
+pub fn client_ip(request: &HttpRequest) -> Option<String> {
+    let peer = request.peer_addr()?.ip();
+    let trusted = match request.app_data::<web::Data<TrustedProxies>>() {
+        Some(trusted) if trusted.0.contains(&peer) => trusted,
+        _ => return Some(peer.to_string()),
+    };
+    let forwarded: Vec<IpAddr> = request
+        .headers()
+        .get_all("X-Forwarded-For")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|ip| ip.trim().parse::<IpAddr>())
+        .collect::<Result<_, _>>()
+        .unwrap_or_default();
+    let client = forwarded
+        .into_iter()
+        .rev()
+        .find(|ip| !trusted.0.contains(ip))
+        .unwrap_or(peer);
+    return Some(client.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{client_ip, TrustedProxies};
+    use actix_web::test::TestRequest;
+    use actix_web::web;
+
+    fn request(peer: &str, forwarded_for: &str) -> TestRequest {
+        return TestRequest::default()
+            .peer_addr(format!("{}:4000", peer).parse().unwrap())
+            .insert_header(("X-Forwarded-For", forwarded_for));
+    }
+
+    fn trusting(proxies: &[&str]) -> web::Data<TrustedProxies> {
+        return web::Data::new(TrustedProxies(
+            proxies.iter().map(|ip| ip.parse().unwrap()).collect(),
+        ));
+    }
+
+    #[test]
+    fn forwarded_addresses_are_ignored_from_untrusted_peers() {
+        let request = request("203.0.113.7", "198.51.100.1")
+            .app_data(trusting(&["10.0.0.1"]))
+            .to_http_request();
+
+        assert_eq!(client_ip(&request).as_deref(), Some("203.0.113.7"));
+    }
+
+    #[test]
+    fn the_rightmost_untrusted_forwarded_address_is_the_client() {
+        let request = request("10.0.0.1", "192.0.2.66, 198.51.100.1, 10.0.0.2")
+            .app_data(trusting(&["10.0.0.1", "10.0.0.2"]))
+            .to_http_request();
+
+        assert_eq!(client_ip(&request).as_deref(), Some("198.51.100.1"));
+    }
+
+    #[test]
+    fn a_malformed_header_falls_back_to_the_peer() {
+        let request = request("10.0.0.1", "not-an-ip")
+            .app_data(trusting(&["10.0.0.1"]))
+            .to_http_request();
+
+        assert_eq!(client_ip(&request).as_deref(), Some("10.0.0.1"));
+    }
+}
@@ -1,5 +1,10 @@
 //! src/configuration.rs
 
+use anyhow::Context;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use p256::ecdsa::VerifyingKey;
+use p256::pkcs8::DecodePublicKey;
 use secrecy::{ExposeSecret, Secret};
 use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::{
@@ -7,7 +12,10 @@ use sqlx::{
     ConnectOptions,
 };
 
-use crate::{domain::SubscriberEmail, email_client::EmailClient};
+use crate::{
+    domain::{OptIn, SubscriberEmail},
+    email_client::EmailClient,
+};
 
 #[derive(serde::Deserialize, Clone)]
 pub struct Settings {
@@ -15,6 +23,90 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
     pub redis_uri: Secret<String>,
+    pub password_hashing: PasswordHashingSettings,
+    pub idempotency: IdempotencySettings,
+    pub subscriptions: SubscriptionSettings,
+    #[serde(default)]
+    pub telemetry: TelemetrySettings,
+    /// Created on startup if there are no users yet.
+    #[serde(default)]
+    pub initial_admin: Option<InitialAdminSettings>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct InitialAdminSettings {
+    pub username: String,
+    pub password: Secret<String>,
+}
+
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+pub struct TelemetrySettings {
+    /// Spans are exported to this OTLP/gRPC collector (e.g. `http://localhost:4317`) when set.
+    pub otlp_endpoint: Option<String>,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct IdempotencySettings {
+    /// Saved responses are replayed for this long; after that a retried key counts as new.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub retention_hours: u64,
+    /// How many expired records the cleanup task deletes per statement.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub cleanup_batch_size: i64,
+}
+
+impl IdempotencySettings {
+    pub fn retention(&self) -> std::time::Duration {
+        return std::time::Duration::from_secs(self.retention_hours * 60 * 60);
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct SubscriptionSettings {
+    /// Applies to new subscribers unless the list they join overrides it.
+    pub opt_in: OptIn,
+    /// How long the preference center link at the bottom of an issue keeps working.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub preference_link_validity_days: u64,
+    pub spam_protection: SpamProtectionSettings,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct SpamProtectionSettings {
+    /// Forms submitted sooner than this after they were rendered are rejected.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub min_form_fill_seconds: u64,
+    /// Forms older than this have to be reloaded.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub form_token_max_age_hours: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_attempts_per_ip_per_hour: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_attempts_per_email_per_hour: u64,
+    /// Addresses at these domains, or their subdomains, cannot subscribe.
+    #[serde(default)]
+    pub disposable_email_domains: Vec<String>,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct PasswordHashingSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub memory_size_kib: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub iterations: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub parallelism: u32,
+}
+
+impl PasswordHashingSettings {
+    pub fn params(&self) -> Result<argon2::Params, argon2::Error> {
+        return argon2::Params::new(
+            self.memory_size_kib,
+            self.iterations,
+            self.parallelism,
+            None,
+        );
+    }
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -23,6 +115,7 @@ pub struct EmailClientSettings {
     pub sender_email: String,
     pub authorisation_token: Secret<String>,
     pub timeout_milliseconds: u64,
+    pub event_webhook_public_key: String,
 }
 
 impl EmailClientSettings {
@@ -34,6 +127,15 @@ impl EmailClientSettings {
         return std::time::Duration::from_millis(self.timeout_milliseconds);
     }
 
+    pub fn event_webhook_key(&self) -> Result<VerifyingKey, anyhow::Error> {
+        let der = STANDARD
+            .decode(&self.event_webhook_public_key)
+            .context("The event webhook public key is not valid base64")?;
+        return VerifyingKey::from_public_key_der(&der)
+            .map_err(|e| anyhow::anyhow!(e))
+            .context("The event webhook public key is not a valid P-256 public key");
+    }
+
     pub fn client(self) -> EmailClient {
         let sender_email = self.sender().expect("Invalid sender email address");
         let timeout = self.timeout();
@@ -50,9 +152,24 @@ impl EmailClientSettings {
 pub struct ApplicationSettings {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub port: u16,
+    /// `/metrics` is served on this port only, so it can be kept off the public network.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub metrics_port: u16,
     pub host: String,
     pub base_url: String,
     pub hmac_secret: Secret<String>,
+    /// How long in-flight requests and worker tasks get to finish after a shutdown signal.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub shutdown_grace_period_seconds: u64,
+    /// Reverse proxies whose `X-Forwarded-For` header is trusted to name the client.
+    #[serde(default)]
+    pub trusted_proxies: Vec<std::net::IpAddr>,
+}
+
+impl ApplicationSettings {
+    pub fn shutdown_grace_period(&self) -> std::time::Duration {
+        return std::time::Duration::from_secs(self.shutdown_grace_period_seconds);
+    }
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -0,0 +1,177 @@
+//! src/consent.rs
+
+use actix_web::http::header::USER_AGENT;
+use actix_web::HttpRequest;
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::client_ip::client_ip;
+use crate::domain::SubscriptionStatus;
+
+/// Where a subscriber's consent, or a change to it, came from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConsentSource {
+    /// The public subscription form.
+    Form,
+    /// An admin created, updated or deleted the subscriber through the API.
+    Api,
+    /// Imported through the API, having consented elsewhere.
+    Import,
+    /// The subscriber followed the link in their confirmation email.
+    ConfirmationLink,
+    /// The subscriber's preference center, linked from every issue.
+    PreferenceCenter,
+    /// A bounce or complaint reported by the email provider.
+    EmailProvider,
+}
+
+impl ConsentSource {
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            ConsentSource::Form => "form",
+            ConsentSource::Api => "api",
+            ConsentSource::Import => "import",
+            ConsentSource::ConfirmationLink => "confirmation_link",
+            ConsentSource::PreferenceCenter => "preference_center",
+            ConsentSource::EmailProvider => "email_provider",
+        };
+    }
+}
+
+/// The circumstances of a consent event, recorded alongside it.
+#[derive(Debug)]
+pub struct ConsentContext {
+    pub source: ConsentSource,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl ConsentContext {
+    pub fn from_request(source: ConsentSource, request: &HttpRequest) -> Self {
+        let ip_address = client_ip(request);
+        let user_agent = request
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+        return Self {
+            source,
+            ip_address,
+            user_agent,
+        };
+    }
+
+    /// For events that are not triggered by the subscriber's own request.
+    pub fn without_request(source: ConsentSource) -> Self {
+        return Self {
+            source,
+            ip_address: None,
+            user_agent: None,
+        };
+    }
+}
+
+pub struct ConsentEvent {
+    pub status: String,
+    pub source: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// Append to the subscriber's consent history. `status` is the status they are in afterwards.
+#[tracing::instrument(name = "Record a consent event", skip(transaction))]
+pub async fn record_consent_event(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    status: SubscriptionStatus,
+    context: &ConsentContext,
+) -> Result<(), sqlx::Error> {
+    return insert_consent_event(transaction, subscriber_id, status.as_str(), context).await;
+}
+
+/// Record the withdrawal of consent. Must happen before the subscriber is deleted, the
+/// history is kept afterwards.
+#[tracing::instrument(name = "Record an unsubscribe", skip(transaction))]
+pub async fn record_unsubscribe(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    context: &ConsentContext,
+) -> Result<(), sqlx::Error> {
+    return insert_consent_event(transaction, subscriber_id, "unsubscribed", context).await;
+}
+
+async fn insert_consent_event(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    status: &str,
+    context: &ConsentContext,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO consent_events (
+            consent_event_id,
+            subscriber_id,
+            email,
+            status,
+            source,
+            ip_address,
+            user_agent,
+            occurred_at
+        )
+        SELECT $1, id, email, $3, $4, $5, $6, now()
+        FROM subscriptions
+        WHERE id = $2
+        "#,
+        Uuid::new_v4(),
+        subscriber_id,
+        status,
+        context.source.as_str(),
+        context.ip_address,
+        context.user_agent
+    );
+    transaction.execute(query).await?;
+    return Ok(());
+}
+
+/// The consent history recorded for `email`, including from before the subscriber
+/// currently holding it changed to it, oldest first. Outlives unsubscribing.
+#[tracing::instrument(name = "Get the consent history of an address", skip(pool))]
+pub async fn get_consent_history_of_address(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Vec<ConsentEvent>, sqlx::Error> {
+    return sqlx::query_as!(
+        ConsentEvent,
+        r#"
+        SELECT status, source, ip_address, user_agent, occurred_at
+        FROM consent_events
+        WHERE email = $1 OR subscriber_id IN (SELECT id FROM subscriptions WHERE email = $1)
+        ORDER BY occurred_at, consent_event_id
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await;
+}
+
+/// The subscriber's consent history, oldest first.
+#[tracing::instrument(name = "Get a subscriber's consent history", skip(pool))]
+pub async fn get_consent_history(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<ConsentEvent>, sqlx::Error> {
+    return sqlx::query_as!(
+        ConsentEvent,
+        r#"
+        SELECT status, source, ip_address, user_agent, occurred_at
+        FROM consent_events
+        WHERE subscriber_id = $1
+        ORDER BY occurred_at, consent_event_id
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await;
+}
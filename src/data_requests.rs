@@ -0,0 +1,335 @@
+//! src/data_requests.rs
+
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::consent::get_consent_history_of_address;
+
+/// Everything stored about an email address, as handed out to answer a data subject
+/// access request.
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[schema(as = SubscriberDataExport)]
+pub struct SubscriberDataExport {
+    pub email: String,
+    /// Missing if the address is not, or no longer, subscribed.
+    pub subscriber: Option<ExportedSubscriber>,
+    pub subscription_tokens: Vec<String>,
+    /// How consent was obtained and every later change of status.
+    pub consent_history: Vec<ExportedConsentEvent>,
+    /// Issues that are waiting to be sent to the address.
+    pub queued_issues: Vec<Uuid>,
+    pub deliveries: Vec<ExportedDelivery>,
+    /// Bounces, complaints and other events reported by the email provider.
+    pub email_events: Vec<ExportedEmailEvent>,
+    /// Tracked opens and clicks.
+    pub engagements: Vec<ExportedEngagement>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ExportedSubscriber {
+    pub id: Uuid,
+    pub name: String,
+    pub status: String,
+    pub subscribed_at: String,
+    pub paused_until: Option<String>,
+    /// The address the subscriber asked to move to, until they confirm it.
+    pub pending_email_change: Option<String>,
+    pub lists: Vec<String>,
+    pub tags: Vec<String>,
+    pub attributes: BTreeMap<String, String>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ExportedConsentEvent {
+    pub status: String,
+    pub source: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub occurred_at: String,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ExportedDelivery {
+    pub newsletter_issue_id: Uuid,
+    pub outcome: String,
+    pub attempted_at: String,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ExportedEmailEvent {
+    pub newsletter_issue_id: Option<Uuid>,
+    pub event_type: String,
+    pub reason: Option<String>,
+    pub occurred_at: String,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ExportedEngagement {
+    pub newsletter_issue_id: Uuid,
+    pub kind: String,
+    pub url: Option<String>,
+    pub occurred_at: String,
+}
+
+fn format_timestamp(timestamp: DateTime<Utc>) -> String {
+    return timestamp.to_rfc3339_opts(SecondsFormat::Secs, true);
+}
+
+#[tracing::instrument(name = "Export a subscriber's data", skip(pool))]
+pub async fn export_subscriber_data(
+    pool: &PgPool,
+    email: &str,
+) -> Result<SubscriberDataExport, anyhow::Error> {
+    let subscriber = sqlx::query!(
+        r#"
+        SELECT
+            id,
+            name,
+            status,
+            subscribed_at,
+            paused_until,
+            (
+                SELECT new_email FROM email_change_requests
+                WHERE subscriber_id = subscriptions.id
+            ) AS pending_email_change,
+            ARRAY(
+                SELECT l.name FROM list_memberships m
+                JOIN lists l ON l.list_id = m.list_id
+                WHERE m.subscriber_id = subscriptions.id
+                ORDER BY l.name
+            ) AS "lists!",
+            ARRAY(
+                SELECT tag FROM subscriber_tags
+                WHERE subscriber_id = subscriptions.id
+                ORDER BY tag
+            ) AS "tags!",
+            ARRAY(
+                SELECT key FROM subscriber_attributes
+                WHERE subscriber_id = subscriptions.id
+                ORDER BY key
+            ) AS "attribute_keys!",
+            ARRAY(
+                SELECT value FROM subscriber_attributes
+                WHERE subscriber_id = subscriptions.id
+                ORDER BY key
+            ) AS "attribute_values!"
+        FROM subscriptions
+        WHERE email = $1
+        "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the subscriber")?;
+    let subscriber_id = subscriber.as_ref().map(|s| s.id);
+
+    let subscription_tokens = sqlx::query_scalar!(
+        "SELECT subscription_token FROM subscription_tokens WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the subscriber's tokens")?;
+    let consent_history = get_consent_history_of_address(pool, email)
+        .await
+        .context("Failed to retrieve the subscriber's consent history")?
+        .into_iter()
+        .map(|e| ExportedConsentEvent {
+            status: e.status,
+            source: e.source,
+            ip_address: e.ip_address,
+            user_agent: e.user_agent,
+            occurred_at: format_timestamp(e.occurred_at),
+        })
+        .collect();
+    let queued_issues = sqlx::query_scalar!(
+        "SELECT newsletter_issue_id FROM issue_delivery_queue WHERE subscriber_email = $1",
+        email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the subscriber's queued issues")?;
+    let deliveries = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, outcome, attempted_at
+        FROM issue_deliveries
+        WHERE subscriber_email = $1
+        ORDER BY attempted_at
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the subscriber's deliveries")?
+    .into_iter()
+    .map(|r| ExportedDelivery {
+        newsletter_issue_id: r.newsletter_issue_id,
+        outcome: r.outcome,
+        attempted_at: format_timestamp(r.attempted_at),
+    })
+    .collect();
+    let email_events = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, event_type, reason, occurred_at
+        FROM email_events
+        WHERE email = $1 OR subscriber_id = $2
+        ORDER BY occurred_at
+        "#,
+        email,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the subscriber's email events")?
+    .into_iter()
+    .map(|r| ExportedEmailEvent {
+        newsletter_issue_id: r.newsletter_issue_id,
+        event_type: r.event_type,
+        reason: r.reason,
+        occurred_at: format_timestamp(r.occurred_at),
+    })
+    .collect();
+    let engagements = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, kind, url, occurred_at
+        FROM issue_engagements
+        WHERE subscriber_id = $1
+        ORDER BY occurred_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve the subscriber's engagements")?
+    .into_iter()
+    .map(|r| ExportedEngagement {
+        newsletter_issue_id: r.newsletter_issue_id,
+        kind: r.kind,
+        url: r.url,
+        occurred_at: format_timestamp(r.occurred_at),
+    })
+    .collect();
+
+    return Ok(SubscriberDataExport {
+        email: email.to_string(),
+        subscriber: subscriber.map(|s| ExportedSubscriber {
+            id: s.id,
+            name: s.name,
+            status: s.status,
+            subscribed_at: format_timestamp(s.subscribed_at),
+            paused_until: s.paused_until.map(format_timestamp),
+            pending_email_change: s.pending_email_change,
+            lists: s.lists,
+            tags: s.tags,
+            attributes: s
+                .attribute_keys
+                .into_iter()
+                .zip(s.attribute_values)
+                .collect(),
+        }),
+        subscription_tokens,
+        consent_history,
+        queued_issues,
+        deliveries,
+        email_events,
+        engagements,
+    });
+}
+
+/// Delete everything stored about `email`, returning whether there was anything to delete.
+///
+/// Delivery outcomes and engagements are kept under a random identity instead, so the
+/// stats of past issues do not change. Suppressions are kept: they exist to make sure the
+/// address is never emailed again. This is the only way to delete from the consent
+/// history, which otherwise outlives unsubscribing.
+#[tracing::instrument(name = "Erase a subscriber", skip(transaction))]
+pub async fn erase_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+) -> Result<bool, anyhow::Error> {
+    let anonymous_id = Uuid::new_v4();
+    let mut n_affected = 0;
+
+    let subscriber_id = sqlx::query_scalar!(
+        "SELECT id FROM subscriptions WHERE email = $1 FOR UPDATE",
+        email
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to look up the subscriber")?;
+    if let Some(subscriber_id) = subscriber_id {
+        sqlx::query!(
+            "UPDATE issue_engagements SET subscriber_id = $2 WHERE subscriber_id = $1",
+            subscriber_id,
+            anonymous_id
+        )
+        .execute(&mut **transaction)
+        .await
+        .context("Failed to anonymise the subscriber's engagements")?;
+        sqlx::query!(
+            "DELETE FROM subscription_tokens WHERE subscriber_id = $1",
+            subscriber_id
+        )
+        .execute(&mut **transaction)
+        .await
+        .context("Failed to delete the subscriber's tokens")?;
+        // Lists, tags, attributes, email events and email change requests go with it
+        n_affected += sqlx::query!("DELETE FROM subscriptions WHERE id = $1", subscriber_id)
+            .execute(&mut **transaction)
+            .await
+            .context("Failed to delete the subscriber")?
+            .rows_affected();
+    }
+    sqlx::query!("SELECT set_config('app.erasing_subscriber', 'on', true)")
+        .fetch_one(&mut **transaction)
+        .await
+        .context("Failed to allow deleting from the consent history")?;
+    n_affected += sqlx::query!(
+        "DELETE FROM consent_events WHERE email = $1 OR subscriber_id = $2",
+        email,
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to delete the subscriber's consent history")?
+    .rows_affected();
+    sqlx::query!("SELECT set_config('app.erasing_subscriber', 'off', true)")
+        .fetch_one(&mut **transaction)
+        .await
+        .context("Failed to protect the consent history again")?;
+    n_affected += sqlx::query!(
+        "DELETE FROM issue_delivery_queue WHERE subscriber_email = $1",
+        email
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to delete the subscriber's pending deliveries")?
+    .rows_affected();
+    n_affected += sqlx::query!(
+        "UPDATE issue_deliveries SET subscriber_email = $2 WHERE subscriber_email = $1",
+        email,
+        format!("erased-{}", anonymous_id)
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to anonymise the subscriber's deliveries")?
+    .rows_affected();
+    n_affected += sqlx::query!("DELETE FROM email_events WHERE email = $1", email)
+        .execute(&mut **transaction)
+        .await
+        .context("Failed to delete the subscriber's email events")?
+        .rows_affected();
+    n_affected += sqlx::query!(
+        "DELETE FROM email_change_requests WHERE new_email = $1",
+        email
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to delete email change requests to the address")?
+    .rows_affected();
+    return Ok(n_affected > 0);
+}
@@ -1,9 +1,17 @@
 //! src/domain/mod.rs
 
 mod new_subscriber;
+mod opt_in;
+mod subscriber_attribute;
 mod subscriber_email;
 mod subscriber_name;
+mod subscriber_tag;
+mod subscription_status;
 
 pub use new_subscriber::NewSubscriber;
+pub use opt_in::OptIn;
+pub use subscriber_attribute::{AttributeKey, AttributeValue};
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
+pub use subscriber_tag::SubscriberTag;
+pub use subscription_status::SubscriptionStatus;
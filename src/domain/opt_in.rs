@@ -0,0 +1,54 @@
+//! src/domain/opt_in.rs
+
+/// Whether new subscribers have to confirm their address before they receive issues.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OptIn {
+    /// Subscribers are confirmed straight away.
+    Single,
+    /// Subscribers are sent a confirmation email and stay pending until they follow it.
+    Double,
+}
+
+impl OptIn {
+    pub const ALL: [OptIn; 2] = [OptIn::Single, OptIn::Double];
+
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            OptIn::Single => "single",
+            OptIn::Double => "double",
+        };
+    }
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        return Self::ALL
+            .into_iter()
+            .find(|opt_in| opt_in.as_str() == s)
+            .ok_or_else(|| format!("{} is not a valid opt-in mode", s));
+    }
+}
+
+impl std::fmt::Display for OptIn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return self.as_str().fmt(f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OptIn;
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn every_mode_round_trips_through_its_string_form() {
+        for opt_in in OptIn::ALL {
+            assert_ok_eq!(OptIn::parse(opt_in.as_str()), opt_in);
+        }
+    }
+
+    #[test]
+    fn unknown_modes_are_rejected() {
+        assert_err!(OptIn::parse("triple"));
+        assert_err!(OptIn::parse("Single"));
+    }
+}
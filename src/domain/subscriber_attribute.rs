@@ -0,0 +1,81 @@
+//! src/domain/subscriber_attribute.rs
+
+/// The name of a custom attribute, e.g. `plan` or `signup_source`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AttributeKey(String);
+
+impl AttributeKey {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let is_too_long = s.chars().count() > 64;
+        let has_invalid_characters = s
+            .chars()
+            .any(|c| !(c.is_ascii_alphanumeric() || ['-', '_', '.'].contains(&c)));
+        if s.is_empty() || is_too_long || has_invalid_characters {
+            return Err(format!("{} is not a valid attribute name", s));
+        } else {
+            return Ok(Self(s.to_string()));
+        }
+    }
+}
+
+impl AsRef<str> for AttributeKey {
+    fn as_ref(&self) -> &str {
+        return &self.0;
+    }
+}
+
+impl std::fmt::Display for AttributeKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return self.0.fmt(f);
+    }
+}
+
+/// The value of a custom attribute. Values are free text, compared exactly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttributeValue(String);
+
+impl AttributeValue {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        if s.chars().count() > 1024 {
+            return Err("Attribute values must be at most 1024 characters long".into());
+        }
+        return Ok(Self(s.to_string()));
+    }
+}
+
+impl AsRef<str> for AttributeValue {
+    fn as_ref(&self) -> &str {
+        return &self.0;
+    }
+}
+
+impl std::fmt::Display for AttributeValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return self.0.fmt(f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AttributeKey, AttributeValue};
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn identifier_like_keys_are_valid() {
+        assert_ok!(AttributeKey::parse("signup_source"));
+        assert_ok!(AttributeKey::parse("plan.tier-2"));
+    }
+
+    #[test]
+    fn empty_keys_or_keys_with_spaces_are_rejected() {
+        assert_err!(AttributeKey::parse(""));
+        assert_err!(AttributeKey::parse("signup source"));
+        assert_err!(AttributeKey::parse(&"a".repeat(65)));
+    }
+
+    #[test]
+    fn overlong_values_are_rejected() {
+        assert_ok!(AttributeValue::parse(""));
+        assert_err!(AttributeValue::parse(&"a".repeat(1025)));
+    }
+}
@@ -0,0 +1,55 @@
+//! src/domain/subscriber_tag.rs
+
+/// A label attached to a subscriber, normalised to lowercase.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubscriberTag(String);
+
+impl SubscriberTag {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim().to_lowercase();
+        let is_too_long = s.chars().count() > 64;
+        let has_invalid_characters = s
+            .chars()
+            .any(|c| !(c.is_alphanumeric() || ['-', '_', ':', '.'].contains(&c)));
+        if s.is_empty() || is_too_long || has_invalid_characters {
+            return Err(format!("{} is not a valid tag", s));
+        } else {
+            return Ok(Self(s));
+        }
+    }
+}
+
+impl AsRef<str> for SubscriberTag {
+    fn as_ref(&self) -> &str {
+        return &self.0;
+    }
+}
+
+impl std::fmt::Display for SubscriberTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return self.0.fmt(f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubscriberTag;
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn tags_are_trimmed_and_lowercased() {
+        assert_ok_eq!(SubscriberTag::parse(" Beta "), SubscriberTag("beta".into()));
+    }
+
+    #[test]
+    fn empty_tags_are_rejected() {
+        assert_err!(SubscriberTag::parse(""));
+        assert_err!(SubscriberTag::parse("  "));
+    }
+
+    #[test]
+    fn tags_with_spaces_or_punctuation_are_rejected() {
+        assert_err!(SubscriberTag::parse("early adopter"));
+        assert_err!(SubscriberTag::parse("<beta>"));
+    }
+}
@@ -1,8 +1,11 @@
 //! src/email_client.rs
 
 use crate::domain::SubscriberEmail;
+use crate::metrics::{EMAILS_FAILED_TOTAL, EMAILS_SENT_TOTAL, EMAIL_PROVIDER_DURATION_SECONDS};
 use reqwest::Client;
 use secrecy::{ExposeSecret, Secret};
+use std::collections::HashMap;
+use std::time::Instant;
 
 pub struct EmailClient {
     base_url: String,
@@ -31,6 +34,20 @@ impl EmailClient {
         subject: &str,
         html_content: &str,
         text_content: &str,
+    ) -> Result<(), reqwest::Error> {
+        return self
+            .send_email_with_custom_args(recipient, subject, html_content, text_content, &[])
+            .await;
+    }
+
+    /// SendGrid echoes `custom_args` back on every Event Webhook event for the email.
+    pub async fn send_email_with_custom_args(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        custom_args: &[(&str, &str)],
     ) -> Result<(), reqwest::Error> {
         let url = format!("{}/v3/mail/send", self.base_url);
         let request_body = SendEmailRequest {
@@ -42,6 +59,7 @@ impl EmailClient {
                 to: vec![Email {
                     email: recipient.as_ref(),
                 }],
+                custom_args: custom_args.iter().copied().collect(),
             }],
             subject,
             content: vec![
@@ -55,7 +73,9 @@ impl EmailClient {
                 },
             ],
         };
-        self.http_client
+        let start = Instant::now();
+        let result = self
+            .http_client
             .post(&url)
             .header(
                 "Authorization",
@@ -63,8 +83,23 @@ impl EmailClient {
             )
             .json(&request_body)
             .send()
-            .await?
-            .error_for_status()?;
+            .await
+            .and_then(|response| response.error_for_status());
+
+        let outcome = match result {
+            Ok(_) => {
+                EMAILS_SENT_TOTAL.inc();
+                "sent"
+            }
+            Err(_) => {
+                EMAILS_FAILED_TOTAL.inc();
+                "failed"
+            }
+        };
+        EMAIL_PROVIDER_DURATION_SECONDS
+            .with_label_values(&[outcome])
+            .observe(start.elapsed().as_secs_f64());
+        result?;
         return Ok(());
     }
 }
@@ -79,6 +114,8 @@ struct Email<'a> {
 #[derive(serde::Serialize)]
 struct Personalization<'a> {
     to: Vec<Email<'a>>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    custom_args: HashMap<&'a str, &'a str>,
 }
 
 #[derive(serde::Serialize)]
@@ -0,0 +1,78 @@
+//! src/idempotency/cleanup.rs
+
+use std::time::Duration;
+
+use anyhow::Context;
+use sqlx::postgres::types::PgInterval;
+use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
+
+use crate::configuration::{IdempotencySettings, Settings};
+use crate::metrics::IDEMPOTENCY_RECORDS_DELETED_TOTAL;
+use crate::shutdown::sleep_unless_shutdown;
+use crate::startup::get_connection_pool;
+
+/// Expired records pile up slowly, so there is no need to look for them often.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Delete up to `batch_size` records older than `retention`, returning how many were deleted.
+/// Records locked by a request that is reusing an expired key are skipped.
+#[tracing::instrument(skip(pool))]
+pub async fn delete_expired_records(
+    pool: &PgPool,
+    retention: Duration,
+    batch_size: i64,
+) -> Result<u64, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM idempotency
+        WHERE (owner_id, idempotency_key) IN (
+            SELECT owner_id, idempotency_key
+            FROM idempotency
+            WHERE created_at < now() - $1::interval
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+        )
+        "#,
+        PgInterval::try_from(retention).map_err(|e| anyhow::anyhow!(e))?,
+        batch_size
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete expired idempotency records")?;
+    IDEMPOTENCY_RECORDS_DELETED_TOTAL.inc_by(result.rows_affected());
+    return Ok(result.rows_affected());
+}
+
+async fn cleanup_loop(
+    pool: PgPool,
+    settings: IdempotencySettings,
+    shutdown: CancellationToken,
+) -> Result<(), anyhow::Error> {
+    let retention = settings.retention();
+    let batch_size = settings.cleanup_batch_size;
+    while !shutdown.is_cancelled() {
+        match delete_expired_records(&pool, retention, batch_size).await {
+            // A full batch means there could be more, so keep going straight away
+            Ok(n_deleted) if n_deleted as i64 >= batch_size => {}
+            Ok(_) => sleep_unless_shutdown(CLEANUP_INTERVAL, &shutdown).await,
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    "Failed to clean up expired idempotency records"
+                );
+                sleep_unless_shutdown(Duration::from_secs(10), &shutdown).await;
+            }
+        }
+    }
+    return Ok(());
+}
+
+pub async fn run_cleanup_until_stopped(
+    configuration: Settings,
+    shutdown: CancellationToken,
+) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    return cleanup_loop(connection_pool, configuration.idempotency, shutdown).await;
+}
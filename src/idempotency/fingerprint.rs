@@ -0,0 +1,44 @@
+//! src/idempotency/fingerprint.rs
+
+use sha2::{Digest, Sha256};
+
+/// A hash of everything that determines a request's outcome, so that a key reused for a
+/// different request can be told apart from a retry.
+pub fn request_fingerprint(method: &str, path: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    for part in [method.as_bytes(), path.as_bytes(), body] {
+        // Length-prefixed so that moving bytes between parts changes the hash
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    return hex::encode(hasher.finalize());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::request_fingerprint;
+
+    #[test]
+    fn the_same_request_has_the_same_fingerprint() {
+        assert_eq!(
+            request_fingerprint("POST", "/subscriptions", b"name=le%20guin"),
+            request_fingerprint("POST", "/subscriptions", b"name=le%20guin")
+        );
+    }
+
+    #[test]
+    fn a_different_body_has_a_different_fingerprint() {
+        assert_ne!(
+            request_fingerprint("POST", "/subscriptions", b"name=le%20guin"),
+            request_fingerprint("POST", "/subscriptions", b"name=ursula")
+        );
+    }
+
+    #[test]
+    fn the_same_body_sent_to_a_different_route_has_a_different_fingerprint() {
+        assert_ne!(
+            request_fingerprint("POST", "/subscriptions", b"{}"),
+            request_fingerprint("POST", "/api/v1/subscribers", b"{}")
+        );
+    }
+}
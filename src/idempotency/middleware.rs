@@ -0,0 +1,176 @@
+//! src/idempotency/middleware.rs
+
+use actix_http::h1;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderMap, CONTENT_TYPE};
+use actix_web::{web, HttpMessage, HttpRequest};
+use actix_web_lab::middleware::Next;
+use anyhow::Context;
+use sqlx::{PgPool, Postgres, Transaction};
+
+use super::{request_fingerprint, save_response, try_processing, IdempotencyError};
+use super::{IdempotencyKey, NextAction};
+use crate::authentication::UserId;
+use crate::configuration::IdempotencySettings;
+use crate::utils::e500;
+
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// The transaction an idempotent request is processed in, left in the request extensions
+/// for the handler to write its side effects in.
+struct ProcessingTransaction(Option<Transaction<'static, Postgres>>);
+
+#[derive(serde::Deserialize)]
+struct IdempotencyKeyField {
+    idempotency_key: Option<String>,
+}
+
+/// Make a mutating route idempotent: a request carrying a key it has already seen gets
+/// the saved response instead of running the handler again. The key is read from the
+/// `Idempotency-Key` header or, for forms, an `idempotency_key` field. Requests without
+/// a key are passed through, so routes that require one must still check for it.
+///
+/// Keys are scoped to the logged in user, so register this inside the authentication
+/// middleware (e.g. with `Route::wrap`) on routes that have one. Anonymous callers all
+/// share one namespace, so do not use it on public routes.
+///
+/// Handlers should write through [`begin_transaction`] and [`commit_transaction`], so
+/// their side effects are committed together with the saved response.
+pub async fn idempotent(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    return idempotent_with_replay_hook(req, next, || {}).await;
+}
+
+/// Like [`idempotent`], calling `on_replay` before a saved response is returned. Flash
+/// messages are added to a response after it has been saved, so this is the place to
+/// send them again.
+pub async fn idempotent_with_replay_hook(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+    on_replay: impl FnOnce(),
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let body = req.extract::<web::Bytes>().await?;
+    let idempotency_key = read_idempotency_key(req.headers(), &body)?;
+    // Put the body back for the handler to read
+    let (_, mut payload) = h1::Payload::create(true);
+    payload.unread_data(body.clone());
+    req.set_payload(payload.into());
+    let Some(idempotency_key) = idempotency_key else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .context("The database pool is not registered")
+        .map_err(e500)?
+        .clone();
+    let retention = req
+        .app_data::<web::Data<IdempotencySettings>>()
+        .context("The idempotency settings are not registered")
+        .map_err(e500)?
+        .retention();
+    let user_id = req.extensions().get::<UserId>().map(|user_id| **user_id);
+    let request_hash = request_fingerprint(req.method().as_str(), req.path(), &body);
+
+    let transaction =
+        match try_processing(&pool, &idempotency_key, user_id, &request_hash, retention).await? {
+            NextAction::StartProcessing(t) => t,
+            NextAction::ReturnSavedResponse(saved_response) => {
+                on_replay();
+                return Ok(req.into_response(saved_response));
+            }
+        };
+
+    req.extensions_mut()
+        .insert(ProcessingTransaction(Some(transaction)));
+    let response = next.call(req).await?;
+    let transaction = response
+        .request()
+        .extensions_mut()
+        .remove::<ProcessingTransaction>()
+        .and_then(|t| t.0);
+    // Dropping the transaction rolls back the handler's writes and releases the key, so
+    // the request can be retried. The handler drops it itself if it bails out.
+    let Some(transaction) = transaction else {
+        return Ok(response.map_into_boxed_body());
+    };
+    if response.status().is_server_error() {
+        return Ok(response.map_into_boxed_body());
+    }
+    let (request, response) = response.into_parts();
+    let response = save_response(
+        transaction,
+        &idempotency_key,
+        user_id,
+        response.map_into_boxed_body(),
+    )
+    .await
+    .map_err(e500)?;
+    return Ok(ServiceResponse::new(request, response));
+}
+
+/// Start the transaction a handler writes its side effects in. Behind [`idempotent`] this
+/// is the transaction the response will be saved in, so a retry either gets the saved
+/// response or finds that nothing was done.
+pub async fn begin_transaction(
+    request: &HttpRequest,
+    pool: &PgPool,
+) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+    let transaction = request
+        .extensions_mut()
+        .get_mut::<ProcessingTransaction>()
+        .and_then(|t| t.0.take());
+    return match transaction {
+        Some(transaction) => Ok(transaction),
+        None => pool.begin().await,
+    };
+}
+
+/// Finish a transaction started with [`begin_transaction`]: hand it back to the idempotency
+/// middleware to commit with the saved response, or commit it straight away.
+pub async fn commit_transaction(
+    request: &HttpRequest,
+    transaction: Transaction<'static, Postgres>,
+) -> Result<(), sqlx::Error> {
+    if let Some(processing) = request.extensions_mut().get_mut::<ProcessingTransaction>() {
+        processing.0 = Some(transaction);
+        return Ok(());
+    }
+    return transaction.commit().await;
+}
+
+fn read_idempotency_key(
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Option<IdempotencyKey>, IdempotencyError> {
+    let key = if let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) {
+        let value = value.to_str().map_err(|_| {
+            IdempotencyError::InvalidKey(format!(
+                "The '{}' header was not a valid UTF8 string",
+                IDEMPOTENCY_KEY_HEADER
+            ))
+        })?;
+        Some(value.to_string())
+    } else if is_form(headers) {
+        serde_urlencoded::from_bytes::<IdempotencyKeyField>(body)
+            .ok()
+            .and_then(|f| f.idempotency_key)
+    } else {
+        None
+    };
+
+    return key
+        .map(IdempotencyKey::try_from)
+        .transpose()
+        .map_err(|e| IdempotencyError::InvalidKey(e.to_string()));
+}
+
+fn is_form(headers: &HeaderMap) -> bool {
+    return headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/x-www-form-urlencoded"));
+}
@@ -1,7 +1,17 @@
 //! src/idempotency/mod.rs
 
+mod cleanup;
+mod fingerprint;
 mod key;
+mod middleware;
 mod persistence;
 
+pub use cleanup::{delete_expired_records, run_cleanup_until_stopped};
+pub use fingerprint::request_fingerprint;
 pub use key::IdempotencyKey;
-pub use persistence::{get_saved_response, save_response, try_processing, NextAction};
+pub use middleware::{
+    begin_transaction, commit_transaction, idempotent, idempotent_with_replay_hook,
+};
+pub use persistence::{
+    get_saved_response, save_response, try_processing, IdempotencyError, NextAction,
+};
@@ -2,13 +2,19 @@
 
 use actix_web::body::to_bytes;
 use actix_web::http::StatusCode;
-use actix_web::HttpResponse;
+use actix_web::{HttpResponse, ResponseError};
+use anyhow::Context;
+use sqlx::postgres::types::PgInterval;
 use sqlx::postgres::PgHasArrayType;
 use sqlx::Executor;
 use sqlx::{PgPool, Postgres, Transaction};
+use std::time::Duration;
 use uuid::Uuid;
 
 use super::IdempotencyKey;
+use crate::metrics::IDEMPOTENCY_REQUESTS_TOTAL;
+use crate::routes::error_chain_fmt;
+use crate::utils::json_error;
 
 #[derive(Debug, sqlx::Type)]
 #[sqlx(type_name = "header_pair")]
@@ -29,10 +35,15 @@ pub enum NextAction {
     ReturnSavedResponse(HttpResponse),
 }
 
+/// Records are scoped to the user making the request. Anonymous requests share the nil UUID.
+fn owner_id(user_id: Option<Uuid>) -> Uuid {
+    return user_id.unwrap_or_else(Uuid::nil);
+}
+
 pub async fn get_saved_response(
     pool: &PgPool,
     idempotency_key: &IdempotencyKey,
-    user_id: Uuid,
+    user_id: Option<Uuid>,
 ) -> Result<Option<HttpResponse>, anyhow::Error> {
     let saved_response = sqlx::query!(
         r#"
@@ -42,10 +53,11 @@ pub async fn get_saved_response(
             response_body as "response_body!"
         FROM idempotency
         WHERE
-            user_id = $1 AND
-            idempotency_key = $2
+            owner_id = $1 AND
+            idempotency_key = $2 AND
+            response_status_code IS NOT NULL
         "#,
-        user_id,
+        owner_id(user_id),
         idempotency_key.as_ref()
     )
     .fetch_optional(pool)
@@ -67,7 +79,7 @@ pub async fn get_saved_response(
 pub async fn save_response(
     mut transaction: Transaction<'static, Postgres>,
     idempotency_key: &IdempotencyKey,
-    user_id: Uuid,
+    user_id: Option<Uuid>,
     http_response: HttpResponse,
 ) -> Result<HttpResponse, anyhow::Error> {
     let (response_head, body) = http_response.into_parts();
@@ -92,10 +104,10 @@ pub async fn save_response(
             response_headers = $4,
             response_body = $5
         WHERE
-            user_id = $1 AND
+            owner_id = $1 AND
             idempotency_key = $2
         "#,
-            user_id,
+            owner_id(user_id),
             idempotency_key.as_ref(),
             status_code,
             headers,
@@ -108,33 +120,125 @@ pub async fn save_response(
     return Ok(http_response);
 }
 
+#[derive(thiserror::Error)]
+pub enum IdempotencyError {
+    #[error("The idempotency key has already been used for a different request")]
+    KeyReused,
+    #[error("A request with the same idempotency key is still being processed")]
+    RequestInProgress,
+    #[error("{0}")]
+    InvalidKey(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for IdempotencyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return error_chain_fmt(self, f);
+    }
+}
+
+impl ResponseError for IdempotencyError {
+    fn status_code(&self) -> StatusCode {
+        return match self {
+            IdempotencyError::KeyReused => StatusCode::UNPROCESSABLE_ENTITY,
+            IdempotencyError::RequestInProgress => StatusCode::CONFLICT,
+            IdempotencyError::InvalidKey(_) => StatusCode::BAD_REQUEST,
+            IdempotencyError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        return match self {
+            IdempotencyError::UnexpectedError(_) => {
+                json_error(self.status_code(), "Something went wrong")
+            }
+            _ => json_error(self.status_code(), self.to_string()),
+        };
+    }
+}
+
+/// A key whose record is older than `retention` is treated as new, even if the
+/// cleanup task has not deleted the record yet.
 pub async fn try_processing(
     pool: &PgPool,
     idempotency_key: &IdempotencyKey,
-    user_id: Uuid,
-) -> Result<NextAction, anyhow::Error> {
-    let mut transaction = pool.begin().await?;
+    user_id: Option<Uuid>,
+    request_hash: &str,
+    retention: Duration,
+) -> Result<NextAction, IdempotencyError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    // Held until the transaction ends. Without it, the insert below would wait for a
+    // concurrent request with the same key to finish instead of failing fast.
+    let is_locked = sqlx::query_scalar!(
+        r#"SELECT pg_try_advisory_xact_lock(hashtextextended($1::uuid || ':' || $2, 0)) AS "locked!""#,
+        owner_id(user_id),
+        idempotency_key.as_ref()
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .context("Failed to lock the idempotency key")?;
+    if !is_locked {
+        return Err(IdempotencyError::RequestInProgress);
+    }
+
     let query = sqlx::query!(
         r#"
         INSERT INTO idempotency (
             user_id,
             idempotency_key,
+            request_hash,
             created_at
         )
-        VALUES ($1, $2, now())
-        ON CONFLICT DO NOTHING
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (owner_id, idempotency_key) DO UPDATE
+        SET
+            request_hash = EXCLUDED.request_hash,
+            created_at = EXCLUDED.created_at,
+            response_status_code = NULL,
+            response_headers = NULL,
+            response_body = NULL
+        WHERE idempotency.created_at < now() - $4::interval
         "#,
         user_id,
-        idempotency_key.as_ref()
+        idempotency_key.as_ref(),
+        request_hash,
+        PgInterval::try_from(retention).map_err(|e| anyhow::anyhow!(e))?
     );
-    let n_inserted_rows = transaction.execute(query).await?.rows_affected();
+    let n_inserted_rows = transaction
+        .execute(query)
+        .await
+        .context("Failed to insert the idempotency key")?
+        .rows_affected();
 
     if n_inserted_rows > 0 {
+        IDEMPOTENCY_REQUESTS_TOTAL
+            .with_label_values(&["miss"])
+            .inc();
         return Ok(NextAction::StartProcessing(transaction));
-    } else {
-        let saved_response = get_saved_response(pool, idempotency_key, user_id)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("We expected a saved response, we didn't find it"))?;
-        return Ok(NextAction::ReturnSavedResponse(saved_response));
     }
+
+    let saved_hash = sqlx::query_scalar!(
+        "SELECT request_hash FROM idempotency WHERE owner_id = $1 AND idempotency_key = $2",
+        owner_id(user_id),
+        idempotency_key.as_ref()
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .context("Failed to retrieve the saved request hash")?;
+    if saved_hash.is_some_and(|saved_hash| saved_hash != request_hash) {
+        IDEMPOTENCY_REQUESTS_TOTAL
+            .with_label_values(&["rejected"])
+            .inc();
+        return Err(IdempotencyError::KeyReused);
+    }
+    // Only a request that gave up without rolling back leaves a record with no response
+    let saved_response = get_saved_response(pool, idempotency_key, user_id)
+        .await?
+        .ok_or(IdempotencyError::RequestInProgress)?;
+    IDEMPOTENCY_REQUESTS_TOTAL.with_label_values(&["hit"]).inc();
+    return Ok(NextAction::ReturnSavedResponse(saved_response));
 }
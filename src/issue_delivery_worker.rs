@@ -3,10 +3,21 @@
 use std::time::Duration;
 
 use crate::{
-    configuration::Settings, domain::SubscriberEmail, email_client::EmailClient,
+    configuration::Settings,
+    domain::SubscriberEmail,
+    email_client::EmailClient,
+    metrics::ISSUE_DELIVERIES_TOTAL,
+    preference_links::PreferenceLinks,
+    shutdown::sleep_unless_shutdown,
     startup::get_connection_pool,
+    suppressions::is_suppressed,
+    telemetry::link_to_trace_context,
+    tracking::Tracker,
+    webhooks::{enqueue_webhook_event, WebhookEvent},
+    worker_heartbeat::{Heartbeat, ISSUE_DELIVERY_WORKER},
 };
 use sqlx::{Executor, PgPool, Postgres, Transaction};
+use tokio_util::sync::CancellationToken;
 use tracing::{field::display, Span};
 use uuid::Uuid;
 
@@ -15,6 +26,24 @@ pub enum ExecutionOutcome {
     EmptyQueue,
 }
 
+/// What happened to a single delivery task, kept after the task is removed from the queue.
+#[derive(Copy, Clone, Debug)]
+pub enum DeliveryOutcome {
+    Delivered,
+    Failed,
+    Skipped,
+}
+
+impl DeliveryOutcome {
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            DeliveryOutcome::Delivered => "delivered",
+            DeliveryOutcome::Failed => "failed",
+            DeliveryOutcome::Skipped => "skipped",
+        };
+    }
+}
+
 #[tracing::instrument(
     skip_all,
     fields(
@@ -26,27 +55,50 @@ pub enum ExecutionOutcome {
 pub async fn try_execute_task(
     pool: &PgPool,
     email_client: &EmailClient,
+    tracker: &Tracker,
+    preference_links: &PreferenceLinks,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
     let task = dequeue_task(pool).await?;
     if task.is_none() {
         return Ok(ExecutionOutcome::EmptyQueue);
     }
 
-    let (transaction, issue_id, email) = task.unwrap();
+    let (mut transaction, issue_id, email) = task.unwrap();
 
     Span::current()
         .record("newsletter_issue_id", &display(issue_id))
         .record("subscriber_email", &display(&email));
 
-    match SubscriberEmail::parse(email.clone()) {
+    let outcome = match SubscriberEmail::parse(email.clone()) {
+        Ok(_) if is_suppressed(pool, &email).await? => {
+            tracing::info!("Skipping a confirmed subscriber. Their address is suppressed.");
+            DeliveryOutcome::Skipped
+        }
         Ok(email) => {
             let issue = get_issue(pool, issue_id).await?;
+            if let Some(trace_context) = &issue.trace_context {
+                link_to_trace_context(&Span::current(), trace_context);
+            }
+            let subscriber_id = get_subscriber_id(pool, email.as_ref()).await?;
+            let mut html_content = match subscriber_id {
+                Some(subscriber_id) if issue.tracking_enabled => {
+                    tracker.instrument_html(&issue.html_content, issue_id, subscriber_id)
+                }
+                _ => issue.html_content,
+            };
+            let mut text_content = issue.text_content;
+            if let Some(subscriber_id) = subscriber_id {
+                // Added after instrumenting, so the signed link is never sent through the click tracker
+                let preferences_url = preference_links.url(subscriber_id);
+                append_preferences_link(&mut html_content, &mut text_content, &preferences_url);
+            }
             if let Err(e) = email_client
-                .send_email(
+                .send_email_with_custom_args(
                     &email,
                     &issue.title,
-                    &issue.html_content,
-                    &issue.text_content,
+                    &html_content,
+                    &text_content,
+                    &[("newsletter_issue_id", &issue_id.to_string())],
                 )
                 .await
             {
@@ -55,6 +107,9 @@ pub async fn try_execute_task(
                     error.message = %e,
                     "Failed to deliver issue to a confirmed subscriber. Skipping."
                 );
+                DeliveryOutcome::Failed
+            } else {
+                DeliveryOutcome::Delivered
             }
         }
         Err(e) => {
@@ -62,11 +117,23 @@ pub async fn try_execute_task(
                 error.cause_chain = ?e,
                 error.message = %e,
                 "Skipping a confirmed subscriber. Their stored contact details are invalid."
-            )
+            );
+            DeliveryOutcome::Skipped
         }
-    }
+    };
 
+    record_delivery_outcome(&mut transaction, issue_id, &email, outcome).await?;
+    if let DeliveryOutcome::Failed = outcome {
+        let event = WebhookEvent::DeliveryFailed {
+            newsletter_issue_id: issue_id,
+            subscriber_email: email.clone(),
+        };
+        enqueue_webhook_event(&mut transaction, &event).await?;
+    }
     delete_task(transaction, issue_id, &email).await?;
+    ISSUE_DELIVERIES_TOTAL
+        .with_label_values(&[outcome.as_str()])
+        .inc();
 
     return Ok(ExecutionOutcome::TaskCompleted);
 }
@@ -100,6 +167,33 @@ async fn dequeue_task(
     }
 }
 
+#[tracing::instrument(skip_all)]
+async fn record_delivery_outcome(
+    transaction: &mut PgTransaction,
+    issue_id: Uuid,
+    email: &str,
+    outcome: DeliveryOutcome,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO issue_deliveries (
+            newsletter_issue_id,
+            subscriber_email,
+            outcome,
+            attempted_at
+        )
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET outcome = EXCLUDED.outcome, attempted_at = EXCLUDED.attempted_at
+        "#,
+        issue_id,
+        email,
+        outcome.as_str()
+    );
+    transaction.execute(query).await?;
+    return Ok(());
+}
+
 #[tracing::instrument(skip_all)]
 async fn delete_task(
     mut transaction: PgTransaction,
@@ -126,6 +220,8 @@ struct NewsletterIssue {
     title: String,
     text_content: String,
     html_content: String,
+    tracking_enabled: bool,
+    trace_context: Option<String>,
 }
 
 #[tracing::instrument(skip_all)]
@@ -133,7 +229,7 @@ async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, any
     let issue = sqlx::query_as!(
         NewsletterIssue,
         r#"
-        SELECT title, text_content, html_content
+        SELECT title, text_content, html_content, tracking_enabled, trace_context
         FROM newsletter_issues
         WHERE
             newsletter_issue_id = $1
@@ -146,22 +242,77 @@ async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, any
     return Ok(issue);
 }
 
-async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
-    loop {
-        match try_execute_task(&pool, &email_client).await {
+#[tracing::instrument(skip_all)]
+async fn get_subscriber_id(pool: &PgPool, email: &str) -> Result<Option<Uuid>, anyhow::Error> {
+    let subscriber = sqlx::query!("SELECT id FROM subscriptions WHERE email = $1", email)
+        .fetch_optional(pool)
+        .await?;
+    return Ok(subscriber.map(|s| s.id));
+}
+
+fn append_preferences_link(html: &mut String, text: &mut String, preferences_url: &str) {
+    let link = format!(
+        r#"<p><a href="{}">Manage your subscription</a></p>"#,
+        htmlescape::encode_minimal(preferences_url)
+    );
+    match html.rfind("</body>") {
+        Some(i) => html.insert_str(i, &link),
+        None => html.push_str(&link),
+    }
+    text.push_str(&format!(
+        "\n\nManage your subscription: {}",
+        preferences_url
+    ));
+}
+
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    tracker: Tracker,
+    preference_links: PreferenceLinks,
+    shutdown: CancellationToken,
+) -> Result<(), anyhow::Error> {
+    let mut heartbeat = Heartbeat::new(ISSUE_DELIVERY_WORKER);
+    let mut n_completed: u64 = 0;
+    // A task is never interrupted once dequeued: cancelling between sending the email
+    // and `delete_task` committing would deliver it again after the restart.
+    while !shutdown.is_cancelled() {
+        heartbeat.beat(&pool).await;
+        match try_execute_task(&pool, &email_client, &tracker, &preference_links).await {
             Ok(ExecutionOutcome::EmptyQueue) => {
-                tokio::time::sleep(Duration::from_secs(10)).await;
+                sleep_unless_shutdown(Duration::from_secs(10), &shutdown).await;
             }
             Err(_) => {
-                tokio::time::sleep(Duration::from_secs(1)).await;
+                sleep_unless_shutdown(Duration::from_secs(1), &shutdown).await;
             }
-            Ok(ExecutionOutcome::TaskCompleted) => {}
+            Ok(ExecutionOutcome::TaskCompleted) => n_completed += 1,
         }
     }
+    tracing::info!(n_completed, "Issue delivery worker drained");
+    return Ok(());
 }
 
-pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+pub async fn run_worker_until_stopped(
+    configuration: Settings,
+    shutdown: CancellationToken,
+) -> Result<(), anyhow::Error> {
     let connection_pool = get_connection_pool(&configuration.database);
     let email_client = configuration.email_client.client();
-    return worker_loop(connection_pool, email_client).await;
+    let tracker = Tracker::new(
+        configuration.application.base_url.clone(),
+        configuration.application.hmac_secret.clone(),
+    );
+    let preference_links = PreferenceLinks::new(
+        configuration.application.base_url,
+        configuration.application.hmac_secret,
+        configuration.subscriptions.preference_link_validity_days,
+    );
+    return worker_loop(
+        connection_pool,
+        email_client,
+        tracker,
+        preference_links,
+        shutdown,
+    )
+    .await;
 }
@@ -1,13 +1,28 @@
 //! lib.rs
 
 pub mod authentication;
+pub mod bootstrap;
+pub mod cli;
+pub mod client_ip;
 pub mod configuration;
+pub mod consent;
+pub mod data_requests;
 pub mod domain;
 pub mod email_client;
 pub mod idempotency;
 pub mod issue_delivery_worker;
+pub mod lists;
+pub mod metrics;
+pub mod preference_links;
 pub mod routes;
+pub mod segments;
 pub mod session_state;
+pub mod shutdown;
+pub mod spam_protection;
 pub mod startup;
+pub mod suppressions;
 pub mod telemetry;
+pub mod tracking;
 pub mod utils;
+pub mod webhooks;
+pub mod worker_heartbeat;
@@ -0,0 +1,217 @@
+//! src/lists.rs
+
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::domain::OptIn;
+
+/// A named list that subscribers can join and issues can be sent to.
+pub struct List {
+    pub list_id: Uuid,
+    pub name: String,
+    /// Overrides the configured opt-in mode for subscribers joining the list.
+    pub opt_in: Option<OptIn>,
+}
+
+fn parse_opt_in(opt_in: Option<String>) -> Result<Option<OptIn>, sqlx::Error> {
+    return opt_in
+        .as_deref()
+        .map(OptIn::parse)
+        .transpose()
+        .map_err(|e| sqlx::Error::Decode(e.into()));
+}
+
+/// The name of a list, as picked on the public subscription form.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ListName(String);
+
+impl ListName {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("The list name cannot be empty".into());
+        }
+        let max_length = 64;
+        if s.chars().count() > max_length {
+            return Err(format!(
+                "The list name must be at most {max_length} characters long"
+            ));
+        }
+        return Ok(Self(s.to_string()));
+    }
+}
+
+impl AsRef<str> for ListName {
+    fn as_ref(&self) -> &str {
+        return &self.0;
+    }
+}
+
+/// A list, and whether a given subscriber is a member of it.
+pub struct ListMembership {
+    pub list_id: Uuid,
+    pub name: String,
+    pub is_member: bool,
+}
+
+#[tracing::instrument(name = "Get lists", skip(pool))]
+pub async fn get_lists(pool: &PgPool) -> Result<Vec<List>, sqlx::Error> {
+    return sqlx::query!("SELECT list_id, name, opt_in FROM lists ORDER BY name")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|r| {
+            Ok(List {
+                list_id: r.list_id,
+                name: r.name,
+                opt_in: parse_opt_in(r.opt_in)?,
+            })
+        })
+        .collect();
+}
+
+/// Store a new list, returning `None` if one with the same name already exists.
+#[tracing::instrument(name = "Create a list", skip(pool))]
+pub async fn create_list(
+    pool: &PgPool,
+    name: &ListName,
+    opt_in: Option<OptIn>,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let list_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO lists (list_id, name, opt_in, created_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (name) DO NOTHING
+        RETURNING list_id
+        "#,
+        Uuid::new_v4(),
+        name.as_ref(),
+        opt_in.as_ref().map(OptIn::as_str)
+    )
+    .fetch_optional(pool)
+    .await?;
+    return Ok(list_id);
+}
+
+#[tracing::instrument(name = "Find a list by name", skip(executor))]
+pub async fn find_list_by_name<'e>(
+    executor: impl Executor<'e, Database = Postgres>,
+    name: &str,
+) -> Result<Option<List>, sqlx::Error> {
+    return sqlx::query!(
+        "SELECT list_id, name, opt_in FROM lists WHERE name = $1",
+        name
+    )
+    .fetch_optional(executor)
+    .await?
+    .map(|r| {
+        Ok(List {
+            list_id: r.list_id,
+            name: r.name,
+            opt_in: parse_opt_in(r.opt_in)?,
+        })
+    })
+    .transpose();
+}
+
+/// The subset of `list_ids` that does not refer to an existing list.
+#[tracing::instrument(name = "Find unknown lists", skip(executor))]
+pub async fn find_unknown_lists<'e>(
+    executor: impl Executor<'e, Database = Postgres>,
+    list_ids: &[Uuid],
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    return sqlx::query_scalar!(
+        r#"
+        SELECT id AS "id!"
+        FROM UNNEST($1::uuid[]) AS id
+        WHERE NOT EXISTS (SELECT 1 FROM lists WHERE list_id = id)
+        "#,
+        list_ids
+    )
+    .fetch_all(executor)
+    .await;
+}
+
+#[tracing::instrument(name = "Get a subscriber's lists", skip(pool))]
+pub async fn get_list_memberships(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<ListMembership>, sqlx::Error> {
+    return sqlx::query_as!(
+        ListMembership,
+        r#"
+        SELECT l.list_id, l.name, m.subscriber_id IS NOT NULL AS "is_member!"
+        FROM lists l
+        LEFT JOIN list_memberships m ON m.list_id = l.list_id AND m.subscriber_id = $1
+        ORDER BY l.name
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await;
+}
+
+/// Make the subscriber a member of exactly the lists in `list_ids`. Unknown lists are ignored.
+#[tracing::instrument(name = "Set a subscriber's lists", skip(transaction))]
+pub async fn set_list_memberships(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    list_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        "DELETE FROM list_memberships WHERE subscriber_id = $1 AND NOT list_id = ANY($2)",
+        subscriber_id,
+        list_ids
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (list_id, subscriber_id, joined_at)
+        SELECT list_id, $1, now() FROM lists WHERE list_id = ANY($2)
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+        list_ids
+    );
+    transaction.execute(query).await?;
+    return Ok(());
+}
+
+#[tracing::instrument(name = "Add a subscriber to a list", skip(transaction))]
+pub async fn add_to_list(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (list_id, subscriber_id, joined_at)
+        VALUES ($1, $2, now())
+        ON CONFLICT DO NOTHING
+        "#,
+        list_id,
+        subscriber_id
+    );
+    transaction.execute(query).await?;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ListName;
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn list_names_are_trimmed() {
+        assert_ok_eq!(
+            ListName::parse(" Weekly digest "),
+            ListName("Weekly digest".into())
+        );
+    }
+
+    #[test]
+    fn empty_or_overlong_names_are_rejected() {
+        assert_err!(ListName::parse("  "));
+        assert_err!(ListName::parse(&"a".repeat(65)));
+    }
+}
@@ -1,32 +1,198 @@
 //! main.rs
 
-use std::fmt::{Debug, Display};
-use tokio::task::JoinError;
+use std::future::Future;
+use std::time::{Duration, Instant};
 
-use zero2prod::configuration::get_configuration;
+use clap::Parser;
+use tokio::task::{JoinError, JoinHandle};
+use tokio_util::sync::CancellationToken;
+
+use zero2prod::cli::{add_user, migrate, queue_stats, reset_password, Cli, Command};
+use zero2prod::configuration::{get_configuration, Settings};
+use zero2prod::idempotency::run_cleanup_until_stopped;
 use zero2prod::issue_delivery_worker::run_worker_until_stopped;
-use zero2prod::startup::Application;
-use zero2prod::telemetry::{get_subscriber, init_subscriber};
+use zero2prod::shutdown::shutdown_signal;
+use zero2prod::startup::{get_connection_pool, Application, MetricsServer};
+use zero2prod::telemetry::{get_subscriber, init_subscriber, shutdown_tracing};
+use zero2prod::webhooks::run_webhook_worker_until_stopped;
+
+type Task = (&'static str, JoinHandle<Result<(), anyhow::Error>>);
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let subscriber = get_subscriber("zero2prod".into(), "info".into(), std::io::stdout);
-    init_subscriber(subscriber);
-
+    let cli = Cli::parse();
     let configuration = get_configuration().expect("Failed to read configuration");
-    let application = Application::build(configuration.clone()).await?;
-    let application_task = tokio::spawn(application.run_until_stopped());
-    let worker_task = tokio::spawn(run_worker_until_stopped(configuration));
+    if is_long_running(&cli.command) {
+        let subscriber = get_subscriber(
+            "zero2prod".into(),
+            "info".into(),
+            std::io::stdout,
+            &configuration.telemetry,
+        );
+        init_subscriber(subscriber);
+    } else {
+        // Keep stdout for the command's own output
+        let subscriber = get_subscriber(
+            "zero2prod".into(),
+            "warn,sqlx::postgres::notice=error".into(),
+            std::io::stderr,
+            &configuration.telemetry,
+        );
+        init_subscriber(subscriber);
+    }
 
-    tokio::select! {
-        o = application_task => report_exit("API", o),
-        o = worker_task => report_exit("Background worker", o),
+    let outcome = match cli.command {
+        None => run(configuration, true, true).await,
+        Some(Command::Serve) => run(configuration, true, false).await,
+        Some(Command::Worker) => run(configuration, false, true).await,
+        Some(command) => run_admin_command(command, configuration).await,
     };
+    shutdown_tracing();
+    return outcome;
+}
+
+fn is_long_running(command: &Option<Command>) -> bool {
+    return matches!(command, None | Some(Command::Serve | Command::Worker));
+}
 
+/// One-off administrative commands print their result and exit.
+async fn run_admin_command(command: Command, configuration: Settings) -> anyhow::Result<()> {
+    let pool = get_connection_pool(&configuration.database);
+    let hashing = &configuration.password_hashing;
+    match command {
+        Command::Migrate => {
+            let n_applied = migrate(&pool).await?;
+            println!("Applied {} migration(s)", n_applied);
+        }
+        Command::CreateUser { username, password } => {
+            let user_id = add_user(&username, password.read()?, &pool, hashing).await?;
+            println!("Created user {} ({})", username, user_id);
+        }
+        Command::ResetPassword { username, password } => {
+            reset_password(&username, password.read()?, &pool, hashing).await?;
+            println!("Reset the password of {}", username);
+        }
+        Command::QueueStats => print!("{}", queue_stats(&pool).await?),
+        Command::Serve | Command::Worker => {
+            unreachable!("Long-running commands are handled by `run`")
+        }
+    }
     return Ok(());
 }
 
-fn report_exit(task_name: &str, outcome: Result<Result<(), impl Debug + Display>, JoinError>) {
+/// Run the API and/or the workers until a shutdown signal arrives or one of them exits.
+async fn run(configuration: Settings, serve: bool, work: bool) -> anyhow::Result<()> {
+    let shutdown = CancellationToken::new();
+    let grace_period = configuration.application.shutdown_grace_period();
+    let mut tasks: Vec<Task> = Vec::new();
+    let mut server_handles = Vec::new();
+
+    if serve {
+        let application = Application::build(configuration.clone()).await?;
+        server_handles.push(application.server_handle());
+        tasks.push((
+            "API",
+            spawn_task(
+                async { Ok(application.run_until_stopped().await?) },
+                &shutdown,
+            ),
+        ));
+    }
+    // Started in every mode, so a worker-only process can still be scraped
+    let metrics_server = MetricsServer::build(&configuration)?;
+    server_handles.push(metrics_server.server_handle());
+    tasks.push((
+        "Metrics",
+        spawn_task(
+            async { Ok(metrics_server.run_until_stopped().await?) },
+            &shutdown,
+        ),
+    ));
+    if work {
+        tasks.push((
+            "Background worker",
+            spawn_task(
+                run_worker_until_stopped(configuration.clone(), shutdown.clone()),
+                &shutdown,
+            ),
+        ));
+        tasks.push((
+            "Webhook worker",
+            spawn_task(
+                run_webhook_worker_until_stopped(configuration.clone(), shutdown.clone()),
+                &shutdown,
+            ),
+        ));
+        tasks.push((
+            "Idempotency cleanup",
+            spawn_task(
+                run_cleanup_until_stopped(configuration, shutdown.clone()),
+                &shutdown,
+            ),
+        ));
+    }
+
+    tokio::select! {
+        _ = shutdown_signal() => {}
+        _ = shutdown.cancelled() => tracing::warn!("A task exited unexpectedly"),
+    };
+    shutdown.cancel();
+    tracing::info!(
+        grace_period_seconds = grace_period.as_secs(),
+        "Shutting down: no longer accepting requests, draining in-flight work"
+    );
+    drain(tasks, server_handles, grace_period).await;
+    return Ok(());
+}
+
+/// Trigger a shutdown of everything else if the task exits, whether it returns or panics.
+fn spawn_task(
+    task: impl Future<Output = Result<(), anyhow::Error>> + Send + 'static,
+    shutdown: &CancellationToken,
+) -> JoinHandle<Result<(), anyhow::Error>> {
+    let guard = shutdown.clone().drop_guard();
+    return tokio::spawn(async move {
+        let _guard = guard;
+        return task.await;
+    });
+}
+
+async fn drain(
+    mut tasks: Vec<Task>,
+    server_handles: Vec<actix_web::dev::ServerHandle>,
+    grace_period: Duration,
+) {
+    let started_at = Instant::now();
+    let drained = tokio::time::timeout(grace_period, async {
+        for server_handle in server_handles {
+            server_handle.stop(true).await;
+        }
+        for (name, task) in tasks.iter_mut() {
+            report_exit(name, task.await);
+        }
+    })
+    .await;
+
+    match drained {
+        Ok(()) => tracing::info!(
+            elapsed_milliseconds = started_at.elapsed().as_millis() as u64,
+            "Shutdown complete"
+        ),
+        Err(_) => {
+            let unfinished: Vec<&str> = tasks
+                .iter()
+                .filter(|(_, task)| !task.is_finished())
+                .map(|(name, _)| *name)
+                .collect();
+            tracing::error!(
+                ?unfinished,
+                "The shutdown grace period elapsed before every task finished. Exiting anyway"
+            );
+        }
+    }
+}
+
+fn report_exit(task_name: &str, outcome: Result<Result<(), anyhow::Error>, JoinError>) {
     match outcome {
         Ok(Ok(())) => {
             tracing::info!("{} has exited", task_name)
@@ -0,0 +1,136 @@
+//! src/metrics.rs
+
+use std::time::Instant;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web_lab::middleware::Next;
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, HistogramVec,
+    IntCounter, IntCounterVec,
+};
+
+pub static HTTP_REQUESTS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    return register_int_counter_vec!(
+        "http_requests_total",
+        "HTTP requests handled, by route and status code",
+        &["method", "route", "status"]
+    )
+    .unwrap();
+});
+
+pub static HTTP_REQUEST_DURATION_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    return register_histogram_vec!(
+        "http_request_duration_seconds",
+        "Time spent handling HTTP requests, by route",
+        &["method", "route"]
+    )
+    .unwrap();
+});
+
+pub static EMAILS_SENT_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    return register_int_counter!("emails_sent_total", "Emails accepted by the email provider")
+        .unwrap();
+});
+
+pub static EMAILS_FAILED_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    return register_int_counter!(
+        "emails_failed_total",
+        "Emails the email provider could not be asked to send"
+    )
+    .unwrap();
+});
+
+pub static EMAIL_PROVIDER_DURATION_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    return register_histogram_vec!(
+        "email_provider_request_duration_seconds",
+        "Time spent waiting on the email provider, by outcome",
+        &["outcome"]
+    )
+    .unwrap();
+});
+
+pub static ISSUE_DELIVERIES_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    return register_int_counter_vec!(
+        "issue_deliveries_total",
+        "Newsletter issue delivery tasks processed by the worker, by outcome",
+        &["outcome"]
+    )
+    .unwrap();
+});
+
+pub static IDEMPOTENCY_REQUESTS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+    return register_int_counter_vec!(
+        "idempotency_requests_total",
+        "Idempotent requests, split by whether a saved response was replayed",
+        &["result"]
+    )
+    .unwrap();
+});
+
+pub static IDEMPOTENCY_RECORDS_DELETED_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+    return register_int_counter!(
+        "idempotency_records_deleted_total",
+        "Expired idempotency records deleted by the cleanup task"
+    )
+    .unwrap();
+});
+
+/// Registers every metric up front, so they are exported before their first observation.
+pub fn init_metrics() {
+    Lazy::force(&HTTP_REQUESTS_TOTAL);
+    Lazy::force(&HTTP_REQUEST_DURATION_SECONDS);
+    Lazy::force(&EMAILS_SENT_TOTAL);
+    Lazy::force(&EMAILS_FAILED_TOTAL);
+    Lazy::force(&EMAIL_PROVIDER_DURATION_SECONDS);
+    Lazy::force(&ISSUE_DELIVERIES_TOTAL);
+    Lazy::force(&IDEMPOTENCY_REQUESTS_TOTAL);
+    Lazy::force(&IDEMPOTENCY_RECORDS_DELETED_TOTAL);
+}
+
+/// Records the count and latency of every request against the route pattern that matched it.
+///
+/// Using the pattern (`/admin/issues/{newsletter_issue_id}`) rather than the path keeps
+/// the number of time series bounded.
+pub async fn record_http_metrics(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let method = method_label(req.method());
+    let route = req
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+    let start = Instant::now();
+
+    let result = next.call(req).await;
+
+    let status = match &result {
+        Ok(response) => response.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    HTTP_REQUESTS_TOTAL
+        .with_label_values(&[method, &route, status.as_str()])
+        .inc();
+    HTTP_REQUEST_DURATION_SECONDS
+        .with_label_values(&[method, &route])
+        .observe(start.elapsed().as_secs_f64());
+    return result;
+}
+
+/// Clients can send any method name, so anything non-standard shares one label value.
+fn method_label(method: &Method) -> &'static str {
+    return match *method {
+        Method::GET => "GET",
+        Method::HEAD => "HEAD",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::PATCH => "PATCH",
+        Method::DELETE => "DELETE",
+        Method::OPTIONS => "OPTIONS",
+        Method::CONNECT => "CONNECT",
+        Method::TRACE => "TRACE",
+        _ => "other",
+    };
+}
@@ -0,0 +1,129 @@
+//! src/preference_links.rs
+
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+
+/// Builds and verifies the links to a subscriber's preference center, appended to
+/// every issue they receive.
+///
+/// The signature covers an expiry, so a leaked link stops working on its own rather
+/// than only when `hmac_secret` is rotated. Links are signed with a key derived for
+/// this purpose alone, so no other signature made with `hmac_secret`, e.g. a tracking
+/// link, can ever pass as one.
+#[derive(Clone)]
+pub struct PreferenceLinks {
+    base_url: String,
+    hmac_secret: Secret<String>,
+    validity: Duration,
+}
+
+impl PreferenceLinks {
+    pub fn new(base_url: String, hmac_secret: Secret<String>, validity_days: u64) -> Self {
+        return Self {
+            base_url,
+            hmac_secret,
+            validity: Duration::days(validity_days as i64),
+        };
+    }
+
+    /// A link that stays valid for the configured number of days from now.
+    pub fn url(&self, subscriber_id: Uuid) -> String {
+        return self.url_expiring_at(subscriber_id, (Utc::now() + self.validity).timestamp());
+    }
+
+    pub fn verify(
+        &self,
+        subscriber_id: Uuid,
+        expires: i64,
+        signature: &str,
+    ) -> Result<(), anyhow::Error> {
+        return self.verify_at(subscriber_id, expires, signature, Utc::now().timestamp());
+    }
+
+    fn url_expiring_at(&self, subscriber_id: Uuid, expires: i64) -> String {
+        return format!(
+            "{}/subscriptions/preferences?subscriber={}&expires={}&signature={}",
+            self.base_url,
+            subscriber_id,
+            expires,
+            self.sign(subscriber_id, expires)
+        );
+    }
+
+    fn verify_at(
+        &self,
+        subscriber_id: Uuid,
+        expires: i64,
+        signature: &str,
+        now: i64,
+    ) -> Result<(), anyhow::Error> {
+        let signature = hex::decode(signature)?;
+        let mut mac = self.mac();
+        mac.update(message(subscriber_id, expires).as_bytes());
+        mac.verify_slice(&signature)?;
+        if expires < now {
+            anyhow::bail!(
+                "This link has expired. Use the link at the bottom of a more recent issue."
+            );
+        }
+        return Ok(());
+    }
+
+    fn sign(&self, subscriber_id: Uuid, expires: i64) -> String {
+        let mut mac = self.mac();
+        mac.update(message(subscriber_id, expires).as_bytes());
+        return hex::encode(mac.finalize().into_bytes());
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        let mut derive =
+            Hmac::<Sha256>::new_from_slice(self.hmac_secret.expose_secret().as_bytes()).unwrap();
+        derive.update(b"preference_links");
+        return Hmac::<Sha256>::new_from_slice(&derive.finalize().into_bytes()).unwrap();
+    }
+}
+
+fn message(subscriber_id: Uuid, expires: i64) -> String {
+    return format!("{}:{}", subscriber_id, expires);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PreferenceLinks;
+    use claims::{assert_err, assert_ok};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn links() -> PreferenceLinks {
+        return PreferenceLinks::new(
+            "https://newsletter.example".into(),
+            Secret::new("secret".into()),
+            60,
+        );
+    }
+
+    fn signature(url: &str) -> String {
+        return url.rsplit_once("signature=").unwrap().1.to_string();
+    }
+
+    #[test]
+    fn links_are_accepted_until_they_expire() {
+        let subscriber = Uuid::new_v4();
+        let signature = signature(&links().url_expiring_at(subscriber, 1_000));
+
+        assert_ok!(links().verify_at(subscriber, 1_000, &signature, 1_000));
+        assert_err!(links().verify_at(subscriber, 1_000, &signature, 1_001));
+    }
+
+    #[test]
+    fn the_expiry_and_subscriber_are_signed() {
+        let subscriber = Uuid::new_v4();
+        let signature = signature(&links().url_expiring_at(subscriber, 1_000));
+
+        assert_err!(links().verify_at(subscriber, 2_000, &signature, 500));
+        assert_err!(links().verify_at(Uuid::new_v4(), 1_000, &signature, 500));
+    }
+}
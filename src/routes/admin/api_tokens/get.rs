@@ -0,0 +1,150 @@
+//! src/routes/admin/api_tokens/get.rs
+
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::authentication::{Scope, UserId};
+use crate::utils::e500;
+
+struct ApiTokenRecord {
+    api_token_id: Uuid,
+    name: String,
+    scopes: Vec<String>,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/api-tokens",
+    tag = "admin",
+    security(("session" = [])),
+    responses(
+        (status = 200, description = "The API token management page", body = String, content_type = "text/html"),
+        (status = 303, description = "Redirect to the login form when logged out", headers(("Location" = String)))
+    )
+)]
+pub async fn api_tokens_form(
+    flash_messages: IncomingFlashMessages,
+    user_id: web::ReqData<UserId>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+
+    let tokens = get_api_tokens(&pool, *user_id).await.map_err(e500)?;
+    let mut tokens_html = String::new();
+    for token in tokens {
+        let last_used = token
+            .last_used_at
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_else(|| "never".into());
+        let status = match token.revoked_at {
+            Some(revoked_at) => format!("revoked {}", revoked_at.to_rfc3339()),
+            None => format!(
+                r#"active
+            <form action="/admin/api-tokens/{}/revoke" method="post">
+                <button type="submit">Revoke</button>
+            </form>"#,
+                token.api_token_id
+            ),
+        };
+        writeln!(
+            tokens_html,
+            r#"<tr>
+        <td>{}</td>
+        <td>{}</td>
+        <td>{}</td>
+        <td>{}</td>
+        <td>{}</td>
+    </tr>"#,
+            htmlescape::encode_minimal(&token.name),
+            token.scopes.join(", "),
+            token.created_at.to_rfc3339(),
+            last_used,
+            status,
+        )
+        .unwrap();
+    }
+
+    let mut scopes_html = String::new();
+    for scope in Scope::ALL {
+        writeln!(
+            scopes_html,
+            r#"<label><input type="checkbox" name="scopes" value="{scope}"> {scope}</label><br>"#
+        )
+        .unwrap();
+    }
+
+    return Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>API Tokens</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    {msg_html}
+    <table>
+    <tr>
+        <th>Name</th>
+        <th>Scopes</th>
+        <th>Created</th>
+        <th>Last used</th>
+        <th>Status</th>
+    </tr>
+    {tokens_html}
+    </table>
+    <form action="/admin/api-tokens" method="post">
+        <label>
+            Name
+            <input type="text" placeholder="Enter a name for the token" name="name">
+        </label>
+        <br>
+        {scopes_html}
+        <button type="submit">Create token</button>
+    </form>
+    <p><a href="/admin/dashboard">‹ Back</a></p>
+</body>
+</html>"#
+        )));
+}
+
+#[tracing::instrument(name = "Get API tokens", skip(pool))]
+async fn get_api_tokens(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<ApiTokenRecord>, anyhow::Error> {
+    let tokens = sqlx::query_as!(
+        ApiTokenRecord,
+        r#"
+        SELECT api_token_id, name, scopes, created_at, last_used_at, revoked_at
+        FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to perform a query to retrieve API tokens")?;
+
+    return Ok(tokens);
+}
@@ -0,0 +1,9 @@
+//! src/routes/admin/api_tokens/mod.rs
+
+mod get;
+mod post;
+
+pub use get::{__path_api_tokens_form, api_tokens_form};
+pub use post::{
+    __path_create_api_token, __path_revoke_api_token, create_api_token, revoke_api_token,
+};
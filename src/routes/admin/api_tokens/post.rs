@@ -0,0 +1,154 @@
+//! src/routes/admin/api_tokens/post.rs
+
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use actix_web_lab::extract::UrlEncodedForm;
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::authentication::{generate_api_token, hash_api_token, Scope, UserId};
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = CreateApiTokenForm)]
+pub struct FormData {
+    name: String,
+    #[serde(default)]
+    scopes: Vec<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/api-tokens",
+    tag = "admin",
+    security(("session" = [])),
+    request_body(content = FormData, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "A page showing the new token once", body = String, content_type = "text/html"),
+        (status = 303, description = "Redirect back to the token page when the form is invalid", headers(("Location" = String)))
+    )
+)]
+#[tracing::instrument(name = "Create an API token", skip_all, fields(user_id=%&*user_id))]
+pub async fn create_api_token(
+    form: UrlEncodedForm<FormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let FormData { name, scopes } = form.into_inner();
+
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        FlashMessage::error("The API token must have a name").send();
+        return Ok(see_other("/admin/api-tokens"));
+    }
+
+    let scopes = match scopes
+        .into_iter()
+        .map(Scope::try_from)
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(scopes) if !scopes.is_empty() => scopes,
+        Ok(_) => {
+            FlashMessage::error("The API token must be granted at least one scope").send();
+            return Ok(see_other("/admin/api-tokens"));
+        }
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/api-tokens"));
+        }
+    };
+
+    let token = generate_api_token();
+    insert_api_token(&pool, *user_id, &name, &scopes, &token)
+        .await
+        .map_err(e500)?;
+
+    return Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>API Token Created</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    <p>Your new API token <b>{}</b> is shown below. Copy it now - it will not be shown again.</p>
+    <p><code id="api-token">{}</code></p>
+    <p><a href="/admin/api-tokens">‹ Back</a></p>
+</body>
+</html>"#,
+            htmlescape::encode_minimal(&name),
+            token.expose_secret(),
+        )));
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/api-tokens/{api_token_id}/revoke",
+    tag = "admin",
+    security(("session" = [])),
+    params(("api_token_id" = Uuid, Path, description = "The token to revoke")),
+    responses((status = 303, description = "Redirect back to the token page", headers(("Location" = String))))
+)]
+#[tracing::instrument(name = "Revoke an API token", skip(pool, user_id))]
+pub async fn revoke_api_token(
+    api_token_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let n_revoked = sqlx::query!(
+        r#"
+        UPDATE api_tokens
+        SET revoked_at = now()
+        WHERE api_token_id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        api_token_id.into_inner(),
+        *user_id
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to revoke API token")
+    .map_err(e500)?
+    .rows_affected();
+
+    if n_revoked > 0 {
+        FlashMessage::info("The API token has been revoked").send();
+    } else {
+        FlashMessage::error("The API token does not exist or was already revoked").send();
+    }
+    return Ok(see_other("/admin/api-tokens"));
+}
+
+#[tracing::instrument(name = "Store API token", skip(pool, token))]
+async fn insert_api_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    scopes: &[Scope],
+    token: &Secret<String>,
+) -> Result<Uuid, anyhow::Error> {
+    let api_token_id = Uuid::new_v4();
+    let scopes: Vec<String> = scopes.iter().map(|s| s.as_str().to_string()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO api_tokens (api_token_id, user_id, name, token_hash, scopes, created_at)
+        VALUES ($1, $2, $3, $4, $5, now())
+        "#,
+        api_token_id,
+        user_id,
+        name,
+        hash_api_token(token),
+        &scopes
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store API token")?;
+
+    return Ok(api_token_id);
+}
@@ -15,6 +15,16 @@ where
     return actix_web::error::ErrorInternalServerError(e);
 }
 
+#[utoipa::path(
+    get,
+    path = "/admin/dashboard",
+    tag = "admin",
+    security(("session" = [])),
+    responses(
+        (status = 200, description = "The admin dashboard", body = String, content_type = "text/html"),
+        (status = 303, description = "Redirect to the login form when logged out", headers(("Location" = String)))
+    )
+)]
 pub async fn admin_dashboard(
     user_id: web::ReqData<UserId>,
     pool: web::Data<PgPool>,
@@ -36,7 +46,15 @@ pub async fn admin_dashboard(
     <p>Available actions:</p>
     <ol>
         <li><a href="/admin/newsletters">Send a Newsletter Issue</a></li>
+        <li><a href="/admin/issues">View Published Issues</a></li>
+        <li><a href="/admin/subscribers">View Subscribers</a></li>
+        <li><a href="/admin/lists">Manage Subscriber Lists</a></li>
+        <li><a href="/admin/segments">Manage Subscriber Segments</a></li>
         <li><a href="/admin/password">Change Password</a></li>
+        <li><a href="/admin/api-tokens">Manage API Tokens</a></li>
+        <li><a href="/admin/webhooks">Manage Webhooks</a></li>
+        <li><a href="/admin/suppressions">Manage Suppression List</a></li>
+        <li><a href="/admin/data-requests">Handle Data Subject Requests</a></li>
         <li>
             <form name="logoutForm" action="/admin/logout" method="post">
                 <input type="submit" value="Logout">
@@ -0,0 +1,106 @@
+//! src/routes/admin/data_requests/get.rs
+
+use actix_web::http::header::{ContentType, CONTENT_DISPOSITION};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use std::fmt::Write;
+
+use crate::data_requests::{export_subscriber_data, SubscriberDataExport};
+use crate::domain::SubscriberEmail;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportParameters {
+    /// The address the data subject request is about.
+    email: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/data-requests",
+    tag = "admin",
+    security(("session" = [])),
+    responses(
+        (status = 200, description = "Forms to export or erase a subscriber's data", body = String, content_type = "text/html"),
+        (status = 303, description = "Redirect to the login form when logged out", headers(("Location" = String)))
+    )
+)]
+pub async fn data_requests_form(
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+
+    return Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>Data Subject Requests</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    {msg_html}
+    <form action="/admin/data-requests/export" method="get">
+        <label>
+            Email address
+            <input type="email" placeholder="someone@example.com" name="email">
+        </label>
+        <button type="submit">Export as JSON</button>
+    </form>
+    <p>Erasing deletes the subscriber and everything stored about them. Past issue stats are kept.</p>
+    <form action="/admin/data-requests/erase" method="post">
+        <label>
+            Email address
+            <input type="email" placeholder="someone@example.com" name="email">
+        </label>
+        <button type="submit">Erase</button>
+    </form>
+    <p><a href="/admin/dashboard">‹ Back</a></p>
+</body>
+</html>"#
+        )));
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/data-requests/export",
+    tag = "admin",
+    security(("session" = [])),
+    params(ExportParameters),
+    responses(
+        (status = 200, description = "Everything stored about the address", body = SubscriberDataExport),
+        (status = 303, description = "Redirect back to the form if the address is invalid, or to the login form when logged out", headers(("Location" = String)))
+    )
+)]
+#[tracing::instrument(name = "Export a subscriber's data on request", skip(parameters, pool))]
+pub async fn export_subscriber(
+    parameters: web::Query<ExportParameters>,
+    pool: web::Data<sqlx::PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = match SubscriberEmail::parse(parameters.0.email) {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/data-requests"));
+        }
+    };
+    let export = export_subscriber_data(&pool, email.as_ref())
+        .await
+        .map_err(e500)?;
+    return Ok(HttpResponse::Ok()
+        .insert_header((
+            CONTENT_DISPOSITION,
+            r#"attachment; filename="subscriber-data.json""#,
+        ))
+        .json(export));
+}
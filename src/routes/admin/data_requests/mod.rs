@@ -0,0 +1,9 @@
+//! src/routes/admin/data_requests/mod.rs
+
+mod get;
+mod post;
+
+pub use get::{
+    __path_data_requests_form, __path_export_subscriber, data_requests_form, export_subscriber,
+};
+pub use post::{__path_erase_subscriber_data, erase_subscriber_data};
@@ -0,0 +1,60 @@
+//! src/routes/admin/data_requests/post.rs
+
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::data_requests::erase_subscriber;
+use crate::domain::SubscriberEmail;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = EraseSubscriberForm)]
+pub struct FormData {
+    email: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/data-requests/erase",
+    tag = "admin",
+    security(("session" = [])),
+    request_body(content = FormData, content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirect back to the data subject request forms", headers(("Location" = String))))
+)]
+#[tracing::instrument(name = "Erase a subscriber's data on request", skip(form, pool))]
+pub async fn erase_subscriber_data(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = match SubscriberEmail::parse(form.0.email) {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/data-requests"));
+        }
+    };
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let erased = erase_subscriber(&mut transaction, email.as_ref())
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to erase a subscriber")
+        .map_err(e500)?;
+
+    let email = email.as_ref();
+    if erased {
+        FlashMessage::info(format!("Everything stored about {} has been erased", email)).send();
+    } else {
+        FlashMessage::error(format!("Nothing is stored about {}", email)).send();
+    }
+    return Ok(see_other("/admin/data-requests"));
+}
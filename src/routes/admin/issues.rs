@@ -0,0 +1,259 @@
+//! src/routes/admin/issues.rs
+
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::utils::e500;
+
+struct IssueSummaryRecord {
+    newsletter_issue_id: Uuid,
+    title: String,
+    published_at: DateTime<Utc>,
+}
+
+struct IssueStatsRecord {
+    title: String,
+    published_at: DateTime<Utc>,
+    tracking_enabled: bool,
+    delivered: i64,
+    failed: i64,
+    unique_opens: i64,
+    unique_clicks: i64,
+}
+
+struct LinkStatsRecord {
+    url: String,
+    clicks: i64,
+    unique_clicks: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/issues",
+    tag = "admin",
+    security(("session" = [])),
+    responses(
+        (status = 200, description = "The published newsletter issues", body = String, content_type = "text/html"),
+        (status = 303, description = "Redirect to the login form when logged out", headers(("Location" = String)))
+    )
+)]
+pub async fn issues_list(pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
+    let issues = sqlx::query_as!(
+        IssueSummaryRecord,
+        r#"
+        SELECT newsletter_issue_id, title, published_at
+        FROM newsletter_issues
+        ORDER BY published_at DESC
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to perform a query to retrieve newsletter issues")
+    .map_err(e500)?;
+
+    let mut issues_html = String::new();
+    for issue in issues {
+        writeln!(
+            issues_html,
+            r#"<li><a href="/admin/issues/{}">{}</a> ({})</li>"#,
+            issue.newsletter_issue_id,
+            htmlescape::encode_minimal(&issue.title),
+            issue.published_at.to_rfc3339(),
+        )
+        .unwrap();
+    }
+
+    return Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>Newsletter Issues</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    <ul>
+    {issues_html}
+    </ul>
+    <p><a href="/admin/dashboard">‹ Back</a></p>
+</body>
+</html>"#
+        )));
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/issues/{newsletter_issue_id}",
+    tag = "admin",
+    security(("session" = [])),
+    params(("newsletter_issue_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Delivery and engagement stats for the issue", body = String, content_type = "text/html"),
+        (status = 303, description = "Redirect to the login form when logged out", headers(("Location" = String))),
+        (status = 404, description = "No such newsletter issue")
+    )
+)]
+pub async fn issue_detail(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let Some(stats) = get_issue_stats(&pool, newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let engagement_html = if stats.tracking_enabled {
+        let links = get_link_stats(&pool, newsletter_issue_id)
+            .await
+            .map_err(e500)?;
+        let mut links_html = String::new();
+        for link in links {
+            writeln!(
+                links_html,
+                r#"<tr>
+        <td>{}</td>
+        <td>{}</td>
+        <td>{}</td>
+    </tr>"#,
+                htmlescape::encode_minimal(&link.url),
+                link.clicks,
+                link.unique_clicks,
+            )
+            .unwrap();
+        }
+        format!(
+            r#"<p>Unique opens: {}</p>
+    <p>Click-through rate: {}</p>
+    <table>
+    <tr>
+        <th>Link</th>
+        <th>Clicks</th>
+        <th>Unique clicks</th>
+    </tr>
+    {links_html}
+    </table>"#,
+            stats.unique_opens,
+            click_through_rate(stats.unique_clicks, stats.delivered),
+        )
+    } else {
+        "<p>Open and click tracking was not enabled for this issue.</p>".to_string()
+    };
+
+    return Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>{title}</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    <h1>{title}</h1>
+    <p>Published {published_at}</p>
+    <p>Delivered: {delivered}</p>
+    <p>Failed: {failed}</p>
+    {engagement_html}
+    <p><a href="/admin/issues">‹ Back</a></p>
+</body>
+</html>"#,
+            title = htmlescape::encode_minimal(&stats.title),
+            published_at = stats.published_at.to_rfc3339(),
+            delivered = stats.delivered,
+            failed = stats.failed,
+        )));
+}
+
+/// Share of delivered recipients who clicked at least one link.
+fn click_through_rate(unique_clicks: i64, delivered: i64) -> String {
+    if delivered == 0 {
+        return "n/a".to_string();
+    }
+    return format!("{:.1}%", 100.0 * unique_clicks as f64 / delivered as f64);
+}
+
+#[tracing::instrument(name = "Get newsletter issue stats", skip(pool))]
+async fn get_issue_stats(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<IssueStatsRecord>, anyhow::Error> {
+    let stats = sqlx::query_as!(
+        IssueStatsRecord,
+        r#"
+        SELECT
+            i.title,
+            i.published_at,
+            i.tracking_enabled,
+            (
+                SELECT COUNT(*) FROM issue_deliveries d
+                WHERE d.newsletter_issue_id = i.newsletter_issue_id AND d.outcome = 'delivered'
+            ) AS "delivered!",
+            (
+                SELECT COUNT(*) FROM issue_deliveries d
+                WHERE d.newsletter_issue_id = i.newsletter_issue_id AND d.outcome = 'failed'
+            ) AS "failed!",
+            (
+                SELECT COUNT(DISTINCT e.subscriber_id) FROM issue_engagements e
+                WHERE e.newsletter_issue_id = i.newsletter_issue_id AND e.kind = 'open'
+            ) AS "unique_opens!",
+            (
+                SELECT COUNT(DISTINCT e.subscriber_id) FROM issue_engagements e
+                WHERE e.newsletter_issue_id = i.newsletter_issue_id AND e.kind = 'click'
+            ) AS "unique_clicks!"
+        FROM newsletter_issues i
+        WHERE i.newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to compute newsletter issue stats")?;
+
+    return Ok(stats);
+}
+
+#[tracing::instrument(name = "Get newsletter issue link stats", skip(pool))]
+async fn get_link_stats(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Vec<LinkStatsRecord>, anyhow::Error> {
+    let links = sqlx::query_as!(
+        LinkStatsRecord,
+        r#"
+        SELECT
+            url AS "url!",
+            COUNT(*) AS "clicks!",
+            COUNT(DISTINCT subscriber_id) AS "unique_clicks!"
+        FROM issue_engagements
+        WHERE newsletter_issue_id = $1 AND kind = 'click'
+        GROUP BY url
+        ORDER BY 2 DESC, 1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to perform a query to compute newsletter issue link stats")?;
+
+    return Ok(links);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::click_through_rate;
+
+    #[test]
+    fn click_through_rate_is_relative_to_delivered_emails() {
+        assert_eq!(click_through_rate(1, 4), "25.0%");
+        assert_eq!(click_through_rate(0, 0), "n/a");
+    }
+}
@@ -0,0 +1,125 @@
+//! src/routes/admin/lists/get.rs
+
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::configuration::SubscriptionSettings;
+use crate::utils::e500;
+
+struct ListSummary {
+    list_id: Uuid,
+    name: String,
+    opt_in: Option<String>,
+    n_members: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/lists",
+    tag = "admin",
+    security(("session" = [])),
+    responses(
+        (status = 200, description = "The subscriber lists", body = String, content_type = "text/html"),
+        (status = 303, description = "Redirect to the login form when logged out", headers(("Location" = String)))
+    )
+)]
+pub async fn lists_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    settings: web::Data<SubscriptionSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+
+    let lists = get_list_summaries(&pool).await.map_err(e500)?;
+    let mut lists_html = String::new();
+    for list in lists {
+        writeln!(
+            lists_html,
+            r#"<tr>
+        <td>{}</td>
+        <td>{}</td>
+        <td>{}</td>
+        <td>{}</td>
+    </tr>"#,
+            htmlescape::encode_minimal(&list.name),
+            list.list_id,
+            list.opt_in.as_deref().unwrap_or("default"),
+            list.n_members,
+        )
+        .unwrap();
+    }
+
+    return Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>Subscriber Lists</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    {msg_html}
+    <p>Subscribers join a list by passing its name as <code>list</code> to <code>/subscriptions</code>.</p>
+    <table>
+    <tr>
+        <th>Name</th>
+        <th>Id</th>
+        <th>Opt-in</th>
+        <th>Members</th>
+    </tr>
+    {lists_html}
+    </table>
+    <form action="/admin/lists" method="post">
+        <label>
+            Name
+            <input type="text" placeholder="Weekly digest" name="name">
+        </label>
+        <label>
+            Opt-in
+            <select name="opt_in">
+                <option value="">Default ({default_opt_in})</option>
+                <option value="single">Single</option>
+                <option value="double">Double</option>
+            </select>
+        </label>
+        <button type="submit">Create list</button>
+    </form>
+    <p><a href="/admin/dashboard">‹ Back</a></p>
+</body>
+</html>"#,
+            default_opt_in = settings.opt_in,
+        )));
+}
+
+#[tracing::instrument(name = "Get list summaries", skip(pool))]
+async fn get_list_summaries(pool: &PgPool) -> Result<Vec<ListSummary>, anyhow::Error> {
+    let lists = sqlx::query_as!(
+        ListSummary,
+        r#"
+        SELECT l.list_id, l.name, l.opt_in, COUNT(m.subscriber_id) AS "n_members!"
+        FROM lists l
+        LEFT JOIN list_memberships m ON m.list_id = l.list_id
+        GROUP BY l.list_id
+        ORDER BY l.name
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to perform a query to retrieve lists")?;
+
+    return Ok(lists);
+}
@@ -0,0 +1,7 @@
+//! src/routes/admin/lists/mod.rs
+
+mod get;
+mod post;
+
+pub use get::{__path_lists_form, lists_form};
+pub use post::{__path_create_subscriber_list, create_subscriber_list};
@@ -0,0 +1,59 @@
+//! src/routes/admin/lists/post.rs
+
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::domain::OptIn;
+use crate::lists::{create_list, ListName};
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = CreateListForm)]
+pub struct FormData {
+    name: String,
+    /// `single` or `double`. Empty to use the configured mode.
+    #[serde(default)]
+    opt_in: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/lists",
+    tag = "admin",
+    security(("session" = [])),
+    request_body(content = FormData, content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirect back to the subscriber lists", headers(("Location" = String))))
+)]
+#[tracing::instrument(name = "Create a subscriber list", skip(form, pool))]
+pub async fn create_subscriber_list(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let parsed = ListName::parse(&form.name).and_then(|name| {
+        let opt_in = Some(form.opt_in.as_str())
+            .filter(|opt_in| !opt_in.is_empty())
+            .map(OptIn::parse)
+            .transpose()?;
+        return Ok((name, opt_in));
+    });
+    let (name, opt_in) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/lists"));
+        }
+    };
+
+    let created = create_list(pool.get_ref(), &name, opt_in)
+        .await
+        .context("Failed to store list")
+        .map_err(e500)?;
+    match created {
+        Some(_) => FlashMessage::info(format!("The list {} has been created", name.as_ref())),
+        None => FlashMessage::error(format!("A list named {} already exists", name.as_ref())),
+    }
+    .send();
+    return Ok(see_other("/admin/lists"));
+}
@@ -5,6 +5,13 @@ use crate::utils::see_other;
 use actix_web::HttpResponse;
 use actix_web_flash_messages::FlashMessage;
 
+#[utoipa::path(
+    post,
+    path = "/admin/logout",
+    tag = "admin",
+    security(("session" = [])),
+    responses((status = 303, description = "Redirect to the login form", headers(("Location" = String))))
+)]
 pub async fn log_out(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
     session.log_out();
     FlashMessage::info("You have successfully logged out").send();
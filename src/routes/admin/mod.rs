@@ -1,11 +1,27 @@
 //! src/routes/admin/mod.rs
 
+mod api_tokens;
 mod dashboard;
+mod data_requests;
+mod issues;
+mod lists;
 mod logout;
 mod newsletters;
 mod password;
+mod segments;
+mod subscribers;
+mod suppressions;
+mod webhooks;
 
-pub use dashboard::admin_dashboard;
+pub use api_tokens::*;
+pub use dashboard::{__path_admin_dashboard, admin_dashboard};
+pub use data_requests::*;
+pub use issues::*;
+pub use lists::*;
 pub use logout::*;
 pub use newsletters::*;
 pub use password::*;
+pub use segments::*;
+pub use subscribers::*;
+pub use suppressions::*;
+pub use webhooks::*;
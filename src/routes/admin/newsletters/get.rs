@@ -1,15 +1,73 @@
 //! src/routes/admin/newsletters/get.rs
 
-use actix_web::{http::header::ContentType, HttpResponse};
+use actix_web::{http::header::ContentType, web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use sqlx::PgPool;
 use std::fmt::Write;
 
+use super::post::count_issue_recipients;
+use crate::lists::get_lists;
+use crate::segments::get_segments;
+use crate::utils::e500;
+
+#[utoipa::path(
+    get,
+    path = "/admin/newsletters",
+    tag = "admin",
+    security(("session" = [])),
+    responses(
+        (status = 200, description = "The newsletter publishing form", body = String, content_type = "text/html"),
+        (status = 303, description = "Redirect to the login form when logged out", headers(("Location" = String)))
+    )
+)]
 pub async fn publish_newsletter_form(
     flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let mut msg_html = String::new();
     for m in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+    let lists = get_lists(&pool)
+        .await
+        .context("Failed to retrieve the lists")
+        .map_err(e500)?;
+    let mut lists_html = String::new();
+    for list in lists {
+        let n_recipients = count_issue_recipients(&pool, &[list.list_id], None)
+            .await
+            .context("Failed to count the list's recipients")
+            .map_err(e500)?;
+        writeln!(
+            lists_html,
+            r#"<label>
+        <input type="checkbox" name="lists" value="{}">
+        {} ({} recipients)
+      </label>
+      <br>"#,
+            list.list_id,
+            htmlescape::encode_minimal(&list.name),
+            n_recipients,
+        )
+        .unwrap();
+    }
+    let segments = get_segments(&pool).await.map_err(e500)?;
+    let mut segments_html = String::new();
+    for segment in segments {
+        writeln!(
+            segments_html,
+            r#"<option value="{}">{} ({} recipients)</option>"#,
+            segment.segment_id,
+            htmlescape::encode_minimal(&segment.name),
+            segment.n_recipients,
+        )
+        .unwrap();
     }
     let idempotency_key = uuid::Uuid::new_v4();
 
@@ -45,6 +103,21 @@ pub async fn publish_newsletter_form(
         <br>
       </label>
       <br>
+      <label>
+        <input type="checkbox" name="track_engagement" value="true">
+        Track opens and clicks
+      </label>
+      <br>
+      <p>Send to these lists only (everyone if none are picked):</p>
+      {lists_html}
+      <label>
+        Segment
+        <select name="segment">
+          <option value="">Everyone</option>
+          {segments_html}
+        </select>
+      </label>
+      <br>
       <input hidden type="text" name="idempotency_key" value="{idempotency_key}">
 
       <button type="submit">Send</button>
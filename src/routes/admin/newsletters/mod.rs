@@ -3,5 +3,8 @@
 mod get;
 mod post;
 
-pub use get::publish_newsletter_form;
-pub use post::publish_newsletter;
+pub use get::{__path_publish_newsletter_form, publish_newsletter_form};
+pub use post::{
+    __path_publish_newsletter, enqueue_delivery_tasks, idempotent_newsletter_submission,
+    insert_newsletter_issue, publish_newsletter,
+};
@@ -1,66 +1,138 @@
 //! src/routes/admin/newsletters/post.rs
 
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
 use actix_web::web::ReqData;
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
+use actix_web_lab::extract::UrlEncodedForm;
+use actix_web_lab::middleware::Next;
 use anyhow::Context;
 use sqlx::{Executor, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::authentication::UserId;
-use crate::idempotency::{save_response, try_processing, IdempotencyKey, NextAction};
+use crate::idempotency::{
+    begin_transaction, commit_transaction, idempotent_with_replay_hook, IdempotencyKey,
+};
+use crate::lists::find_unknown_lists;
+use crate::segments::segment_exists;
+use crate::telemetry::current_trace_context;
 use crate::utils::{e400, e500, see_other};
+use crate::webhooks::{enqueue_webhook_event, WebhookEvent};
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = PublishNewsletterForm)]
 pub struct FormData {
     title: String,
     text_content: String,
     html_content: String,
+    /// Deduplicates resubmissions of the form, see `idempotent_newsletter_submission`.
     idempotency_key: String,
+    /// Rewrite links and add an open pixel to measure engagement.
+    #[serde(default)]
+    track_engagement: bool,
+    /// Only send the issue to members of these lists. Everyone gets it if none are picked.
+    #[serde(default)]
+    lists: Vec<Uuid>,
+    /// Only send the issue to subscribers in this segment.
+    segment: Option<Uuid>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/admin/newsletters",
+    tag = "admin",
+    security(("session" = [])),
+    request_body(content = FormData, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirect back to the newsletter form", headers(("Location" = String))),
+        (status = 400, description = "The idempotency key is invalid or a list or segment does not exist"),
+        (status = 409, description = "A request with the same idempotency key is still being processed"),
+        (status = 422, description = "The idempotency key was already used for a different newsletter")
+    )
+)]
 #[tracing::instrument(name = "Publish a newsletter issue", skip_all, fields(user_id=%&*user_id))]
 pub async fn publish_newsletter(
-    form: web::Form<FormData>,
+    // Checkboxes for several lists repeat the `lists` field, which `web::Form` rejects
+    form: UrlEncodedForm<FormData>,
     pool: web::Data<PgPool>,
     user_id: ReqData<UserId>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let user_id = user_id.into_inner();
     let FormData {
         title,
         text_content,
         html_content,
         idempotency_key,
+        track_engagement,
+        lists,
+        segment,
     } = form.0;
-    let idempotency_key: IdempotencyKey = idempotency_key.try_into().map_err(e400)?;
-
-    let mut transaction = match try_processing(&pool, &idempotency_key, *user_id)
+    // Replays are answered by the idempotency middleware, but the key is still mandatory
+    IdempotencyKey::try_from(idempotency_key).map_err(e400)?;
+    let unknown_lists = find_unknown_lists(pool.get_ref(), &lists)
         .await
-        .map_err(e500)?
-    {
-        NextAction::StartProcessing(t) => t,
-        NextAction::ReturnSavedResponse(saved_response) => {
-            success_message().send();
-            return Ok(saved_response);
+        .context("Failed to look up the selected lists")
+        .map_err(e500)?;
+    if let Some(list_id) = unknown_lists.first() {
+        return Err(e400(format!("There is no list with id {}", list_id)));
+    }
+    if let Some(segment_id) = segment {
+        let exists = segment_exists(pool.get_ref(), segment_id)
+            .await
+            .context("Failed to look up the selected segment")
+            .map_err(e500)?;
+        if !exists {
+            return Err(e400(format!("There is no segment with id {}", segment_id)));
         }
-    };
+    }
 
-    let issue_id = insert_newsletter_issue(&mut transaction, &title, &text_content, &html_content)
+    let mut transaction = begin_transaction(&request, &pool)
         .await
-        .context("Failed to store newsletter issue details")
+        .context("Failed to acquire a Postgres connection from the pool")
         .map_err(e500)?;
 
-    enqueue_delivery_tasks(&mut transaction, issue_id)
+    let issue_id = insert_newsletter_issue(
+        &mut transaction,
+        &title,
+        &text_content,
+        &html_content,
+        track_engagement,
+    )
+    .await
+    .context("Failed to store newsletter issue details")
+    .map_err(e500)?;
+
+    enqueue_delivery_tasks(&mut transaction, issue_id, &lists, segment)
         .await
         .context("Failed to enqueue delivery tasks")
         .map_err(e500)?;
 
-    let response = see_other("/admin/newsletters");
-    let response = save_response(transaction, &idempotency_key, *user_id, response)
+    let event = WebhookEvent::IssuePublished {
+        newsletter_issue_id: issue_id,
+        title,
+    };
+    enqueue_webhook_event(&mut transaction, &event)
+        .await
+        .context("Failed to enqueue the issue published webhook")
+        .map_err(e500)?;
+
+    commit_transaction(&request, transaction)
         .await
+        .context("Failed to commit SQL transaction to store a new newsletter issue")
         .map_err(e500)?;
     success_message().send();
-    return Ok(response);
+    return Ok(see_other("/admin/newsletters"));
+}
+
+/// The idempotency middleware for `publish_newsletter`. A resubmitted form is answered
+/// with the saved redirect, so the success message has to be sent again.
+pub async fn idempotent_newsletter_submission(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    return idempotent_with_replay_hook(req, next, || success_message().send()).await;
 }
 
 fn success_message() -> FlashMessage {
@@ -70,13 +142,16 @@ fn success_message() -> FlashMessage {
 }
 
 #[tracing::instrument(skip_all)]
-async fn insert_newsletter_issue(
+pub async fn insert_newsletter_issue(
     transaction: &mut Transaction<'_, Postgres>,
     title: &str,
     text_content: &str,
     html_content: &str,
+    tracking_enabled: bool,
 ) -> Result<Uuid, sqlx::Error> {
     let newsletter_issue_id = Uuid::new_v4();
+    // Lets the delivery worker link its spans back to this request.
+    let trace_context = current_trace_context();
     let query = sqlx::query!(
         r#"
         INSERT INTO newsletter_issues (
@@ -84,23 +159,31 @@ async fn insert_newsletter_issue(
             title,
             text_content,
             html_content,
-            published_at
+            published_at,
+            tracking_enabled,
+            trace_context
         )
-        VALUES ($1, $2, $3, $4, now())
+        VALUES ($1, $2, $3, $4, now(), $5, $6)
         "#,
         newsletter_issue_id,
         title,
         text_content,
-        html_content
+        html_content,
+        tracking_enabled,
+        trace_context
     );
     transaction.execute(query).await?;
     return Ok(newsletter_issue_id);
 }
 
+/// Queue the issue for every confirmed subscriber who has not paused delivery, narrowed down to members of `list_ids`
+/// when it is not empty and to the members of `segment_id` when it is set.
 #[tracing::instrument(skip_all)]
-async fn enqueue_delivery_tasks(
+pub async fn enqueue_delivery_tasks(
     transaction: &mut Transaction<'_, Postgres>,
     newsletter_issue_id: Uuid,
+    list_ids: &[Uuid],
+    segment_id: Option<Uuid>,
 ) -> Result<(), sqlx::Error> {
     let query = sqlx::query!(
         r#"
@@ -109,12 +192,34 @@ async fn enqueue_delivery_tasks(
             subscriber_email
         )
         SELECT $1, email
-        FROM subscriptions
-        WHERE status = 'confirmed'
+        FROM issue_recipients($2, $3)
         "#,
-        newsletter_issue_id
+        newsletter_issue_id,
+        list_ids,
+        segment_id
     );
 
     transaction.execute(query).await?;
     return Ok(());
 }
+
+/// How many subscribers an issue sent to `list_ids` and `segment_id` would reach. Unlike
+/// the delivery queue, this leaves out suppressed addresses, which the delivery worker skips.
+#[tracing::instrument(skip(pool))]
+pub async fn count_issue_recipients(
+    pool: &PgPool,
+    list_ids: &[Uuid],
+    segment_id: Option<Uuid>,
+) -> Result<i64, sqlx::Error> {
+    return sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM issue_recipients($1, $2)
+        WHERE NOT email_is_suppressed(email)
+        "#,
+        list_ids,
+        segment_id
+    )
+    .fetch_one(pool)
+    .await;
+}
@@ -3,5 +3,5 @@
 mod get;
 mod post;
 
-pub use get::change_password_form;
-pub use post::change_password;
+pub use get::{__path_change_password_form, change_password_form};
+pub use post::{__path_change_password, change_password};
@@ -6,33 +6,45 @@ use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 
 use crate::authentication::UserId;
+use crate::configuration::PasswordHashingSettings;
 use crate::routes::admin::dashboard::get_username;
 use crate::{
-    authentication::{validate_credentials, AuthError, Credentials},
+    authentication::{
+        validate_credentials, validate_new_password, AuthError, Credentials, DummyPasswordHash,
+    },
     utils::{e500, see_other},
 };
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = ChangePasswordForm)]
 pub struct FormData {
+    #[schema(value_type = String, format = Password)]
     current_password: Secret<String>,
+    #[schema(value_type = String, format = Password)]
     new_password: Secret<String>,
+    #[schema(value_type = String, format = Password)]
     new_password_check: Secret<String>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/admin/password",
+    tag = "admin",
+    security(("session" = [])),
+    request_body(content = FormData, content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirect back to the change password form", headers(("Location" = String))))
+)]
 pub async fn change_password(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     user_id: web::ReqData<UserId>,
+    hashing: web::Data<PasswordHashingSettings>,
+    dummy_password_hash: web::Data<DummyPasswordHash>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
 
-    if form.0.new_password.expose_secret().len() <= 12 {
-        FlashMessage::error("New password must be longer than 12 characters").send();
-        return Ok(see_other("/admin/password"));
-    }
-
-    if form.0.new_password.expose_secret().len() >= 129 {
-        FlashMessage::error("New password must be shorter than 129 characters").send();
+    if let Err(e) = validate_new_password(&form.0.new_password) {
+        FlashMessage::error(e).send();
         return Ok(see_other("/admin/password"));
     }
 
@@ -50,7 +62,7 @@ pub async fn change_password(
         password: form.0.current_password,
     };
 
-    if let Err(e) = validate_credentials(credentials, &pool).await {
+    if let Err(e) = validate_credentials(credentials, &pool, &hashing, &dummy_password_hash).await {
         return match e {
             AuthError::InvalidCredentials(_) => {
                 FlashMessage::error("The current password is incorrect").send();
@@ -60,7 +72,7 @@ pub async fn change_password(
         };
     }
 
-    crate::authentication::change_password(*user_id, form.0.new_password, &pool)
+    crate::authentication::change_password(*user_id, form.0.new_password, &pool, &hashing)
         .await
         .map_err(e500)?;
     FlashMessage::error("Your password has been changed").send();
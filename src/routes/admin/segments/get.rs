@@ -0,0 +1,98 @@
+//! src/routes/admin/segments/get.rs
+
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::segments::get_segments;
+use crate::utils::e500;
+
+#[utoipa::path(
+    get,
+    path = "/admin/segments",
+    tag = "admin",
+    security(("session" = [])),
+    responses(
+        (status = 200, description = "The subscriber segments", body = String, content_type = "text/html"),
+        (status = 303, description = "Redirect to the login form when logged out", headers(("Location" = String)))
+    )
+)]
+pub async fn segments_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+
+    let segments = get_segments(&pool).await.map_err(e500)?;
+    let mut segments_html = String::new();
+    for segment in segments {
+        let rules = segment
+            .rules
+            .iter()
+            .map(|rule| htmlescape::encode_minimal(&rule.to_string()))
+            .collect::<Vec<_>>()
+            .join("<br>AND ");
+        writeln!(
+            segments_html,
+            r#"<tr>
+        <td>{}</td>
+        <td>{}</td>
+        <td>{}</td>
+    </tr>"#,
+            htmlescape::encode_minimal(&segment.name),
+            rules,
+            segment.n_recipients,
+        )
+        .unwrap();
+    }
+
+    return Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>Subscriber Segments</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    {msg_html}
+    <table>
+    <tr>
+        <th>Name</th>
+        <th>Rules</th>
+        <th>Confirmed subscribers</th>
+    </tr>
+    {segments_html}
+    </table>
+    <form action="/admin/segments" method="post">
+        <label>
+            Name
+            <input type="text" placeholder="Beta testers" name="name">
+        </label>
+        <br>
+        <label>
+            Rules, one per line (all of them must match)
+            <br>
+            <textarea name="rules" rows="5" cols="50" placeholder="tag = beta
+attribute plan = pro
+subscribed_at after 2024-01-01"></textarea>
+        </label>
+        <br>
+        <button type="submit">Create segment</button>
+    </form>
+    <p><a href="/admin/dashboard">‹ Back</a></p>
+</body>
+</html>"#
+        )));
+}
@@ -0,0 +1,7 @@
+//! src/routes/admin/segments/mod.rs
+
+mod get;
+mod post;
+
+pub use get::{__path_segments_form, segments_form};
+pub use post::{__path_create_subscriber_segment, create_subscriber_segment};
@@ -0,0 +1,50 @@
+//! src/routes/admin/segments/post.rs
+
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+use crate::segments::{create_segment, parse_rules, SegmentName};
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = CreateSegmentForm)]
+pub struct FormData {
+    name: String,
+    /// Rules separated by newlines or ` AND `, e.g. `tag = beta AND subscribed_at after 2024-01-01`.
+    rules: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/segments",
+    tag = "admin",
+    security(("session" = [])),
+    request_body(content = FormData, content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirect back to the subscriber segments", headers(("Location" = String))))
+)]
+#[tracing::instrument(name = "Create a subscriber segment", skip(form, pool))]
+pub async fn create_subscriber_segment(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let parsed =
+        SegmentName::parse(&form.name).and_then(|name| Ok((name, parse_rules(&form.rules)?)));
+    let (name, rules) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/segments"));
+        }
+    };
+
+    let created = create_segment(pool.get_ref(), &name, &rules)
+        .await
+        .map_err(e500)?;
+    match created {
+        Some(_) => FlashMessage::info(format!("The segment {} has been created", name.as_ref())),
+        None => FlashMessage::error(format!("A segment named {} already exists", name.as_ref())),
+    }
+    .send();
+    return Ok(see_other("/admin/segments"));
+}
@@ -0,0 +1,171 @@
+//! src/routes/admin/subscribers.rs
+
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::consent::get_consent_history;
+use crate::utils::e500;
+
+struct SubscriberSummaryRecord {
+    id: Uuid,
+    email: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+struct SubscriberRecord {
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/subscribers",
+    tag = "admin",
+    security(("session" = [])),
+    responses(
+        (status = 200, description = "The subscribers, newest first", body = String, content_type = "text/html"),
+        (status = 303, description = "Redirect to the login form when logged out", headers(("Location" = String)))
+    )
+)]
+pub async fn subscribers_list(pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
+    let subscribers = sqlx::query_as!(
+        SubscriberSummaryRecord,
+        r#"
+        SELECT id, email, status, subscribed_at
+        FROM subscriptions
+        ORDER BY subscribed_at DESC
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to perform a query to retrieve subscribers")
+    .map_err(e500)?;
+
+    let mut subscribers_html = String::new();
+    for subscriber in subscribers {
+        writeln!(
+            subscribers_html,
+            r#"<li><a href="/admin/subscribers/{}">{}</a> ({}, since {})</li>"#,
+            subscriber.id,
+            htmlescape::encode_minimal(&subscriber.email),
+            subscriber.status,
+            subscriber.subscribed_at.to_rfc3339(),
+        )
+        .unwrap();
+    }
+
+    return Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>Subscribers</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    <ul>
+    {subscribers_html}
+    </ul>
+    <p><a href="/admin/dashboard">‹ Back</a></p>
+</body>
+</html>"#
+        )));
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/subscribers/{subscriber_id}",
+    tag = "admin",
+    security(("session" = [])),
+    params(("subscriber_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "The subscriber and their consent history", body = String, content_type = "text/html"),
+        (status = 303, description = "Redirect to the login form when logged out", headers(("Location" = String))),
+        (status = 404, description = "No such subscriber")
+    )
+)]
+pub async fn subscriber_detail(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let Some(subscriber) = sqlx::query_as!(
+        SubscriberRecord,
+        "SELECT email, name, status, subscribed_at FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to perform a query to retrieve the subscriber")
+    .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let history = get_consent_history(&pool, subscriber_id)
+        .await
+        .context("Failed to retrieve the subscriber's consent history")
+        .map_err(e500)?;
+
+    let mut history_html = String::new();
+    for event in history {
+        writeln!(
+            history_html,
+            r#"<tr>
+        <td>{}</td>
+        <td>{}</td>
+        <td>{}</td>
+        <td>{}</td>
+        <td>{}</td>
+    </tr>"#,
+            event.occurred_at.to_rfc3339(),
+            event.status,
+            event.source,
+            htmlescape::encode_minimal(event.ip_address.as_deref().unwrap_or("-")),
+            htmlescape::encode_minimal(event.user_agent.as_deref().unwrap_or("-")),
+        )
+        .unwrap();
+    }
+
+    return Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>Subscriber</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    <h1>{email}</h1>
+    <p>Name: {name}</p>
+    <p>Status: {status}</p>
+    <p>Subscribed at: {subscribed_at}</p>
+    <h2>Consent history</h2>
+    <table>
+    <tr>
+        <th>When</th>
+        <th>Status</th>
+        <th>Source</th>
+        <th>IP address</th>
+        <th>User agent</th>
+    </tr>
+    {history_html}
+    </table>
+    <p><a href="/admin/subscribers">‹ Back</a></p>
+</body>
+</html>"#,
+            email = htmlescape::encode_minimal(&subscriber.email),
+            name = htmlescape::encode_minimal(&subscriber.name),
+            status = subscriber.status,
+            subscribed_at = subscriber.subscribed_at.to_rfc3339(),
+        )));
+}
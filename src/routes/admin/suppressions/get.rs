@@ -0,0 +1,117 @@
+//! src/routes/admin/suppressions/get.rs
+
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::utils::e500;
+
+struct SuppressionRecord {
+    suppression_id: Uuid,
+    pattern: String,
+    reason: String,
+    created_at: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/suppressions",
+    tag = "admin",
+    security(("session" = [])),
+    responses(
+        (status = 200, description = "The suppression list", body = String, content_type = "text/html"),
+        (status = 303, description = "Redirect to the login form when logged out", headers(("Location" = String)))
+    )
+)]
+pub async fn suppressions_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+
+    let suppressions = get_suppressions(&pool).await.map_err(e500)?;
+    let mut suppressions_html = String::new();
+    for suppression in suppressions {
+        writeln!(
+            suppressions_html,
+            r#"<tr>
+        <td>{}</td>
+        <td>{}</td>
+        <td>{}</td>
+        <td>
+            <form action="/admin/suppressions/{}/delete" method="post">
+                <button type="submit">Remove</button>
+            </form>
+        </td>
+    </tr>"#,
+            htmlescape::encode_minimal(&suppression.pattern),
+            suppression.reason,
+            suppression.created_at.to_rfc3339(),
+            suppression.suppression_id,
+        )
+        .unwrap();
+    }
+
+    return Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>Suppression List</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    {msg_html}
+    <p>No email is ever sent to these addresses or domains.</p>
+    <table>
+    <tr>
+        <th>Address or domain</th>
+        <th>Reason</th>
+        <th>Added</th>
+        <th></th>
+    </tr>
+    {suppressions_html}
+    </table>
+    <form action="/admin/suppressions" method="post">
+        <label>
+            Address or domain
+            <input type="text" placeholder="someone@example.com or example.com" name="pattern">
+        </label>
+        <button type="submit">Suppress</button>
+    </form>
+    <p><a href="/admin/dashboard">‹ Back</a></p>
+</body>
+</html>"#
+        )));
+}
+
+#[tracing::instrument(name = "Get suppressions", skip(pool))]
+async fn get_suppressions(pool: &PgPool) -> Result<Vec<SuppressionRecord>, anyhow::Error> {
+    let suppressions = sqlx::query_as!(
+        SuppressionRecord,
+        r#"
+        SELECT suppression_id, pattern, reason, created_at
+        FROM suppressions
+        ORDER BY created_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to perform a query to retrieve suppressions")?;
+
+    return Ok(suppressions);
+}
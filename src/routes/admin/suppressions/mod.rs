@@ -0,0 +1,10 @@
+//! src/routes/admin/suppressions/mod.rs
+
+mod get;
+mod post;
+
+pub use get::{__path_suppressions_form, suppressions_form};
+pub use post::{
+    __path_add_suppression_entry, __path_delete_suppression, add_suppression_entry,
+    delete_suppression,
+};
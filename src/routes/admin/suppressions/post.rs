@@ -0,0 +1,76 @@
+//! src/routes/admin/suppressions/post.rs
+
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::suppressions::{add_suppression, SuppressionPattern, SuppressionReason};
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = AddSuppressionForm)]
+pub struct FormData {
+    pattern: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/suppressions",
+    tag = "admin",
+    security(("session" = [])),
+    request_body(content = FormData, content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirect back to the suppression list", headers(("Location" = String))))
+)]
+#[tracing::instrument(name = "Manually suppress an address", skip(form, pool))]
+pub async fn add_suppression_entry(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let pattern = match SuppressionPattern::parse(&form.pattern) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/suppressions"));
+        }
+    };
+
+    add_suppression(pool.get_ref(), &pattern, SuppressionReason::Manual)
+        .await
+        .context("Failed to store suppression")
+        .map_err(e500)?;
+    FlashMessage::info(format!("{} has been suppressed", pattern.as_ref())).send();
+    return Ok(see_other("/admin/suppressions"));
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/suppressions/{suppression_id}/delete",
+    tag = "admin",
+    security(("session" = [])),
+    params(("suppression_id" = Uuid, Path, description = "The suppression to remove")),
+    responses((status = 303, description = "Redirect back to the suppression list", headers(("Location" = String))))
+)]
+#[tracing::instrument(name = "Remove a suppression", skip(pool))]
+pub async fn delete_suppression(
+    suppression_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let n_deleted = sqlx::query!(
+        "DELETE FROM suppressions WHERE suppression_id = $1",
+        suppression_id.into_inner()
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to delete suppression")
+    .map_err(e500)?
+    .rows_affected();
+
+    if n_deleted > 0 {
+        FlashMessage::info("The suppression has been removed").send();
+    } else {
+        FlashMessage::error("The suppression does not exist").send();
+    }
+    return Ok(see_other("/admin/suppressions"));
+}
@@ -0,0 +1,140 @@
+//! src/routes/admin/webhooks/get.rs
+
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::utils::e500;
+use crate::webhooks::WebhookEventType;
+
+struct WebhookEndpointRecord {
+    webhook_endpoint_id: Uuid,
+    url: String,
+    event_types: Vec<String>,
+    created_at: DateTime<Utc>,
+    pending_deliveries: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks",
+    tag = "admin",
+    security(("session" = [])),
+    responses(
+        (status = 200, description = "The webhook management page", body = String, content_type = "text/html"),
+        (status = 303, description = "Redirect to the login form when logged out", headers(("Location" = String)))
+    )
+)]
+pub async fn webhooks_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+
+    let endpoints = get_webhook_endpoints(&pool).await.map_err(e500)?;
+    let mut endpoints_html = String::new();
+    for endpoint in endpoints {
+        writeln!(
+            endpoints_html,
+            r#"<tr>
+        <td>{}</td>
+        <td>{}</td>
+        <td>{}</td>
+        <td>{}</td>
+        <td>
+            <form action="/admin/webhooks/{}/delete" method="post">
+                <button type="submit">Delete</button>
+            </form>
+        </td>
+    </tr>"#,
+            htmlescape::encode_minimal(&endpoint.url),
+            endpoint.event_types.join(", "),
+            endpoint.created_at.to_rfc3339(),
+            endpoint.pending_deliveries,
+            endpoint.webhook_endpoint_id,
+        )
+        .unwrap();
+    }
+
+    let mut event_types_html = String::new();
+    for event_type in WebhookEventType::ALL {
+        writeln!(
+            event_types_html,
+            r#"<label><input type="checkbox" name="event_types" value="{event_type}"> {event_type}</label><br>"#
+        )
+        .unwrap();
+    }
+
+    return Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>Webhooks</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    {msg_html}
+    <table>
+    <tr>
+        <th>URL</th>
+        <th>Events</th>
+        <th>Created</th>
+        <th>Pending deliveries</th>
+        <th></th>
+    </tr>
+    {endpoints_html}
+    </table>
+    <form action="/admin/webhooks" method="post">
+        <label>
+            URL
+            <input type="text" placeholder="https://example.com/webhooks" name="url">
+        </label>
+        <br>
+        {event_types_html}
+        <button type="submit">Add endpoint</button>
+    </form>
+    <p><a href="/admin/dashboard">‹ Back</a></p>
+</body>
+</html>"#
+        )));
+}
+
+#[tracing::instrument(name = "Get webhook endpoints", skip(pool))]
+async fn get_webhook_endpoints(pool: &PgPool) -> Result<Vec<WebhookEndpointRecord>, anyhow::Error> {
+    let endpoints = sqlx::query_as!(
+        WebhookEndpointRecord,
+        r#"
+        SELECT
+            e.webhook_endpoint_id,
+            e.url,
+            e.event_types,
+            e.created_at,
+            (
+                SELECT COUNT(*) FROM webhook_delivery_queue q
+                WHERE q.webhook_endpoint_id = e.webhook_endpoint_id
+            ) AS "pending_deliveries!"
+        FROM webhook_endpoints e
+        ORDER BY e.created_at DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to perform a query to retrieve webhook endpoints")?;
+
+    return Ok(endpoints);
+}
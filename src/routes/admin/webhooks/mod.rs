@@ -0,0 +1,10 @@
+//! src/routes/admin/webhooks/mod.rs
+
+mod get;
+mod post;
+
+pub use get::{__path_webhooks_form, webhooks_form};
+pub use post::{
+    __path_create_webhook_endpoint, __path_delete_webhook_endpoint, create_webhook_endpoint,
+    delete_webhook_endpoint,
+};
@@ -0,0 +1,144 @@
+//! src/routes/admin/webhooks/post.rs
+
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use actix_web_lab::extract::UrlEncodedForm;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::utils::{e500, see_other};
+use crate::webhooks::{generate_webhook_secret, WebhookEventType};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = CreateWebhookEndpointForm)]
+pub struct FormData {
+    url: String,
+    #[serde(default)]
+    event_types: Vec<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/webhooks",
+    tag = "admin",
+    security(("session" = [])),
+    request_body(content = FormData, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "A page showing the new endpoint's signing secret once", body = String, content_type = "text/html"),
+        (status = 303, description = "Redirect back to the webhook page when the form is invalid", headers(("Location" = String)))
+    )
+)]
+#[tracing::instrument(name = "Create a webhook endpoint", skip_all, fields(url = %form.url))]
+pub async fn create_webhook_endpoint(
+    form: UrlEncodedForm<FormData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData { url, event_types } = form.into_inner();
+
+    let url = match reqwest::Url::parse(url.trim()) {
+        Ok(url) if ["http", "https"].contains(&url.scheme()) => url,
+        _ => {
+            FlashMessage::error("The webhook URL must be a valid http(s) URL").send();
+            return Ok(see_other("/admin/webhooks"));
+        }
+    };
+
+    let event_types = match event_types
+        .into_iter()
+        .map(WebhookEventType::try_from)
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(event_types) if !event_types.is_empty() => event_types,
+        Ok(_) => {
+            FlashMessage::error("The webhook endpoint must subscribe to at least one event").send();
+            return Ok(see_other("/admin/webhooks"));
+        }
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/webhooks"));
+        }
+    };
+
+    let secret = generate_webhook_secret();
+    insert_webhook_endpoint(&pool, url.as_str(), &event_types, &secret)
+        .await
+        .map_err(e500)?;
+
+    return Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>Webhook Endpoint Created</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    <p>Requests to <b>{}</b> are signed with the secret below. Copy it now - it will not be shown again.</p>
+    <p><code id="webhook-secret">{}</code></p>
+    <p><a href="/admin/webhooks">‹ Back</a></p>
+</body>
+</html>"#,
+            htmlescape::encode_minimal(url.as_str()),
+            secret,
+        )));
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/webhooks/{webhook_endpoint_id}/delete",
+    tag = "admin",
+    security(("session" = [])),
+    params(("webhook_endpoint_id" = Uuid, Path, description = "The endpoint to delete")),
+    responses((status = 303, description = "Redirect back to the webhook page", headers(("Location" = String))))
+)]
+#[tracing::instrument(name = "Delete a webhook endpoint", skip(pool))]
+pub async fn delete_webhook_endpoint(
+    webhook_endpoint_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let n_deleted = sqlx::query!(
+        "DELETE FROM webhook_endpoints WHERE webhook_endpoint_id = $1",
+        webhook_endpoint_id.into_inner()
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to delete webhook endpoint")
+    .map_err(e500)?
+    .rows_affected();
+
+    if n_deleted > 0 {
+        FlashMessage::info("The webhook endpoint has been deleted").send();
+    } else {
+        FlashMessage::error("The webhook endpoint does not exist").send();
+    }
+    return Ok(see_other("/admin/webhooks"));
+}
+
+#[tracing::instrument(name = "Store webhook endpoint", skip(pool, secret))]
+async fn insert_webhook_endpoint(
+    pool: &PgPool,
+    url: &str,
+    event_types: &[WebhookEventType],
+    secret: &str,
+) -> Result<Uuid, anyhow::Error> {
+    let webhook_endpoint_id = Uuid::new_v4();
+    let event_types: Vec<String> = event_types.iter().map(|e| e.as_str().to_string()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO webhook_endpoints (webhook_endpoint_id, url, secret, event_types, created_at)
+        VALUES ($1, $2, $3, $4, now())
+        "#,
+        webhook_endpoint_id,
+        url,
+        secret,
+        &event_types
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store webhook endpoint")?;
+
+    return Ok(webhook_endpoint_id);
+}
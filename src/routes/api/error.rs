@@ -0,0 +1,82 @@
+//! src/routes/api/error.rs
+
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, ResponseError};
+
+use crate::authentication::{ApiToken, Scope};
+use crate::routes::{error_chain_fmt, SubscribeError};
+use crate::utils::json_error;
+
+#[derive(thiserror::Error)]
+pub enum ApiError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("The API token is missing the `{0}` scope")]
+    MissingScope(Scope),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return error_chain_fmt(self, f);
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        return match self {
+            ApiError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::MissingScope(_) => StatusCode::FORBIDDEN,
+            ApiError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        return match self {
+            ApiError::UnexpectedError(_) => json_error(self.status_code(), "Something went wrong"),
+            _ => json_error(self.status_code(), self.to_string()),
+        };
+    }
+}
+
+impl From<SubscribeError> for ApiError {
+    fn from(e: SubscribeError) -> Self {
+        return match e {
+            SubscribeError::ValidationError(e) => ApiError::ValidationError(e),
+            SubscribeError::Suppressed => ApiError::ValidationError(e.to_string()),
+            // Only the public subscribe form is rate limited
+            SubscribeError::RateLimited => {
+                ApiError::UnexpectedError(anyhow::anyhow!(e.to_string()))
+            }
+            SubscribeError::UnexpectedError(e) => ApiError::UnexpectedError(e),
+        };
+    }
+}
+
+pub fn require_scope(api_token: &ApiToken, scope: Scope) -> Result<(), ApiError> {
+    if api_token.has_scope(scope) {
+        return Ok(());
+    } else {
+        return Err(ApiError::MissingScope(scope));
+    }
+}
+
+/// Extractor configurations that report malformed requests using the API error body.
+pub fn json_config() -> web::JsonConfig {
+    return web::JsonConfig::default()
+        .error_handler(|e, _| ApiError::ValidationError(e.to_string()).into());
+}
+
+pub fn query_config() -> web::QueryConfig {
+    return web::QueryConfig::default()
+        .error_handler(|e, _| ApiError::ValidationError(e.to_string()).into());
+}
+
+pub fn path_config() -> web::PathConfig {
+    return web::PathConfig::default()
+        .error_handler(|e, _| ApiError::ValidationError(e.to_string()).into());
+}
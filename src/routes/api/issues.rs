@@ -0,0 +1,309 @@
+//! src/routes/api/issues.rs
+
+use actix_web::http::header::HeaderMap;
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::{require_scope, ApiError, Cursor, Page, PageParameters};
+use crate::authentication::{ApiToken, Scope};
+use crate::idempotency::{begin_transaction, commit_transaction, IdempotencyKey};
+use crate::routes::{enqueue_delivery_tasks, insert_newsletter_issue};
+use crate::utils::ErrorBody;
+use crate::webhooks::{enqueue_webhook_event, WebhookEvent};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct IssueData {
+    title: String,
+    text_content: String,
+    html_content: String,
+    /// Rewrite links and add an open pixel to measure engagement.
+    #[serde(default)]
+    track_engagement: bool,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct PublishedIssue {
+    newsletter_issue_id: Uuid,
+}
+
+struct IssueSummaryRecord {
+    newsletter_issue_id: Uuid,
+    title: String,
+    published_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct IssueSummary {
+    newsletter_issue_id: Uuid,
+    title: String,
+    published_at: String,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct Issue {
+    newsletter_issue_id: Uuid,
+    title: String,
+    text_content: String,
+    html_content: String,
+    published_at: String,
+    track_engagement: bool,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct DeliveryStats {
+    newsletter_issue_id: Uuid,
+    pending: i64,
+    delivered: i64,
+    failed: i64,
+    skipped: i64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/issues",
+    tag = "issues",
+    security(("api_token" = ["issues:write"])),
+    params(("Idempotency-Key" = String, Header, description = "Deduplicates retried requests")),
+    request_body = IssueData,
+    responses(
+        (status = 202, description = "The issue was stored and queued for delivery", body = PublishedIssue),
+        (status = 400, description = "The request body or idempotency key is invalid", body = ErrorBody),
+        (status = 401, description = "The API token is missing or invalid", body = ErrorBody),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorBody),
+        (status = 409, description = "A request with the same idempotency key is still being processed", body = ErrorBody),
+        (status = 422, description = "The idempotency key was already used for a different issue", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(
+    name = "Publish a newsletter issue via the API",
+    skip_all,
+    fields(user_id=%api_token.user_id, api_token_id=%api_token.api_token_id)
+)]
+pub async fn publish_issue(
+    body: web::Json<IssueData>,
+    pool: web::Data<PgPool>,
+    api_token: web::ReqData<ApiToken>,
+    request: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let api_token = api_token.into_inner();
+    require_scope(&api_token, Scope::IssuesWrite)?;
+    // Replays are answered by the idempotency middleware, but the key is still mandatory
+    idempotency_key(request.headers()).map_err(|e| ApiError::ValidationError(e.to_string()))?;
+    let IssueData {
+        title,
+        text_content,
+        html_content,
+        track_engagement,
+    } = body.0;
+
+    let mut transaction = begin_transaction(&request, &pool)
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+
+    let newsletter_issue_id = insert_newsletter_issue(
+        &mut transaction,
+        &title,
+        &text_content,
+        &html_content,
+        track_engagement,
+    )
+    .await
+    .context("Failed to store newsletter issue details")?;
+
+    enqueue_delivery_tasks(&mut transaction, newsletter_issue_id, &[], None)
+        .await
+        .context("Failed to enqueue delivery tasks")?;
+
+    let event = WebhookEvent::IssuePublished {
+        newsletter_issue_id,
+        title,
+    };
+    enqueue_webhook_event(&mut transaction, &event)
+        .await
+        .context("Failed to enqueue the issue published webhook")?;
+
+    commit_transaction(&request, transaction)
+        .await
+        .context("Failed to commit SQL transaction to store a new newsletter issue")?;
+    return Ok(HttpResponse::Accepted().json(PublishedIssue {
+        newsletter_issue_id,
+    }));
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/issues",
+    tag = "issues",
+    security(("api_token" = ["issues:read"])),
+    params(PageParameters),
+    responses(
+        (status = 200, description = "A page of newsletter issues", body = Page<IssueSummary>),
+        (status = 400, description = "The query parameters are invalid", body = ErrorBody),
+        (status = 401, description = "The API token is missing or invalid", body = ErrorBody),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(name = "List newsletter issues via the API", skip_all)]
+pub async fn list_issues(
+    page: web::Query<PageParameters>,
+    pool: web::Data<PgPool>,
+    api_token: web::ReqData<ApiToken>,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_token, Scope::IssuesRead)?;
+    let cursor = page.cursor()?;
+    let limit = page.limit()?;
+
+    let rows = sqlx::query_as!(
+        IssueSummaryRecord,
+        r#"
+        SELECT newsletter_issue_id, title, published_at
+        FROM newsletter_issues
+        WHERE $1::timestamptz IS NULL OR (published_at, newsletter_issue_id) > ($1, $2::uuid)
+        ORDER BY published_at, newsletter_issue_id
+        LIMIT $3
+        "#,
+        cursor.as_ref().map(|c| c.timestamp),
+        cursor.as_ref().map(|c| c.id),
+        limit + 1
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to list newsletter issues")?;
+
+    let page = Page::new(rows, limit, |r| Cursor {
+        timestamp: r.published_at,
+        id: r.newsletter_issue_id,
+    });
+    return Ok(HttpResponse::Ok().json(Page {
+        data: page
+            .data
+            .into_iter()
+            .map(|r| IssueSummary {
+                newsletter_issue_id: r.newsletter_issue_id,
+                title: r.title,
+                published_at: r.published_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            })
+            .collect(),
+        next_cursor: page.next_cursor,
+    }));
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/issues/{newsletter_issue_id}",
+    tag = "issues",
+    security(("api_token" = ["issues:read"])),
+    params(("newsletter_issue_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "The newsletter issue", body = Issue),
+        (status = 401, description = "The API token is missing or invalid", body = ErrorBody),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorBody),
+        (status = 404, description = "No such newsletter issue", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(name = "Get a newsletter issue via the API", skip(pool, api_token))]
+pub async fn get_issue(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    api_token: web::ReqData<ApiToken>,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_token, Scope::IssuesRead)?;
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let issue = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, title, text_content, html_content, published_at, tracking_enabled
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to retrieve the newsletter issue")?
+    .ok_or_else(|| issue_not_found(newsletter_issue_id))?;
+
+    return Ok(HttpResponse::Ok().json(Issue {
+        newsletter_issue_id: issue.newsletter_issue_id,
+        title: issue.title,
+        text_content: issue.text_content,
+        html_content: issue.html_content,
+        published_at: issue
+            .published_at
+            .to_rfc3339_opts(SecondsFormat::Secs, true),
+        track_engagement: issue.tracking_enabled,
+    }));
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/issues/{newsletter_issue_id}/stats",
+    tag = "issues",
+    security(("api_token" = ["issues:read"])),
+    params(("newsletter_issue_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Delivery counts for the newsletter issue", body = DeliveryStats),
+        (status = 401, description = "The API token is missing or invalid", body = ErrorBody),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorBody),
+        (status = 404, description = "No such newsletter issue", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(
+    name = "Get newsletter issue delivery stats via the API",
+    skip(pool, api_token)
+)]
+pub async fn get_issue_stats(
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    api_token: web::ReqData<ApiToken>,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_token, Scope::IssuesRead)?;
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let stats = sqlx::query!(
+        r#"
+        SELECT
+            (
+                SELECT COUNT(*) FROM issue_delivery_queue q
+                WHERE q.newsletter_issue_id = i.newsletter_issue_id
+            ) AS "pending!",
+            COUNT(d.outcome) FILTER (WHERE d.outcome = 'delivered') AS "delivered!",
+            COUNT(d.outcome) FILTER (WHERE d.outcome = 'failed') AS "failed!",
+            COUNT(d.outcome) FILTER (WHERE d.outcome = 'skipped') AS "skipped!"
+        FROM newsletter_issues i
+        LEFT JOIN issue_deliveries d ON d.newsletter_issue_id = i.newsletter_issue_id
+        WHERE i.newsletter_issue_id = $1
+        GROUP BY i.newsletter_issue_id
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .context("Failed to compute the newsletter issue delivery stats")?
+    .ok_or_else(|| issue_not_found(newsletter_issue_id))?;
+
+    return Ok(HttpResponse::Ok().json(DeliveryStats {
+        newsletter_issue_id,
+        pending: stats.pending,
+        delivered: stats.delivered,
+        failed: stats.failed,
+        skipped: stats.skipped,
+    }));
+}
+
+fn idempotency_key(headers: &HeaderMap) -> Result<IdempotencyKey, anyhow::Error> {
+    let key = headers
+        .get("Idempotency-Key")
+        .context("The 'Idempotency-Key' header was missing")?
+        .to_str()
+        .context("The 'Idempotency-Key' header was not a valid UTF8 string")?;
+    return IdempotencyKey::try_from(key.to_string());
+}
+
+fn issue_not_found(newsletter_issue_id: Uuid) -> ApiError {
+    return ApiError::NotFound(format!(
+        "No newsletter issue with id {}",
+        newsletter_issue_id
+    ));
+}
@@ -0,0 +1,11 @@
+//! src/routes/api/mod.rs
+
+mod error;
+mod issues;
+mod pagination;
+mod subscribers;
+
+pub use error::*;
+pub use issues::*;
+pub use pagination::*;
+pub use subscribers::*;
@@ -0,0 +1,147 @@
+//! src/routes/api/pagination.rs
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, SecondsFormat, Utc};
+use uuid::Uuid;
+
+use super::ApiError;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// Opaque keyset cursor pointing at the last row of the previous page.
+#[derive(Debug, PartialEq)]
+pub struct Cursor {
+    pub timestamp: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let raw = format!(
+            "{}|{}",
+            self.timestamp.to_rfc3339_opts(SecondsFormat::Micros, true),
+            self.id
+        );
+        return URL_SAFE_NO_PAD.encode(raw);
+    }
+
+    pub fn decode(s: &str) -> Result<Self, String> {
+        let invalid = || format!("{} is not a valid cursor", s);
+        let raw = URL_SAFE_NO_PAD.decode(s).map_err(|_| invalid())?;
+        let raw = String::from_utf8(raw).map_err(|_| invalid())?;
+        let (timestamp, id) = raw.split_once('|').ok_or_else(invalid)?;
+        let timestamp = DateTime::parse_from_rfc3339(timestamp)
+            .map_err(|_| invalid())?
+            .with_timezone(&Utc);
+        let id = Uuid::parse_str(id).map_err(|_| invalid())?;
+        return Ok(Self { timestamp, id });
+    }
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageParameters {
+    /// The `next_cursor` returned with the previous page.
+    cursor: Option<String>,
+    /// The number of items per page, between 1 and 100. Defaults to 50.
+    #[param(minimum = 1, maximum = 100)]
+    limit: Option<i64>,
+}
+
+impl PageParameters {
+    pub fn cursor(&self) -> Result<Option<Cursor>, ApiError> {
+        return self
+            .cursor
+            .as_deref()
+            .map(Cursor::decode)
+            .transpose()
+            .map_err(ApiError::ValidationError);
+    }
+
+    pub fn limit(&self) -> Result<i64, ApiError> {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+            return Err(ApiError::ValidationError(format!(
+                "The page size must be between 1 and {}",
+                MAX_PAGE_SIZE
+            )));
+        }
+        return Ok(limit);
+    }
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Build a page from up to `limit + 1` rows - the extra row only signals
+    /// that another page exists.
+    pub fn new(mut rows: Vec<T>, limit: i64, cursor: impl Fn(&T) -> Cursor) -> Self {
+        let has_more = rows.len() as i64 > limit;
+        rows.truncate(limit as usize);
+        let next_cursor = if has_more {
+            rows.last().map(|row| cursor(row).encode())
+        } else {
+            None
+        };
+        return Self {
+            data: rows,
+            next_cursor,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cursor, Page};
+    use chrono::{SubsecRound, Utc};
+    use claims::{assert_err, assert_ok_eq};
+    use uuid::Uuid;
+
+    #[test]
+    fn a_cursor_round_trips() {
+        // Postgres stores timestamps with microsecond precision
+        let cursor = Cursor {
+            timestamp: Utc::now().trunc_subsecs(6),
+            id: Uuid::new_v4(),
+        };
+        let expected = Cursor {
+            timestamp: cursor.timestamp,
+            id: cursor.id,
+        };
+        assert_ok_eq!(Cursor::decode(&cursor.encode()), expected);
+    }
+
+    #[test]
+    fn garbage_cursors_are_rejected() {
+        assert_err!(Cursor::decode("not-a-cursor"));
+        assert_err!(Cursor::decode(""));
+    }
+
+    #[test]
+    fn the_last_page_has_no_next_cursor() {
+        let page = Page::new(vec![Uuid::new_v4()], 2, |id| Cursor {
+            timestamp: Utc::now(),
+            id: *id,
+        });
+        assert_eq!(page.data.len(), 1);
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[test]
+    fn an_extra_row_produces_a_next_cursor() {
+        let rows = vec![Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+        let page = Page::new(rows.clone(), 2, |id| Cursor {
+            timestamp: Utc::now(),
+            id: *id,
+        });
+        assert_eq!(page.data, rows[..2]);
+        let next = Cursor::decode(&page.next_cursor.unwrap()).unwrap();
+        assert_eq!(next.id, rows[1]);
+    }
+}
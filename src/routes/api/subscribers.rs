@@ -0,0 +1,640 @@
+//! src/routes/api/subscribers.rs
+
+use std::collections::BTreeMap;
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::{require_scope, ApiError, Cursor, Page, PageParameters};
+use crate::authentication::{ApiToken, Scope};
+use crate::configuration::SubscriptionSettings;
+use crate::consent::{record_consent_event, ConsentContext, ConsentSource};
+use crate::domain::{
+    AttributeKey, AttributeValue, NewSubscriber, SubscriberEmail, SubscriberName, SubscriberTag,
+    SubscriptionStatus,
+};
+use crate::email_client::EmailClient;
+use crate::idempotency::{begin_transaction, commit_transaction};
+use crate::lists::find_unknown_lists;
+use crate::routes::{register_subscriber, remove_subscriber, store_new_subscriber};
+use crate::startup::ApplicationBaseUrl;
+use crate::suppressions::is_suppressed;
+use crate::utils::ErrorBody;
+
+struct SubscriberRecord {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    tags: Vec<String>,
+    attribute_keys: Vec<String>,
+    attribute_values: Vec<String>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+#[schema(as = Subscriber)]
+struct SubscriberResponse {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: String,
+    tags: Vec<String>,
+    attributes: BTreeMap<String, String>,
+}
+
+impl From<SubscriberRecord> for SubscriberResponse {
+    fn from(r: SubscriberRecord) -> Self {
+        return Self {
+            id: r.id,
+            email: r.email,
+            name: r.name,
+            status: r.status,
+            subscribed_at: r.subscribed_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            tags: r.tags,
+            attributes: r
+                .attribute_keys
+                .into_iter()
+                .zip(r.attribute_values)
+                .collect(),
+        };
+    }
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatusFilter {
+    /// Only list subscribers with this status.
+    status: Option<String>,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct NewSubscriberData {
+    email: String,
+    name: String,
+}
+
+/// How many subscribers a single import request may contain.
+const MAX_IMPORT_SIZE: usize = 1000;
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct SubscriberImport {
+    /// Subscribers who already consented elsewhere. They are confirmed straight away.
+    subscribers: Vec<NewSubscriberData>,
+    /// A list to add the imported subscribers to.
+    list_id: Option<Uuid>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct ImportResponse {
+    imported: Vec<Uuid>,
+    skipped: Vec<SkippedSubscriber>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+struct SkippedSubscriber {
+    email: String,
+    /// `already_subscribed` or `suppressed`.
+    reason: &'static str,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+pub struct SubscriberUpdate {
+    status: Option<String>,
+    /// Replaces all of the subscriber's tags.
+    tags: Option<Vec<String>>,
+    /// Attributes to set. A `null` value removes the attribute, the others are kept.
+    attributes: Option<BTreeMap<String, Option<String>>>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/subscribers",
+    tag = "subscribers",
+    security(("api_token" = ["subscribers:read"])),
+    params(StatusFilter, PageParameters),
+    responses(
+        (status = 200, description = "A page of subscribers", body = Page<SubscriberResponse>),
+        (status = 400, description = "The query parameters are invalid", body = ErrorBody),
+        (status = 401, description = "The API token is missing or invalid", body = ErrorBody),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(name = "List subscribers via the API", skip_all)]
+pub async fn list_subscribers(
+    filter: web::Query<StatusFilter>,
+    page: web::Query<PageParameters>,
+    pool: web::Data<PgPool>,
+    api_token: web::ReqData<ApiToken>,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_token, Scope::SubscribersRead)?;
+    let status = filter
+        .status
+        .as_deref()
+        .map(SubscriptionStatus::parse)
+        .transpose()
+        .map_err(ApiError::ValidationError)?;
+    let cursor = page.cursor()?;
+    let limit = page.limit()?;
+
+    let rows = sqlx::query_as!(
+        SubscriberRecord,
+        r#"
+        SELECT
+            id,
+            email,
+            name,
+            status,
+            subscribed_at,
+            ARRAY(
+                SELECT tag FROM subscriber_tags
+                WHERE subscriber_id = subscriptions.id
+                ORDER BY tag
+            ) AS "tags!",
+            ARRAY(
+                SELECT key FROM subscriber_attributes
+                WHERE subscriber_id = subscriptions.id
+                ORDER BY key
+            ) AS "attribute_keys!",
+            ARRAY(
+                SELECT value FROM subscriber_attributes
+                WHERE subscriber_id = subscriptions.id
+                ORDER BY key
+            ) AS "attribute_values!"
+        FROM subscriptions
+        WHERE
+            ($1::timestamptz IS NULL OR (subscribed_at, id) > ($1, $2::uuid)) AND
+            ($3::text IS NULL OR status = $3)
+        ORDER BY subscribed_at, id
+        LIMIT $4
+        "#,
+        cursor.as_ref().map(|c| c.timestamp),
+        cursor.as_ref().map(|c| c.id),
+        status.as_ref().map(|s| s.as_str()),
+        limit + 1
+    )
+    .fetch_all(pool.get_ref())
+    .await
+    .context("Failed to list subscribers")?;
+
+    let page = Page::new(rows, limit, |r| Cursor {
+        timestamp: r.subscribed_at,
+        id: r.id,
+    });
+    return Ok(HttpResponse::Ok().json(Page {
+        data: page
+            .data
+            .into_iter()
+            .map(SubscriberResponse::from)
+            .collect(),
+        next_cursor: page.next_cursor,
+    }));
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/subscribers/{subscriber_id}",
+    tag = "subscribers",
+    security(("api_token" = ["subscribers:read"])),
+    params(("subscriber_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "The subscriber", body = SubscriberResponse),
+        (status = 401, description = "The API token is missing or invalid", body = ErrorBody),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorBody),
+        (status = 404, description = "No such subscriber", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(name = "Get a subscriber via the API", skip(pool, api_token))]
+pub async fn get_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    api_token: web::ReqData<ApiToken>,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_token, Scope::SubscribersRead)?;
+    let subscriber = fetch_subscriber(&pool, *subscriber_id)
+        .await?
+        .ok_or_else(|| subscriber_not_found(*subscriber_id))?;
+    return Ok(HttpResponse::Ok().json(SubscriberResponse::from(subscriber)));
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/subscribers",
+    tag = "subscribers",
+    security(("api_token" = ["subscribers:write"])),
+    request_body = NewSubscriberData,
+    responses(
+        (status = 201, description = "The subscriber was created and, with double opt-in, sent a confirmation email", body = SubscriberResponse),
+        (status = 400, description = "The request body is invalid", body = ErrorBody),
+        (status = 401, description = "The API token is missing or invalid", body = ErrorBody),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(
+    name = "Create a subscriber via the API",
+    skip_all,
+    fields(subscriber_email = %body.email)
+)]
+pub async fn create_subscriber(
+    body: web::Json<NewSubscriberData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+    api_token: web::ReqData<ApiToken>,
+    request: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_token, Scope::SubscribersWrite)?;
+    let NewSubscriberData { email, name } = body.0;
+    let new_subscriber = NewSubscriber {
+        email: SubscriberEmail::parse(email).map_err(ApiError::ValidationError)?,
+        name: SubscriberName::parse(name).map_err(ApiError::ValidationError)?,
+    };
+
+    let consent = ConsentContext::from_request(ConsentSource::Api, &request);
+    let subscriber_id = register_subscriber(
+        &pool,
+        &email_client,
+        &base_url.0,
+        new_subscriber,
+        None,
+        settings.opt_in,
+        &consent,
+    )
+    .await?;
+    let subscriber = fetch_subscriber(&pool, subscriber_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("The new subscriber could not be found"))?;
+    return Ok(HttpResponse::Created().json(SubscriberResponse::from(subscriber)));
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/subscribers/import",
+    tag = "subscribers",
+    security(("api_token" = ["subscribers:import"])),
+    request_body = SubscriberImport,
+    responses(
+        (status = 200, description = "The subscribers were imported as confirmed, without a confirmation email", body = ImportResponse),
+        (status = 400, description = "The request body is invalid", body = ErrorBody),
+        (status = 401, description = "The API token is missing or invalid", body = ErrorBody),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(
+    name = "Import subscribers via the API",
+    skip_all,
+    fields(n_subscribers = body.subscribers.len())
+)]
+pub async fn import_subscribers(
+    body: web::Json<SubscriberImport>,
+    pool: web::Data<PgPool>,
+    api_token: web::ReqData<ApiToken>,
+    request: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_token, Scope::SubscribersImport)?;
+    let SubscriberImport {
+        subscribers,
+        list_id,
+    } = body.0;
+    if subscribers.len() > MAX_IMPORT_SIZE {
+        return Err(ApiError::ValidationError(format!(
+            "At most {} subscribers can be imported at once",
+            MAX_IMPORT_SIZE
+        )));
+    }
+    let subscribers = subscribers
+        .into_iter()
+        .enumerate()
+        .map(|(i, NewSubscriberData { email, name })| {
+            let new_subscriber = NewSubscriber {
+                email: SubscriberEmail::parse(email)
+                    .map_err(|e| format!("Subscriber {}: {}", i, e))?,
+                name: SubscriberName::parse(name)
+                    .map_err(|e| format!("Subscriber {}: {}", i, e))?,
+            };
+            return Ok(new_subscriber);
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map_err(ApiError::ValidationError)?;
+
+    let mut transaction = begin_transaction(&request, &pool)
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    if let Some(list_id) = list_id {
+        let unknown = find_unknown_lists(&mut *transaction, &[list_id])
+            .await
+            .context("Failed to look up the list")?;
+        if !unknown.is_empty() {
+            return Err(ApiError::ValidationError(format!(
+                "There is no list with id {}",
+                list_id
+            )));
+        }
+    }
+    // The request comes from whoever runs the import, not from the subscribers, who
+    // consented elsewhere
+    let consent = ConsentContext::without_request(ConsentSource::Import);
+    let mut imported = Vec::new();
+    let mut skipped = Vec::new();
+    for new_subscriber in subscribers {
+        let email = new_subscriber.email.as_ref();
+        let reason = if is_suppressed(&pool, email)
+            .await
+            .context("Failed to check the suppression list")?
+        {
+            Some("suppressed")
+        } else if email_exists(&mut transaction, email).await? {
+            Some("already_subscribed")
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            skipped.push(SkippedSubscriber {
+                email: email.to_string(),
+                reason,
+            });
+            continue;
+        }
+        let subscriber_id = store_new_subscriber(
+            &mut transaction,
+            &new_subscriber,
+            SubscriptionStatus::Confirmed,
+            list_id,
+            &consent,
+        )
+        .await?;
+        imported.push(subscriber_id);
+    }
+    commit_transaction(&request, transaction)
+        .await
+        .context("Failed to commit SQL transaction to import subscribers")?;
+
+    return Ok(HttpResponse::Ok().json(ImportResponse { imported, skipped }));
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/subscribers/{subscriber_id}",
+    tag = "subscribers",
+    security(("api_token" = ["subscribers:write"])),
+    params(("subscriber_id" = Uuid, Path)),
+    request_body = SubscriberUpdate,
+    responses(
+        (status = 200, description = "The updated subscriber", body = SubscriberResponse),
+        (status = 400, description = "The request body is invalid", body = ErrorBody),
+        (status = 401, description = "The API token is missing or invalid", body = ErrorBody),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorBody),
+        (status = 404, description = "No such subscriber", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(
+    name = "Update a subscriber via the API",
+    skip(body, pool, api_token, request)
+)]
+pub async fn update_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    body: web::Json<SubscriberUpdate>,
+    pool: web::Data<PgPool>,
+    api_token: web::ReqData<ApiToken>,
+    request: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_token, Scope::SubscribersWrite)?;
+    let subscriber_id = subscriber_id.into_inner();
+    let SubscriberUpdate {
+        status,
+        tags,
+        attributes,
+    } = body.0;
+    let status = status
+        .as_deref()
+        .map(SubscriptionStatus::parse)
+        .transpose()
+        .map_err(ApiError::ValidationError)?;
+    let tags = tags
+        .map(|tags| {
+            tags.iter()
+                .map(|tag| SubscriberTag::parse(tag))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()
+        .map_err(ApiError::ValidationError)?;
+    let attributes = attributes
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(key, value)| {
+            let value = value.as_deref().map(AttributeValue::parse).transpose()?;
+            return Ok((AttributeKey::parse(&key)?, value));
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map_err(ApiError::ValidationError)?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let previous_status = sqlx::query_scalar!(
+        r#"
+        UPDATE subscriptions s
+        SET status = COALESCE($2, s.status)
+        FROM (SELECT id, status FROM subscriptions WHERE id = $1 FOR UPDATE) previous
+        WHERE s.id = previous.id
+        RETURNING previous.status
+        "#,
+        subscriber_id,
+        status.as_ref().map(|s| s.as_str())
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to update the subscriber status")?
+    .ok_or_else(|| subscriber_not_found(subscriber_id))?;
+    if let Some(status) = status.filter(|s| s.as_str() != previous_status) {
+        let consent = ConsentContext::from_request(ConsentSource::Api, &request);
+        record_consent_event(&mut transaction, subscriber_id, status, &consent)
+            .await
+            .context("Failed to record the subscriber's status change")?;
+    }
+    if let Some(tags) = tags {
+        replace_tags(&mut transaction, subscriber_id, &tags).await?;
+    }
+    update_attributes(&mut transaction, subscriber_id, &attributes).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update a subscriber")?;
+
+    let subscriber = fetch_subscriber(&pool, subscriber_id)
+        .await?
+        .ok_or_else(|| subscriber_not_found(subscriber_id))?;
+    return Ok(HttpResponse::Ok().json(SubscriberResponse::from(subscriber)));
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/subscribers/{subscriber_id}",
+    tag = "subscribers",
+    security(("api_token" = ["subscribers:write"])),
+    params(("subscriber_id" = Uuid, Path)),
+    responses(
+        (status = 204, description = "The subscriber was deleted"),
+        (status = 401, description = "The API token is missing or invalid", body = ErrorBody),
+        (status = 403, description = "The API token lacks the required scope", body = ErrorBody),
+        (status = 404, description = "No such subscriber", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(
+    name = "Delete a subscriber via the API",
+    skip(pool, api_token, request)
+)]
+pub async fn delete_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    api_token: web::ReqData<ApiToken>,
+    request: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    require_scope(&api_token, Scope::SubscribersWrite)?;
+    let subscriber_id = subscriber_id.into_inner();
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let consent = ConsentContext::from_request(ConsentSource::Api, &request);
+    remove_subscriber(&mut transaction, subscriber_id, &consent)
+        .await?
+        .ok_or_else(|| subscriber_not_found(subscriber_id))?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to delete a subscriber")?;
+
+    return Ok(HttpResponse::NoContent().finish());
+}
+
+async fn email_exists(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+) -> Result<bool, anyhow::Error> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM subscriptions WHERE email = $1) AS "exists!""#,
+        email
+    )
+    .fetch_one(&mut **transaction)
+    .await
+    .context("Failed to check whether the address is subscribed")?;
+    return Ok(exists);
+}
+
+async fn fetch_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberRecord>, anyhow::Error> {
+    let subscriber = sqlx::query_as!(
+        SubscriberRecord,
+        r#"
+        SELECT
+            id,
+            email,
+            name,
+            status,
+            subscribed_at,
+            ARRAY(
+                SELECT tag FROM subscriber_tags
+                WHERE subscriber_id = subscriptions.id
+                ORDER BY tag
+            ) AS "tags!",
+            ARRAY(
+                SELECT key FROM subscriber_attributes
+                WHERE subscriber_id = subscriptions.id
+                ORDER BY key
+            ) AS "attribute_keys!",
+            ARRAY(
+                SELECT value FROM subscriber_attributes
+                WHERE subscriber_id = subscriptions.id
+                ORDER BY key
+            ) AS "attribute_values!"
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the subscriber")?;
+    return Ok(subscriber);
+}
+
+async fn replace_tags(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    tags: &[SubscriberTag],
+) -> Result<(), anyhow::Error> {
+    let tags: Vec<String> = tags.iter().map(|tag| tag.to_string()).collect();
+    sqlx::query!(
+        "DELETE FROM subscriber_tags WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to delete the subscriber's tags")?;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_tags (subscriber_id, tag)
+        SELECT $1, UNNEST($2::text[])
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+        &tags
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to store the subscriber's tags")?;
+    return Ok(());
+}
+
+/// Set the attributes with a value and remove the ones without.
+async fn update_attributes(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    attributes: &[(AttributeKey, Option<AttributeValue>)],
+) -> Result<(), anyhow::Error> {
+    let (set, removed): (Vec<_>, Vec<_>) =
+        attributes.iter().partition(|(_, value)| value.is_some());
+    let keys: Vec<String> = set.iter().map(|(key, _)| key.to_string()).collect();
+    let values: Vec<String> = set
+        .iter()
+        .filter_map(|(_, value)| value.as_ref().map(|v| v.to_string()))
+        .collect();
+    let removed: Vec<String> = removed.iter().map(|(key, _)| key.to_string()).collect();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_attributes (subscriber_id, key, value)
+        SELECT $1, * FROM UNNEST($2::text[], $3::text[])
+        ON CONFLICT (subscriber_id, key) DO UPDATE SET value = EXCLUDED.value
+        "#,
+        subscriber_id,
+        &keys,
+        &values
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to store the subscriber's attributes")?;
+    sqlx::query!(
+        "DELETE FROM subscriber_attributes WHERE subscriber_id = $1 AND key = ANY($2)",
+        subscriber_id,
+        &removed
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to remove the subscriber's attributes")?;
+    return Ok(());
+}
+
+fn subscriber_not_found(subscriber_id: Uuid) -> ApiError {
+    return ApiError::NotFound(format!("No subscriber with id {}", subscriber_id));
+}
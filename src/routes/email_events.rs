@@ -0,0 +1,275 @@
+//! src/routes/email_events.rs
+
+use actix_web::http::header::HeaderMap;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::consent::{record_consent_event, ConsentContext, ConsentSource};
+use crate::domain::SubscriptionStatus;
+use crate::routes::error_chain_fmt;
+use crate::suppressions::{add_suppression, SuppressionPattern, SuppressionReason};
+use crate::utils::{json_error, ErrorBody};
+
+const SIGNATURE_HEADER: &str = "X-Twilio-Email-Event-Webhook-Signature";
+const TIMESTAMP_HEADER: &str = "X-Twilio-Email-Event-Webhook-Timestamp";
+/// Signed requests older, or further in the future, than this are rejected as replays.
+const MAX_TIMESTAMP_SKEW_SECONDS: i64 = 5 * 60;
+
+/// The public key SendGrid signs Event Webhook requests with.
+pub struct EventWebhookKey(pub VerifyingKey);
+
+/// A single entry of a SendGrid Event Webhook payload. Fields we do not use are ignored.
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = SendGridEvent)]
+pub struct EmailEvent {
+    sg_event_id: String,
+    email: String,
+    event: String,
+    timestamp: i64,
+    /// For `bounce` events: `bounce` for hard bounces, `blocked` for soft ones.
+    #[serde(rename = "type")]
+    bounce_type: Option<String>,
+    reason: Option<String>,
+    /// Echoed back from the custom args set when the issue was sent.
+    newsletter_issue_id: Option<String>,
+}
+
+impl EmailEvent {
+    /// The status a subscriber should be moved to after this event, if any.
+    fn subscription_status(&self) -> Option<SubscriptionStatus> {
+        return match (self.event.as_str(), self.bounce_type.as_deref()) {
+            ("bounce", Some("blocked")) => None,
+            ("bounce", _) => Some(SubscriptionStatus::Bounced),
+            ("spamreport", _) => Some(SubscriptionStatus::Complained),
+            _ => None,
+        };
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum EmailEventsError {
+    #[error("Invalid event webhook signature")]
+    InvalidSignature(#[source] anyhow::Error),
+    #[error("{0}")]
+    ValidationError(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ResponseError for EmailEventsError {
+    fn status_code(&self) -> StatusCode {
+        return match self {
+            EmailEventsError::InvalidSignature(_) => StatusCode::UNAUTHORIZED,
+            EmailEventsError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            EmailEventsError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        return match self {
+            EmailEventsError::UnexpectedError(_) => {
+                json_error(self.status_code(), "Something went wrong")
+            }
+            _ => json_error(self.status_code(), self.to_string()),
+        };
+    }
+}
+
+impl std::fmt::Debug for EmailEventsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return error_chain_fmt(self, f);
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/email-events/sendgrid",
+    tag = "email events",
+    params(
+        ("X-Twilio-Email-Event-Webhook-Signature" = String, Header, description = "Base64 ECDSA signature of the timestamp followed by the body"),
+        ("X-Twilio-Email-Event-Webhook-Timestamp" = String, Header)
+    ),
+    request_body = Vec<EmailEvent>,
+    responses(
+        (status = 204, description = "The events were recorded"),
+        (status = 400, description = "The payload is not a list of events", body = ErrorBody),
+        (status = 401, description = "The signature is missing, invalid or too old", body = ErrorBody)
+    )
+)]
+#[tracing::instrument(name = "Receive SendGrid email events", skip_all)]
+pub async fn receive_email_events(
+    request: HttpRequest,
+    body: web::Bytes,
+    pool: web::Data<PgPool>,
+    key: web::Data<EventWebhookKey>,
+) -> Result<HttpResponse, EmailEventsError> {
+    verify_signature(request.headers(), &body, &key.0)
+        .map_err(EmailEventsError::InvalidSignature)?;
+    let events: Vec<EmailEvent> = serde_json::from_slice(&body)
+        .map_err(|e| EmailEventsError::ValidationError(e.to_string()))?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    for event in &events {
+        // One bad event must not fail the batch, or SendGrid retries it forever
+        let Some(occurred_at) = DateTime::<Utc>::from_timestamp(event.timestamp, 0) else {
+            tracing::warn!(
+                sg_event_id = %event.sg_event_id,
+                "Skipping an email event with an out-of-range timestamp: {}",
+                event.timestamp
+            );
+            continue;
+        };
+        let is_new = record_email_event(&mut transaction, event, occurred_at)
+            .await
+            .context("Failed to store an email event")?;
+        if let (true, Some(status)) = (is_new, event.subscription_status()) {
+            update_subscription_status(&mut transaction, &event.email, status)
+                .await
+                .context("Failed to update the subscriber status after an email event")?;
+            suppress(&mut transaction, &event.email, status)
+                .await
+                .context("Failed to suppress an address after an email event")?;
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store email events")?;
+
+    return Ok(HttpResponse::NoContent().finish());
+}
+
+/// SendGrid signs the timestamp header followed by the raw request body. The timestamp
+/// has to be recent, so captured requests cannot be replayed.
+fn verify_signature(
+    headers: &HeaderMap,
+    body: &[u8],
+    key: &VerifyingKey,
+) -> Result<(), anyhow::Error> {
+    let signature = headers
+        .get(SIGNATURE_HEADER)
+        .context("The signature header was missing")?
+        .to_str()
+        .context("The signature header was not a valid UTF8 string")?;
+    let timestamp = headers
+        .get(TIMESTAMP_HEADER)
+        .context("The timestamp header was missing")?
+        .to_str()
+        .context("The timestamp header was not a valid UTF8 string")?;
+    let signature = STANDARD
+        .decode(signature)
+        .context("The signature was not valid base64")?;
+    let signature = Signature::from_der(&signature).context("The signature was malformed")?;
+
+    let mut payload = timestamp.as_bytes().to_vec();
+    payload.extend_from_slice(body);
+    key.verify(&payload, &signature)
+        .context("The signature does not match the payload")?;
+
+    let timestamp: i64 = timestamp
+        .parse()
+        .context("The timestamp header was not a number")?;
+    if (Utc::now().timestamp() - timestamp).abs() > MAX_TIMESTAMP_SKEW_SECONDS {
+        anyhow::bail!("The timestamp is too far from the current time");
+    }
+    return Ok(());
+}
+
+/// Returns `false` if the event had already been recorded - SendGrid may deliver it twice.
+#[tracing::instrument(skip(transaction, event), fields(event_type = %event.event))]
+async fn record_email_event(
+    transaction: &mut Transaction<'_, Postgres>,
+    event: &EmailEvent,
+    occurred_at: DateTime<Utc>,
+) -> Result<bool, anyhow::Error> {
+    let newsletter_issue_id = event
+        .newsletter_issue_id
+        .as_deref()
+        .and_then(|id| Uuid::parse_str(id).ok());
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO email_events (
+            provider_event_id,
+            subscriber_id,
+            newsletter_issue_id,
+            email,
+            event_type,
+            reason,
+            occurred_at,
+            received_at
+        )
+        VALUES (
+            $1,
+            (SELECT id FROM subscriptions WHERE email = $2),
+            (SELECT newsletter_issue_id FROM newsletter_issues WHERE newsletter_issue_id = $3),
+            $2,
+            $4,
+            $5,
+            $6,
+            now()
+        )
+        ON CONFLICT (provider_event_id) DO NOTHING
+        "#,
+        event.sg_event_id,
+        event.email,
+        newsletter_issue_id,
+        event.event,
+        event.reason,
+        occurred_at
+    );
+    let n_inserted = transaction.execute(query).await?.rows_affected();
+    return Ok(n_inserted > 0);
+}
+
+#[tracing::instrument(skip(transaction, email))]
+async fn suppress(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+    status: SubscriptionStatus,
+) -> Result<(), anyhow::Error> {
+    let reason = match status {
+        SubscriptionStatus::Complained => SuppressionReason::Complained,
+        _ => SuppressionReason::Bounced,
+    };
+    match SuppressionPattern::parse(email) {
+        Ok(pattern) => add_suppression(&mut **transaction, &pattern, reason).await?,
+        Err(e) => tracing::warn!("Cannot suppress an invalid address: {}", e),
+    }
+    return Ok(());
+}
+
+#[tracing::instrument(skip(transaction, email))]
+async fn update_subscription_status(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+    status: SubscriptionStatus,
+) -> Result<(), sqlx::Error> {
+    let updated = sqlx::query_scalar!(
+        r#"
+        UPDATE subscriptions
+        SET status = $2
+        WHERE email = $1 AND status IN ('pending_confirmation', 'confirmed')
+        RETURNING id
+        "#,
+        email,
+        status.as_str()
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    if let Some(subscriber_id) = updated {
+        let consent = ConsentContext::without_request(ConsentSource::EmailProvider);
+        record_consent_event(transaction, subscriber_id, status, &consent).await?;
+    }
+    return Ok(());
+}
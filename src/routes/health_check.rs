@@ -2,6 +2,12 @@
 
 use actix_web::{HttpResponse, Responder};
 
+#[utoipa::path(
+    get,
+    path = "/health_check",
+    tag = "health",
+    responses((status = 200, description = "The application is running"))
+)]
 #[tracing::instrument(name = "Checking health")]
 pub async fn health_check() -> impl Responder {
     return HttpResponse::Ok();
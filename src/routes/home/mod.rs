@@ -1,9 +1,20 @@
 //! src/routes/home/mod.rs
 
-use actix_web::{http::header::ContentType, HttpResponse};
+use actix_web::{http::header::ContentType, web, HttpResponse};
 
-pub async fn home() -> HttpResponse {
+use crate::spam_protection::SpamGuard;
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "pages",
+    responses((status = 200, description = "The home page, with the subscribe form", body = String, content_type = "text/html"))
+)]
+pub async fn home(spam_guard: web::Data<SpamGuard>) -> HttpResponse {
     return HttpResponse::Ok()
         .content_type(ContentType::html())
-        .body(include_str!("home.html"));
+        .body(format!(
+            include_str!("home.html"),
+            form_token = spam_guard.form_token()
+        ));
 }
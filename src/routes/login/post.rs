@@ -8,7 +8,8 @@ use actix_web_flash_messages::FlashMessage;
 use secrecy::Secret;
 use sqlx::PgPool;
 
-use crate::authentication::{validate_credentials, AuthError, Credentials};
+use crate::authentication::{validate_credentials, AuthError, Credentials, DummyPasswordHash};
+use crate::configuration::PasswordHashingSettings;
 use crate::routes::error_chain_fmt;
 use crate::session_state::TypedSession;
 
@@ -32,20 +33,37 @@ impl ResponseError for LoginError {
     }
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = LoginForm)]
 pub struct FormData {
     username: String,
+    #[schema(value_type = String, format = Password)]
     password: Secret<String>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/login",
+    tag = "authentication",
+    request_body(content = FormData, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (
+            status = 303,
+            description = "Redirect to the admin dashboard on success, back to the login form on failure",
+            headers(("Location" = String))
+        )
+    )
+)]
 #[tracing::instrument(
-    skip(form, pool, session),
+    skip(form, pool, session, hashing, dummy_password_hash),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
 pub async fn login(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     session: TypedSession,
+    hashing: web::Data<PasswordHashingSettings>,
+    dummy_password_hash: web::Data<DummyPasswordHash>,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
     let credentials = Credentials {
         username: form.0.username,
@@ -54,7 +72,7 @@ pub async fn login(
 
     tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
 
-    return match validate_credentials(credentials, &pool).await {
+    return match validate_credentials(credentials, &pool, &hashing, &dummy_password_hash).await {
         Ok(user_id) => {
             tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
             session.renew();
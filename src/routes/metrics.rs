@@ -0,0 +1,62 @@
+//! src/routes/metrics.rs
+
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use prometheus::{Encoder, IntGauge, Registry, TextEncoder};
+use sqlx::PgPool;
+
+use crate::utils::e500;
+
+/// Served by [`MetricsServer`](crate::startup::MetricsServer) rather than alongside the
+/// public routes.
+pub async fn metrics(pool: web::Data<PgPool>) -> Result<HttpResponse, actix_web::Error> {
+    // Queue depths are read from the database on every scrape so they stay
+    // accurate no matter which process is running the workers.
+    let queues = queue_depths(&pool).await.map_err(e500)?;
+    let mut metric_families = prometheus::gather();
+    metric_families.extend(queues.gather());
+
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    encoder
+        .encode(&metric_families, &mut buffer)
+        .context("Failed to encode metrics")
+        .map_err(e500)?;
+
+    return Ok(HttpResponse::Ok()
+        .content_type(encoder.format_type())
+        .body(buffer));
+}
+
+#[tracing::instrument(name = "Measure queue depths", skip(pool))]
+async fn queue_depths(pool: &PgPool) -> Result<Registry, anyhow::Error> {
+    let depths = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM issue_delivery_queue) AS "issue_delivery!",
+            (SELECT COUNT(*) FROM webhook_delivery_queue) AS "webhook_delivery!"
+        "#
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to perform a query to measure queue depths")?;
+
+    let registry = Registry::new();
+    for (name, help, depth) in [
+        (
+            "issue_delivery_queue_depth",
+            "Newsletter emails waiting to be delivered",
+            depths.issue_delivery,
+        ),
+        (
+            "webhook_delivery_queue_depth",
+            "Webhook events waiting to be delivered",
+            depths.webhook_delivery,
+        ),
+    ] {
+        let gauge = IntGauge::new(name, help)?;
+        gauge.set(depth);
+        registry.register(Box::new(gauge))?;
+    }
+    return Ok(registry);
+}
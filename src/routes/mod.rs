@@ -1,17 +1,33 @@
 //! src/routes/mod.rs
 
 mod admin;
+mod api;
+mod email_events;
 mod health_check;
 mod home;
 mod login;
+mod metrics;
+mod openapi;
 // mod newsletter;
+mod ready;
+mod setup;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_preferences;
+mod tracking;
 
 pub use admin::*;
+pub use api::*;
+pub use email_events::*;
 pub use health_check::*;
 pub use home::*;
 pub use login::*;
+pub use metrics::*;
+pub use openapi::*;
 // pub use newsletter::*;
+pub use ready::*;
+pub use setup::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_preferences::*;
+pub use tracking::*;
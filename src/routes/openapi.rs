@@ -0,0 +1,104 @@
+//! src/routes/openapi.rs
+
+use actix_web::HttpResponse;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+use crate::routes::*;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "zero2prod", description = "A newsletter delivery service"),
+    paths(
+        home,
+        login_form,
+        login,
+        setup_form,
+        setup,
+        health_check,
+        ready,
+        subscribe,
+        confirm,
+        preferences_form,
+        update_preferences,
+        unsubscribe,
+        request_email_change,
+        email_change_confirmation_form,
+        confirm_email_change,
+        email_change_cancellation_form,
+        cancel_email_change,
+        request_data_export,
+        request_erasure,
+        data_request_form,
+        fulfil_data_request,
+        openapi_json,
+        receive_email_events,
+        track_open,
+        track_click,
+        admin_dashboard,
+        publish_newsletter_form,
+        publish_newsletter,
+        issues_list,
+        issue_detail,
+        subscribers_list,
+        subscriber_detail,
+        lists_form,
+        create_subscriber_list,
+        segments_form,
+        create_subscriber_segment,
+        change_password_form,
+        change_password,
+        log_out,
+        api_tokens_form,
+        create_api_token,
+        revoke_api_token,
+        webhooks_form,
+        create_webhook_endpoint,
+        delete_webhook_endpoint,
+        suppressions_form,
+        add_suppression_entry,
+        delete_suppression,
+        data_requests_form,
+        export_subscriber,
+        erase_subscriber_data,
+        list_subscribers,
+        create_subscriber,
+        import_subscribers,
+        get_subscriber,
+        update_subscriber,
+        delete_subscriber,
+        list_issues,
+        publish_issue,
+        get_issue,
+        get_issue_stats,
+    ),
+    modifiers(&SecurityAddon)
+)]
+pub struct ApiDoc;
+
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        // `actix-session` stores the session key in the `id` cookie.
+        components.add_security_scheme(
+            "session",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("id"))),
+        );
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/openapi.json",
+    tag = "documentation",
+    responses((status = 200, description = "This OpenAPI document", content_type = "application/json"))
+)]
+pub async fn openapi_json() -> HttpResponse {
+    return HttpResponse::Ok().json(ApiDoc::openapi());
+}
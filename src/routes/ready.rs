@@ -0,0 +1,158 @@
+//! src/routes/ready.rs
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::startup::count_pending_migrations;
+use crate::worker_heartbeat::WORKERS;
+
+/// Every dependency must answer within this long for the instance to count as ready.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+/// Idle workers poll every 10 seconds, so a heartbeat older than this means a worker is stuck.
+const HEARTBEAT_MAX_AGE: chrono::Duration = chrono::Duration::seconds(60);
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ReadinessReport {
+    #[schema(example = "ready")]
+    status: &'static str,
+    checks: BTreeMap<String, CheckResult>,
+}
+
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct CheckResult {
+    #[schema(example = "up")]
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl CheckResult {
+    fn is_up(&self) -> bool {
+        return self.error.is_none();
+    }
+}
+
+impl From<Result<(), anyhow::Error>> for CheckResult {
+    fn from(result: Result<(), anyhow::Error>) -> Self {
+        return match result {
+            Ok(()) => CheckResult {
+                status: "up",
+                error: None,
+            },
+            Err(e) => CheckResult {
+                status: "down",
+                error: Some(format!("{:#}", e)),
+            },
+        };
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "Every dependency is healthy", body = ReadinessReport),
+        (status = 503, description = "At least one dependency is unhealthy", body = ReadinessReport)
+    )
+)]
+#[tracing::instrument(name = "Checking readiness", skip_all)]
+pub async fn ready(
+    pool: web::Data<PgPool>,
+    redis_client: web::Data<redis::Client>,
+) -> HttpResponse {
+    let (postgres, redis, migrations, workers) = tokio::join!(
+        run_check(check_postgres(&pool)),
+        run_check(check_redis(&redis_client)),
+        run_check(check_migrations(&pool)),
+        check_workers(&pool),
+    );
+
+    let mut checks = BTreeMap::from([
+        ("postgres".to_string(), postgres),
+        ("redis".to_string(), redis),
+        ("migrations".to_string(), migrations),
+    ]);
+    checks.extend(workers);
+
+    let is_ready = checks.values().all(CheckResult::is_up);
+    for (name, check) in checks.iter().filter(|(_, c)| !c.is_up()) {
+        tracing::warn!(check = %name, error = ?check.error, "Readiness check failed");
+    }
+    let report = ReadinessReport {
+        status: if is_ready { "ready" } else { "unavailable" },
+        checks,
+    };
+    return if is_ready {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    };
+}
+
+async fn run_check(check: impl Future<Output = Result<(), anyhow::Error>>) -> CheckResult {
+    let result = tokio::time::timeout(CHECK_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("The check timed out")));
+    return result.into();
+}
+
+async fn check_postgres(pool: &PgPool) -> Result<(), anyhow::Error> {
+    sqlx::query!("SELECT 1 AS one")
+        .fetch_one(pool)
+        .await
+        .context("Failed to query Postgres")?;
+    return Ok(());
+}
+
+async fn check_redis(client: &redis::Client) -> Result<(), anyhow::Error> {
+    let mut connection = client
+        .get_async_connection()
+        .await
+        .context("Failed to connect to Redis")?;
+    redis::cmd("PING")
+        .query_async::<_, String>(&mut connection)
+        .await
+        .context("Failed to ping Redis")?;
+    return Ok(());
+}
+
+async fn check_migrations(pool: &PgPool) -> Result<(), anyhow::Error> {
+    let pending = count_pending_migrations(pool).await?;
+    if pending > 0 {
+        anyhow::bail!("{} migration(s) have not been applied", pending);
+    }
+    return Ok(());
+}
+
+async fn check_workers(pool: &PgPool) -> Vec<(String, CheckResult)> {
+    let heartbeats = tokio::time::timeout(
+        CHECK_TIMEOUT,
+        sqlx::query!("SELECT worker_name, last_seen_at FROM worker_heartbeats").fetch_all(pool),
+    )
+    .await;
+
+    return WORKERS
+        .iter()
+        .map(|&worker| {
+            let result = match &heartbeats {
+                Err(_) => Err(anyhow::anyhow!("The check timed out")),
+                Ok(Err(e)) => Err(anyhow::anyhow!("Failed to read worker heartbeats: {}", e)),
+                Ok(Ok(rows)) => match rows.iter().find(|r| r.worker_name == worker) {
+                    None => Err(anyhow::anyhow!("No heartbeat has been recorded")),
+                    Some(r) if Utc::now() - r.last_seen_at > HEARTBEAT_MAX_AGE => Err(
+                        anyhow::anyhow!("The last heartbeat was at {}", r.last_seen_at),
+                    ),
+                    Some(_) => Ok(()),
+                },
+            };
+            (worker.to_string(), result.into())
+        })
+        .collect();
+}
@@ -0,0 +1,86 @@
+//! src/routes/setup/get.rs
+
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::bootstrap::has_users;
+use crate::utils::e500;
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SetupQuery {
+    /// The setup token logged on startup
+    token: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/setup",
+    tag = "authentication",
+    params(SetupQuery),
+    responses(
+        (status = 200, description = "The form to create the first admin", body = String, content_type = "text/html"),
+        (status = 404, description = "An admin account already exists")
+    )
+)]
+pub async fn setup_form(
+    query: web::Query<SetupQuery>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    if has_users(&pool).await.map_err(e500)? {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let mut error_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(
+            error_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+    let token = htmlescape::encode_attribute(query.token.as_deref().unwrap_or_default());
+
+    return Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+  <head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Set up</title>
+  </head>
+  <body>
+    {error_html}
+    <p>Create the first admin account.</p>
+    <form action="/setup" method="post">
+      <label>
+        Setup token
+        <input type="text" placeholder="Enter the token from the logs" name="token" value="{token}">
+      </label>
+      <br>
+      <label>
+        Username
+        <input type="text" placeholder="Enter username" name="username">
+      </label>
+      <br>
+      <label>
+        Password
+        <input type="password" placeholder="Enter password" name="password">
+      </label>
+      <br>
+      <label>
+        Confirm password
+        <input type="password" placeholder="Type the password again" name="password_check">
+      </label>
+      <br>
+      <button type="submit">Create admin</button>
+    </form>
+  </body>
+</html>"#
+        )));
+}
@@ -0,0 +1,7 @@
+//! src/routes/setup/mod.rs
+
+mod get;
+mod post;
+
+pub use get::{__path_setup_form, setup_form};
+pub use post::{__path_setup, setup};
@@ -0,0 +1,79 @@
+//! src/routes/setup/post.rs
+
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::authentication::validate_new_password;
+use crate::bootstrap::{complete_setup, SetupError};
+use crate::configuration::PasswordHashingSettings;
+use crate::utils::{e500, see_other};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = SetupForm)]
+pub struct FormData {
+    #[schema(value_type = String)]
+    token: Secret<String>,
+    username: String,
+    #[schema(value_type = String, format = Password)]
+    password: Secret<String>,
+    #[schema(value_type = String, format = Password)]
+    password_check: Secret<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/setup",
+    tag = "authentication",
+    request_body(content = FormData, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirect to the login form once the admin exists, or back to the setup form", headers(("Location" = String))),
+        (status = 404, description = "An admin account already exists")
+    )
+)]
+#[tracing::instrument(name = "Create the first admin", skip(form, pool, hashing), fields(username = %form.username))]
+pub async fn setup(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    hashing: web::Data<PasswordHashingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData {
+        token,
+        username,
+        password,
+        password_check,
+    } = form.0;
+    let retry = format!(
+        "/setup?token={}",
+        urlencoding::encode(token.expose_secret())
+    );
+
+    let username = username.trim();
+    if username.is_empty() {
+        FlashMessage::error("The username cannot be empty").send();
+        return Ok(see_other(&retry));
+    }
+    if let Err(e) = validate_new_password(&password) {
+        FlashMessage::error(e).send();
+        return Ok(see_other(&retry));
+    }
+    if password.expose_secret() != password_check.expose_secret() {
+        FlashMessage::error("You entered two different passwords - the field values must match")
+            .send();
+        return Ok(see_other(&retry));
+    }
+
+    return match complete_setup(&token, username, password, &pool, &hashing).await {
+        Ok(_) => {
+            FlashMessage::info("The admin account has been created - you can now log in").send();
+            Ok(see_other("/login"))
+        }
+        Err(SetupError::InvalidToken) => {
+            FlashMessage::error("The setup token is invalid").send();
+            Ok(see_other("/setup"))
+        }
+        Err(SetupError::AlreadyCompleted) => Ok(HttpResponse::NotFound().finish()),
+        Err(e @ SetupError::UnexpectedError(_)) => Err(e500(e)),
+    };
+}
@@ -1,7 +1,7 @@
 //! src/routes/subscriptions.rs
 
 use actix_web::http::StatusCode;
-use actix_web::{web, HttpResponse, ResponseError};
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
 use anyhow::Context;
 use chrono::Utc;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
@@ -10,9 +10,15 @@ use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
-    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+    configuration::SubscriptionSettings,
+    consent::{record_consent_event, record_unsubscribe, ConsentContext, ConsentSource},
+    domain::{NewSubscriber, OptIn, SubscriberEmail, SubscriberName, SubscriptionStatus},
     email_client::EmailClient,
+    lists::{add_to_list, find_list_by_name},
+    spam_protection::SpamGuard,
     startup::ApplicationBaseUrl,
+    suppressions::is_suppressed,
+    webhooks::{enqueue_webhook_event, WebhookEvent},
 };
 
 // pub struct StoreTokenError(sqlx::Error);
@@ -43,6 +49,10 @@ use crate::{
 pub enum SubscribeError {
     #[error("{0}")]
     ValidationError(String),
+    #[error("The email address is on the suppression list")]
+    Suppressed,
+    #[error("Too many subscription attempts, please try again later")]
+    RateLimited,
     // transparent delegates both `Display` and `source` implementation
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
@@ -51,7 +61,10 @@ pub enum SubscribeError {
 impl ResponseError for SubscribeError {
     fn status_code(&self) -> StatusCode {
         return match self {
-            SubscribeError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            SubscribeError::ValidationError(_) | SubscribeError::Suppressed => {
+                StatusCode::BAD_REQUEST
+            }
+            SubscribeError::RateLimited => StatusCode::TOO_MANY_REQUESTS,
             SubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
     }
@@ -63,10 +76,19 @@ impl std::fmt::Debug for SubscribeError {
     }
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = SubscribeForm)]
 pub struct FormData {
     email: String,
     name: String,
+    /// The name of a list to join.
+    list: Option<String>,
+    /// The signed timestamp embedded in the form on the home page.
+    #[serde(default)]
+    form_token: String,
+    /// A honeypot hidden from people. Bots that fill it in are ignored.
+    #[serde(default)]
+    website: String,
 }
 
 impl TryFrom<FormData> for NewSubscriber {
@@ -79,46 +101,261 @@ impl TryFrom<FormData> for NewSubscriber {
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/subscriptions",
+    tag = "subscriptions",
+    request_body(content = FormData, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "The subscriber was stored and a confirmation email sent"),
+        (status = 400, description = "The form data is invalid, the form token is missing, invalid, too recent or was used already, the address is disposable or the list does not exist", body = String, content_type = "text/plain"),
+        (status = 429, description = "Too many attempts from the IP address or for the email address", body = String, content_type = "text/plain"),
+        (status = 500, description = "Something went wrong", body = String, content_type = "text/plain")
+    )
+)]
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(form, pool, email_client, base_url),
+    skip(form, pool, email_client, base_url, settings, spam_guard, redis_client, request),
     fields(
         subscriber_email = %form.email,
         subscriber_name = %form.name
     )
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn subscribe(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     email_client: web::Data<EmailClient>,
     base_url: web::Data<ApplicationBaseUrl>,
+    settings: web::Data<SubscriptionSettings>,
+    spam_guard: web::Data<SpamGuard>,
+    redis_client: web::Data<redis::Client>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, SubscribeError> {
-    let new_subscriber = form.0.try_into().map_err(SubscribeError::ValidationError)?;
+    let mut form = form.0;
+    if !form.website.is_empty() {
+        tracing::info!("Ignoring a subscription with the honeypot field filled in");
+        return Ok(HttpResponse::Ok().finish());
+    }
+    let form_token = std::mem::take(&mut form.form_token);
+    spam_guard
+        .verify_form_token(&form_token)
+        .map_err(SubscribeError::ValidationError)?;
+    let list = match form.list.take().filter(|list| !list.is_empty()) {
+        Some(list) => Some(
+            find_list_by_name(pool.get_ref(), &list)
+                .await
+                .context("Failed to look up the list")?
+                .ok_or_else(|| {
+                    SubscribeError::ValidationError(format!("There is no list named {}", list))
+                })?,
+        ),
+        None => None,
+    };
+    let opt_in = list
+        .as_ref()
+        .and_then(|list| list.opt_in)
+        .unwrap_or(settings.opt_in);
+    let new_subscriber: NewSubscriber = form.try_into().map_err(SubscribeError::ValidationError)?;
+    if spam_guard.is_disposable(&new_subscriber.email) {
+        return Err(SubscribeError::ValidationError(
+            "Disposable email addresses cannot subscribe".into(),
+        ));
+    }
+    let consent = ConsentContext::from_request(ConsentSource::Form, &request);
+    if !spam_guard
+        .check_rate_limits(
+            &redis_client,
+            consent.ip_address.as_deref(),
+            &new_subscriber.email,
+        )
+        .await?
+    {
+        return Err(SubscribeError::RateLimited);
+    }
+    // Claimed last, so correcting a typo does not need a fresh form
+    if !spam_guard
+        .claim_form_token(&redis_client, &form_token)
+        .await?
+    {
+        return Err(SubscribeError::ValidationError(
+            "The form was already submitted, please reload the page".into(),
+        ));
+    }
+    match register_subscriber(
+        &pool,
+        &email_client,
+        &base_url.0,
+        new_subscriber,
+        list.map(|list| list.list_id),
+        opt_in,
+        &consent,
+    )
+    .await
+    {
+        // Respond as if nothing happened, so the form cannot be used to probe the list
+        Ok(_) | Err(SubscribeError::Suppressed) => return Ok(HttpResponse::Ok().finish()),
+        Err(e) => return Err(e),
+    }
+}
+
+/// Store a new subscriber, optionally as a member of `list_id`. With double opt-in they
+/// are pending until they follow the link in the confirmation email they are sent, with
+/// single opt-in they are confirmed straight away. Suppressed addresses are refused. How
+/// their consent was obtained is recorded from `consent`.
+#[tracing::instrument(
+    name = "Register a new subscriber",
+    skip(pool, email_client, base_url, new_subscriber)
+)]
+pub async fn register_subscriber(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+    new_subscriber: NewSubscriber,
+    list_id: Option<Uuid>,
+    opt_in: OptIn,
+    consent: &ConsentContext,
+) -> Result<Uuid, SubscribeError> {
+    if is_suppressed(pool, new_subscriber.email.as_ref())
+        .await
+        .context("Failed to check the suppression list")?
+    {
+        return Err(SubscribeError::Suppressed);
+    }
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to acquire a Postgres connection from the pool")?;
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
-        .await
-        .context("Failed to insert new subscriber in the database")?;
-    let subscription_token = generate_subscription_token();
-    store_token(&mut transaction, subscriber_id, &subscription_token)
-        .await
-        .context("Failed to store confirmation token for new subscriber")?;
+    let status = match opt_in {
+        OptIn::Single => SubscriptionStatus::Confirmed,
+        OptIn::Double => SubscriptionStatus::PendingConfirmation,
+    };
+    let subscriber_id =
+        store_new_subscriber(&mut transaction, &new_subscriber, status, list_id, consent).await?;
+    let subscription_token = match opt_in {
+        OptIn::Single => None,
+        OptIn::Double => {
+            let subscription_token = generate_subscription_token();
+            store_token(&mut transaction, subscriber_id, &subscription_token)
+                .await
+                .context("Failed to store confirmation token for new subscriber")?;
+            Some(subscription_token)
+        }
+    };
     transaction
         .commit()
         .await
         .context("Failed to commit SQL transaction to store new subscriber")?;
-    send_confirmation_email(
-        &email_client,
-        new_subscriber,
-        &base_url.0,
-        &subscription_token,
+    if let Some(subscription_token) = subscription_token {
+        send_confirmation_email(
+            pool,
+            email_client,
+            new_subscriber,
+            base_url,
+            &subscription_token,
+        )
+        .await
+        .context("Failed to send a confirmation email")?;
+    }
+
+    return Ok(subscriber_id);
+}
+
+/// Insert a subscriber in `status` along with their consent, list membership and the
+/// webhook events announcing them. Confirming them is left to the caller.
+#[tracing::instrument(
+    name = "Store a new subscriber",
+    skip(transaction, new_subscriber, consent)
+)]
+pub async fn store_new_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    new_subscriber: &NewSubscriber,
+    status: SubscriptionStatus,
+    list_id: Option<Uuid>,
+    consent: &ConsentContext,
+) -> Result<Uuid, anyhow::Error> {
+    let subscriber_id = insert_subscriber(transaction, new_subscriber, status)
+        .await
+        .context("Failed to insert new subscriber in the database")?;
+    record_consent_event(transaction, subscriber_id, status, consent)
+        .await
+        .context("Failed to record the new subscriber's consent")?;
+    if let Some(list_id) = list_id {
+        add_to_list(transaction, list_id, subscriber_id)
+            .await
+            .context("Failed to add the new subscriber to the list")?;
+    }
+    let email = new_subscriber.email.as_ref().to_string();
+    let event = WebhookEvent::SubscriberCreated {
+        subscriber_id,
+        email: email.clone(),
+    };
+    enqueue_webhook_event(transaction, &event)
+        .await
+        .context("Failed to enqueue the subscriber created webhook")?;
+    if status == SubscriptionStatus::Confirmed {
+        let event = WebhookEvent::SubscriberConfirmed {
+            subscriber_id,
+            email,
+        };
+        enqueue_webhook_event(transaction, &event)
+            .await
+            .context("Failed to enqueue the subscriber confirmed webhook")?;
+    }
+    return Ok(subscriber_id);
+}
+
+/// Delete a subscriber along with their pending deliveries, returning their email if they
+/// existed. The withdrawal of consent is recorded in their consent history, which is kept.
+/// Subscribers of the `subscriber.unsubscribed` webhook are notified.
+#[tracing::instrument(name = "Remove a subscriber", skip(transaction))]
+pub async fn remove_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    consent: &ConsentContext,
+) -> Result<Option<String>, anyhow::Error> {
+    record_unsubscribe(transaction, subscriber_id, consent)
+        .await
+        .context("Failed to record the withdrawal of consent")?;
+    sqlx::query!(
+        "DELETE FROM subscription_tokens WHERE subscriber_id = $1",
+        subscriber_id
     )
+    .execute(&mut **transaction)
     .await
-    .context("Failed to send a confirmation email")?;
-
-    return Ok(HttpResponse::Ok().finish());
+    .context("Failed to delete the subscriber's tokens")?;
+    sqlx::query!(
+        "DELETE FROM issue_engagements WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to delete the subscriber's engagements")?;
+    let Some(deleted) = sqlx::query!(
+        "DELETE FROM subscriptions WHERE id = $1 RETURNING email",
+        subscriber_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await
+    .context("Failed to delete the subscriber")?
+    else {
+        return Ok(None);
+    };
+    sqlx::query!(
+        "DELETE FROM issue_delivery_queue WHERE subscriber_email = $1",
+        deleted.email
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to delete the subscriber's pending deliveries")?;
+    let event = WebhookEvent::SubscriberUnsubscribed {
+        subscriber_id,
+        email: deleted.email.clone(),
+    };
+    enqueue_webhook_event(transaction, &event)
+        .await
+        .context("Failed to enqueue the subscriber unsubscribed webhook")?;
+    return Ok(Some(deleted.email));
 }
 
 #[tracing::instrument(
@@ -128,28 +365,35 @@ pub async fn subscribe(
 pub async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     new_subscriber: &NewSubscriber,
+    status: SubscriptionStatus,
 ) -> Result<Uuid, sqlx::Error> {
     let subscriber_id = Uuid::new_v4();
     let query = sqlx::query!(
         r#"
         INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending_confirmation')
+        VALUES ($1, $2, $3, $4, $5)
         "#,
         subscriber_id,
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
         Utc::now(),
+        status.as_str(),
     );
     transaction.execute(query).await?;
     return Ok(subscriber_id);
 }
 
 async fn send_confirmation_email(
+    pool: &PgPool,
     email_client: &EmailClient,
     new_subscriber: NewSubscriber,
     base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), anyhow::Error> {
+    if is_suppressed(pool, new_subscriber.email.as_ref()).await? {
+        tracing::info!("Not sending a confirmation email to a suppressed address");
+        return Ok(());
+    }
     let confirmation_link = format!(
         "{}/subscriptions/confirm?subscription_token={}",
         base_url, subscription_token,
@@ -163,9 +407,10 @@ async fn send_confirmation_email(
         "Welcome to the newsletter!\nVisit {} to confirm your subscription.",
         confirmation_link
     );
-    return email_client
+    email_client
         .send_email(&new_subscriber.email, "Welcome!", &html_body, &text_body)
-        .await;
+        .await?;
+    return Ok(());
 }
 
 #[tracing::instrument(
@@ -190,7 +435,7 @@ async fn store_token(
 }
 
 /// Generate a random 25 character case-insensitive subscription token.
-fn generate_subscription_token() -> String {
+pub fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     return std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
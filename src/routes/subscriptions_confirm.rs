@@ -1,14 +1,18 @@
 //! src/routes/subscriptions_confirm.rs
 
 use actix_web::http::StatusCode;
-use actix_web::{web, HttpResponse, ResponseError};
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
 use anyhow::Context;
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
+use crate::consent::{record_consent_event, ConsentContext, ConsentSource};
+use crate::domain::SubscriptionStatus;
 use crate::routes::error_chain_fmt;
+use crate::webhooks::{enqueue_webhook_event, WebhookEvent};
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct Parameters {
     subscription_token: String,
 }
@@ -36,40 +40,88 @@ impl std::fmt::Debug for ConfirmSubscriberError {
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/subscriptions/confirm",
+    tag = "subscriptions",
+    params(Parameters),
+    responses(
+        (status = 200, description = "The subscription was confirmed"),
+        (status = 400, description = "The subscription token is missing"),
+        (status = 401, description = "No subscriber is associated with the token", body = String, content_type = "text/plain"),
+        (status = 500, description = "Something went wrong", body = String, content_type = "text/plain")
+    )
+)]
 #[tracing::instrument(
     name = "Confirm a pending subscriber"
-    skip(parameters)
+    skip(parameters, request)
 )]
 pub async fn confirm(
     parameters: web::Query<Parameters>,
     pool: web::Data<PgPool>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, ConfirmSubscriberError> {
     let subscriber_id = get_subscriber_id_from_token(&pool, &parameters.subscription_token)
         .await
         .context("Failed to retrieve the subscriber ID associated with the provided token")?
         .ok_or(ConfirmSubscriberError::UnauthorisedError)?;
-    confirm_subscriber(&pool, subscriber_id)
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let confirmed_email = confirm_subscriber(&mut transaction, subscriber_id)
         .await
         .context("Failed to update the subscriber status to `confirmed`")?;
+    if let Some(email) = confirmed_email {
+        let consent = ConsentContext::from_request(ConsentSource::ConfirmationLink, &request);
+        record_consent_event(
+            &mut transaction,
+            subscriber_id,
+            SubscriptionStatus::Confirmed,
+            &consent,
+        )
+        .await
+        .context("Failed to record the subscriber's confirmation")?;
+        let event = WebhookEvent::SubscriberConfirmed {
+            subscriber_id,
+            email,
+        };
+        enqueue_webhook_event(&mut transaction, &event)
+            .await
+            .context("Failed to enqueue the subscriber confirmed webhook")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to confirm a subscriber")?;
     return Ok(HttpResponse::Ok().finish());
 }
 
-#[tracing::instrument(name = "Mark subscriber as confirmed", skip(subscriber_id, pool))]
-pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query!(
+/// Returns the subscriber's email if they were not already confirmed.
+#[tracing::instrument(
+    name = "Mark subscriber as confirmed",
+    skip(subscriber_id, transaction)
+)]
+pub async fn confirm_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Option<String>, sqlx::Error> {
+    let confirmed = sqlx::query!(
         r#"
-        UPDATE subscriptions SET status = 'confirmed' WHERE id = $1
+        UPDATE subscriptions SET status = 'confirmed'
+        WHERE id = $1 AND status != 'confirmed'
+        RETURNING email
         "#,
         subscriber_id,
     )
-    .execute(pool)
+    .fetch_optional(&mut **transaction)
     .await
     .map_err(|e| {
         tracing::error!("Failed to execute query: {:?}", e);
         e
     })?;
 
-    return Ok(());
+    return Ok(confirmed.map(|r| r.email));
 }
 
 #[tracing::instrument(
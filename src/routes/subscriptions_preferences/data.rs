@@ -0,0 +1,406 @@
+//! src/routes/subscriptions_preferences/data.rs
+
+use actix_web::http::header::{ContentType, CONTENT_DISPOSITION};
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::preferences_path;
+use crate::data_requests::{erase_subscriber, export_subscriber_data, SubscriberDataExport};
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::preference_links::PreferenceLinks;
+use crate::routes::generate_subscription_token;
+use crate::startup::ApplicationBaseUrl;
+use crate::suppressions::is_suppressed;
+use crate::utils::{e400, e500, see_other};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = DataRequestForm)]
+pub struct DataRequestData {
+    subscriber: Uuid,
+    expires: i64,
+    signature: String,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ConfirmParameters {
+    /// From the link sent to the subscriber's address.
+    token: String,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = DataRequestConfirmationForm)]
+pub struct ConfirmData {
+    token: String,
+}
+
+/// What the subscriber asked for, stored with the token that confirms it.
+#[derive(Clone, Copy)]
+enum DataAction {
+    Export,
+    Erase,
+}
+
+impl DataAction {
+    fn as_str(&self) -> &'static str {
+        return match self {
+            Self::Export => "export",
+            Self::Erase => "erase",
+        };
+    }
+
+    fn parse(s: &str) -> Result<Self, anyhow::Error> {
+        return match s {
+            "export" => Ok(Self::Export),
+            "erase" => Ok(Self::Erase),
+            other => Err(anyhow::anyhow!("Unknown data request action: {}", other)),
+        };
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/subscriptions/preferences/export",
+    tag = "subscriptions",
+    request_body(content = DataRequestData, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "A link to download the data was emailed to the subscriber", headers(("Location" = String))),
+        (status = 400, description = "The signature is invalid or has expired"),
+        (status = 404, description = "The subscriber no longer exists")
+    )
+)]
+#[tracing::instrument(
+    name = "Request a subscriber's own data",
+    skip(form, pool, preference_links, email_client, base_url),
+    fields(subscriber_id = %form.subscriber)
+)]
+pub async fn request_data_export(
+    form: web::Form<DataRequestData>,
+    pool: web::Data<PgPool>,
+    preference_links: web::Data<PreferenceLinks>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    return request_data_action(
+        form.0,
+        DataAction::Export,
+        &pool,
+        &preference_links,
+        &email_client,
+        &base_url.0,
+    )
+    .await;
+}
+
+#[utoipa::path(
+    post,
+    path = "/subscriptions/preferences/erase",
+    tag = "subscriptions",
+    request_body(content = DataRequestData, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "A link to confirm the erasure was emailed to the subscriber", headers(("Location" = String))),
+        (status = 400, description = "The signature is invalid or has expired"),
+        (status = 404, description = "The subscriber no longer exists")
+    )
+)]
+#[tracing::instrument(
+    name = "Request the erasure of a subscriber's own data",
+    skip(form, pool, preference_links, email_client, base_url),
+    fields(subscriber_id = %form.subscriber)
+)]
+pub async fn request_erasure(
+    form: web::Form<DataRequestData>,
+    pool: web::Data<PgPool>,
+    preference_links: web::Data<PreferenceLinks>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    return request_data_action(
+        form.0,
+        DataAction::Erase,
+        &pool,
+        &preference_links,
+        &email_client,
+        &base_url.0,
+    )
+    .await;
+}
+
+/// The preference link only proves someone has seen an issue, so the request is
+/// confirmed through the subscriber's inbox before anything is handed out or erased.
+async fn request_data_action(
+    form: DataRequestData,
+    action: DataAction,
+    pool: &PgPool,
+    preference_links: &PreferenceLinks,
+    email_client: &EmailClient,
+    base_url: &str,
+) -> Result<HttpResponse, actix_web::Error> {
+    let DataRequestData {
+        subscriber,
+        expires,
+        signature,
+    } = form;
+    preference_links
+        .verify(subscriber, expires, &signature)
+        .map_err(e400)?;
+    let preferences_path = preferences_path(subscriber, expires, &signature);
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let Some(email) =
+        sqlx::query_scalar!("SELECT email FROM subscriptions WHERE id = $1", subscriber)
+            .fetch_optional(&mut *transaction)
+            .await
+            .context("Failed to retrieve the subscriber's email")
+            .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let email = SubscriberEmail::parse(email).map_err(e500)?;
+    // Pretend the link was sent, so the form does not reveal that the address is suppressed
+    if is_suppressed(pool, email.as_ref())
+        .await
+        .context("Failed to check the suppression list")
+        .map_err(e500)?
+    {
+        tracing::info!("Not sending a data request confirmation. The address is suppressed.");
+    } else {
+        let token = generate_subscription_token();
+        store_data_request(&mut transaction, subscriber, action, &token)
+            .await
+            .context("Failed to store the data request")
+            .map_err(e500)?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to store a data request")
+            .map_err(e500)?;
+        send_data_request_confirmation(email_client, &email, action, base_url, &token)
+            .await
+            .context("Failed to send a data request confirmation")
+            .map_err(e500)?;
+    }
+
+    FlashMessage::info(format!(
+        "We sent a link to {}. Follow it within an hour to {}.",
+        email.as_ref(),
+        match action {
+            DataAction::Export => "download your data",
+            DataAction::Erase => "erase your data",
+        }
+    ))
+    .send();
+    return Ok(see_other(&preferences_path));
+}
+
+#[utoipa::path(
+    get,
+    path = "/subscriptions/preferences/data",
+    tag = "subscriptions",
+    params(ConfirmParameters),
+    responses(
+        (status = 200, description = "A page to go ahead with the export or erasure", body = String, content_type = "text/html"),
+        (status = 401, description = "The token is unknown, was used already or has expired")
+    )
+)]
+#[tracing::instrument(name = "Show a data request confirmation", skip(parameters, pool))]
+pub async fn data_request_form(
+    parameters: web::Query<ConfirmParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // Following the link only shows a button, so mail scanners that fetch links
+    // cannot use up the token or erase anything
+    let action = sqlx::query_scalar!(
+        r#"
+        SELECT action FROM data_request_tokens
+        WHERE token = $1 AND requested_at > now() - interval '1 hour'
+        "#,
+        parameters.token
+    )
+    .fetch_optional(&**pool)
+    .await
+    .context("Failed to look up the data request")
+    .map_err(e500)?;
+    let Some(action) = action else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+    let (description, button) = match DataAction::parse(&action).map_err(e500)? {
+        DataAction::Export => (
+            "Download a copy of everything we store about you.",
+            "Download your data",
+        ),
+        DataAction::Erase => (
+            "Unsubscribe and erase everything we store about you. This cannot be undone.",
+            "Erase your data",
+        ),
+    };
+
+    return Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>Your Data</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    <p>{description}</p>
+    <form action="/subscriptions/preferences/data" method="post">
+        <input hidden type="text" name="token" value="{token}">
+        <button type="submit">{button}</button>
+    </form>
+</body>
+</html>"#,
+            token = htmlescape::encode_minimal(&parameters.token),
+        )));
+}
+
+#[utoipa::path(
+    post,
+    path = "/subscriptions/preferences/data",
+    tag = "subscriptions",
+    request_body(content = ConfirmData, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Everything stored about the subscriber, or a confirmation that it was erased", body = SubscriberDataExport),
+        (status = 401, description = "The token is unknown, was used already or has expired")
+    )
+)]
+#[tracing::instrument(name = "Fulfil a subscriber's data request", skip(form, pool))]
+pub async fn fulfil_data_request(
+    form: web::Form<ConfirmData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let Some(request) = sqlx::query!(
+        r#"
+        DELETE FROM data_request_tokens t
+        USING subscriptions s
+        WHERE t.token = $1
+            AND t.requested_at > now() - interval '1 hour'
+            AND s.id = t.subscriber_id
+        RETURNING t.action, s.email
+        "#,
+        form.token
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to redeem the data request token")
+    .map_err(e500)?
+    else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+
+    let response = match DataAction::parse(&request.action).map_err(e500)? {
+        DataAction::Export => {
+            let export = export_subscriber_data(&pool, &request.email)
+                .await
+                .map_err(e500)?;
+            HttpResponse::Ok()
+                .insert_header((
+                    CONTENT_DISPOSITION,
+                    r#"attachment; filename="subscriber-data.json""#,
+                ))
+                .json(export)
+        }
+        DataAction::Erase => {
+            erase_subscriber(&mut transaction, &request.email)
+                .await
+                .map_err(e500)?;
+            HttpResponse::Ok().content_type(ContentType::html()).body(
+                r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>Data Erased</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    <p>You have been unsubscribed and everything we stored about you has been erased.</p>
+</body>
+</html>"#,
+            )
+        }
+    };
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to fulfil a data request")
+        .map_err(e500)?;
+    return Ok(response);
+}
+
+/// Replaces any earlier request for the same action, so only the latest link works.
+#[tracing::instrument(name = "Store a data request", skip(transaction, action, token))]
+async fn store_data_request(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    action: DataAction,
+    token: &str,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        "DELETE FROM data_request_tokens WHERE subscriber_id = $1 AND action = $2",
+        subscriber_id,
+        action.as_str()
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO data_request_tokens (token, subscriber_id, action, requested_at)
+        VALUES ($1, $2, $3, now())
+        "#,
+        token,
+        subscriber_id,
+        action.as_str()
+    );
+    transaction.execute(query).await?;
+    return Ok(());
+}
+
+async fn send_data_request_confirmation(
+    email_client: &EmailClient,
+    email: &SubscriberEmail,
+    action: DataAction,
+    base_url: &str,
+    token: &str,
+) -> Result<(), anyhow::Error> {
+    let link = format!(
+        "{}/subscriptions/preferences/data?token={}",
+        base_url, token
+    );
+    let (subject, request) = match action {
+        DataAction::Export => (
+            "Download your data",
+            "Someone asked for a copy of everything we store about this address.",
+        ),
+        DataAction::Erase => (
+            "Confirm the erasure of your data",
+            "Someone asked for everything we store about this address to be erased.",
+        ),
+    };
+    let html_body = format!(
+        "{}<br /> \
+        Click <a href=\"{}\">here</a> within an hour to go ahead. If it was not you, ignore this email.",
+        request, link
+    );
+    let text_body = format!(
+        "{}\n\
+        Visit {} within an hour to go ahead. If it was not you, ignore this email.",
+        request, link
+    );
+    email_client
+        .send_email(email, subject, &html_body, &text_body)
+        .await?;
+    return Ok(());
+}
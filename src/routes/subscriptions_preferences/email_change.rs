@@ -0,0 +1,531 @@
+//! src/routes/subscriptions_preferences/email_change.rs
+
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::preferences_path;
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::preference_links::PreferenceLinks;
+use crate::routes::generate_subscription_token;
+use crate::startup::ApplicationBaseUrl;
+use crate::suppressions::is_suppressed;
+use crate::utils::{e400, e500, see_other};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = EmailChangeForm)]
+pub struct FormData {
+    subscriber: Uuid,
+    expires: i64,
+    signature: String,
+    /// The address to receive issues at from now on.
+    email: String,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ConfirmParameters {
+    /// From the link sent to the new address.
+    token: String,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CancelParameters {
+    /// From the notice sent to the current address.
+    token: String,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = EmailChangeConfirmationForm)]
+pub struct ConfirmData {
+    token: String,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = EmailChangeCancellationForm)]
+pub struct CancelData {
+    token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/subscriptions/preferences/email",
+    tag = "subscriptions",
+    request_body(content = FormData, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirect back to the preference center", headers(("Location" = String))),
+        (status = 400, description = "The signature is invalid or has expired"),
+        (status = 404, description = "The subscriber no longer exists")
+    )
+)]
+#[tracing::instrument(
+    name = "Request an email address change",
+    skip(form, pool, preference_links, email_client, base_url),
+    fields(subscriber_id = %form.subscriber)
+)]
+pub async fn request_email_change(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    preference_links: web::Data<PreferenceLinks>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData {
+        subscriber,
+        expires,
+        signature,
+        email,
+    } = form.0;
+    preference_links
+        .verify(subscriber, expires, &signature)
+        .map_err(e400)?;
+    let preferences_path = preferences_path(subscriber, expires, &signature);
+    let new_email = match SubscriberEmail::parse(email) {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other(&preferences_path));
+        }
+    };
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let Some(old_email) =
+        sqlx::query_scalar!("SELECT email FROM subscriptions WHERE id = $1", subscriber)
+            .fetch_optional(&mut *transaction)
+            .await
+            .context("Failed to look up the subscriber")
+            .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    // Pretend the link was sent, so the form cannot be used to probe the list
+    let skip = is_email_taken(&mut *transaction, new_email.as_ref())
+        .await
+        .context("Failed to check whether the new address is taken")
+        .map_err(e500)?
+        || is_suppressed(&pool, new_email.as_ref())
+            .await
+            .context("Failed to check the suppression list")
+            .map_err(e500)?;
+    if !skip {
+        let token = generate_subscription_token();
+        let cancel_token = generate_subscription_token();
+        store_email_change_request(
+            &mut transaction,
+            subscriber,
+            &new_email,
+            &token,
+            &cancel_token,
+        )
+        .await
+        .context("Failed to store the email change request")
+        .map_err(e500)?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to store an email change request")
+            .map_err(e500)?;
+        send_email_change_confirmation(&email_client, &new_email, &base_url.0, &token)
+            .await
+            .context("Failed to send an email change confirmation")
+            .map_err(e500)?;
+        // Whoever got hold of the preference link may not be the owner of the address
+        match SubscriberEmail::parse(old_email) {
+            Ok(old_email)
+                if is_suppressed(&pool, old_email.as_ref())
+                    .await
+                    .context("Failed to check the suppression list")
+                    .map_err(e500)? =>
+            {
+                tracing::info!(
+                    "Not notifying the current address of an email change. It is suppressed."
+                );
+            }
+            Ok(old_email) => send_email_change_notice(
+                &email_client,
+                &old_email,
+                &new_email,
+                &base_url.0,
+                &cancel_token,
+            )
+            .await
+            .context("Failed to notify the current address of an email change")
+            .map_err(e500)?,
+            Err(e) => tracing::warn!(
+                error.message = %e,
+                "Cannot notify the current address of an email change. It is invalid."
+            ),
+        }
+    }
+
+    FlashMessage::info(format!(
+        "We sent a confirmation link to {}. Your address changes once you follow it.",
+        new_email.as_ref()
+    ))
+    .send();
+    return Ok(see_other(&preferences_path));
+}
+
+#[utoipa::path(
+    get,
+    path = "/subscriptions/preferences/email/confirm",
+    tag = "subscriptions",
+    params(ConfirmParameters),
+    responses(
+        (status = 200, description = "A page to go ahead with the change", body = String, content_type = "text/html"),
+        (status = 401, description = "The token is unknown or has expired")
+    )
+)]
+#[tracing::instrument(name = "Show an email change confirmation", skip(parameters, pool))]
+pub async fn email_change_confirmation_form(
+    parameters: web::Query<ConfirmParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    // Following the link only shows a button, so mail scanners that fetch links
+    // cannot change the address on the subscriber's behalf
+    let new_email = sqlx::query_scalar!(
+        r#"
+        SELECT new_email FROM email_change_requests
+        WHERE token = $1 AND requested_at > now() - interval '24 hours'
+        "#,
+        parameters.token
+    )
+    .fetch_optional(&**pool)
+    .await
+    .context("Failed to look up the email change request")
+    .map_err(e500)?;
+    let Some(new_email) = new_email else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+
+    return Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>Confirm Your New Address</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    <p>Send upcoming issues to {new_email}?</p>
+    <form action="/subscriptions/preferences/email/confirm" method="post">
+        <input hidden type="text" name="token" value="{token}">
+        <button type="submit">Confirm the change</button>
+    </form>
+</body>
+</html>"#,
+            new_email = htmlescape::encode_minimal(&new_email),
+            token = htmlescape::encode_minimal(&parameters.token),
+        )));
+}
+
+#[utoipa::path(
+    post,
+    path = "/subscriptions/preferences/email/confirm",
+    tag = "subscriptions",
+    request_body(content = ConfirmData, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "The address was changed", body = String, content_type = "text/html"),
+        (status = 401, description = "The token is unknown or has expired"),
+        (status = 409, description = "Another subscriber took the address in the meantime")
+    )
+)]
+#[tracing::instrument(name = "Confirm an email address change", skip(form, pool))]
+pub async fn confirm_email_change(
+    form: web::Form<ConfirmData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let Some(request) = sqlx::query!(
+        r#"
+        SELECT r.subscriber_id, r.new_email, s.email AS old_email
+        FROM email_change_requests r
+        JOIN subscriptions s ON s.id = r.subscriber_id
+        WHERE r.token = $1 AND r.requested_at > now() - interval '24 hours'
+        FOR UPDATE OF s
+        "#,
+        form.token
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to look up the email change request")
+    .map_err(e500)?
+    else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+    if is_email_taken(&mut *transaction, &request.new_email)
+        .await
+        .context("Failed to check whether the new address is taken")
+        .map_err(e500)?
+    {
+        return Ok(HttpResponse::Conflict().finish());
+    }
+    change_email(
+        &mut transaction,
+        request.subscriber_id,
+        &request.old_email,
+        &request.new_email,
+    )
+    .await
+    .context("Failed to change the subscriber's email address")
+    .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to change a subscriber's email address")
+        .map_err(e500)?;
+
+    return Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>Email Address Changed</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    <p>Your email address has been updated. Upcoming issues will be sent to it.</p>
+</body>
+</html>"#,
+    ));
+}
+
+#[utoipa::path(
+    get,
+    path = "/subscriptions/preferences/email/cancel",
+    tag = "subscriptions",
+    params(CancelParameters),
+    responses(
+        (status = 200, description = "A page to call off the pending change", body = String, content_type = "text/html"),
+        (status = 401, description = "The token is unknown, or the change was already confirmed or cancelled")
+    )
+)]
+#[tracing::instrument(name = "Show an email change cancellation", skip(parameters, pool))]
+pub async fn email_change_cancellation_form(
+    parameters: web::Query<CancelParameters>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let new_email = sqlx::query_scalar!(
+        "SELECT new_email FROM email_change_requests WHERE cancel_token = $1",
+        parameters.token
+    )
+    .fetch_optional(&**pool)
+    .await
+    .context("Failed to look up the email change request")
+    .map_err(e500)?;
+    let Some(new_email) = new_email else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+
+    return Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>Cancel the Email Address Change</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    <p>Someone asked to send upcoming issues to {new_email} instead of this address.</p>
+    <form action="/subscriptions/preferences/email/cancel" method="post">
+        <input hidden type="text" name="token" value="{token}">
+        <button type="submit">Cancel the change</button>
+    </form>
+</body>
+</html>"#,
+            new_email = htmlescape::encode_minimal(&new_email),
+            token = htmlescape::encode_minimal(&parameters.token),
+        )));
+}
+
+#[utoipa::path(
+    post,
+    path = "/subscriptions/preferences/email/cancel",
+    tag = "subscriptions",
+    request_body(content = CancelData, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "The pending change was called off", body = String, content_type = "text/html"),
+        (status = 401, description = "The token is unknown, or the change was already confirmed or cancelled")
+    )
+)]
+#[tracing::instrument(name = "Cancel an email address change", skip(form, pool))]
+pub async fn cancel_email_change(
+    form: web::Form<CancelData>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let cancelled = sqlx::query_scalar!(
+        "DELETE FROM email_change_requests WHERE cancel_token = $1 RETURNING subscriber_id",
+        form.token
+    )
+    .fetch_optional(&**pool)
+    .await
+    .context("Failed to cancel the email change request")
+    .map_err(e500)?;
+    if cancelled.is_none() {
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+
+    return Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>Email Address Change Cancelled</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    <p>The change was cancelled. Issues keep going to this address.</p>
+</body>
+</html>"#,
+    ));
+}
+
+async fn is_email_taken<'e>(
+    executor: impl Executor<'e, Database = Postgres>,
+    email: &str,
+) -> Result<bool, sqlx::Error> {
+    return sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM subscriptions WHERE email = $1) AS "exists!""#,
+        email
+    )
+    .fetch_one(executor)
+    .await;
+}
+
+/// Replaces any earlier request, so only the latest link works.
+#[tracing::instrument(
+    name = "Store an email change request",
+    skip(transaction, token, cancel_token)
+)]
+async fn store_email_change_request(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    new_email: &SubscriberEmail,
+    token: &str,
+    cancel_token: &str,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        "DELETE FROM email_change_requests WHERE subscriber_id = $1",
+        subscriber_id
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO email_change_requests
+            (token, subscriber_id, new_email, requested_at, cancel_token)
+        VALUES ($1, $2, $3, now(), $4)
+        "#,
+        token,
+        subscriber_id,
+        new_email.as_ref(),
+        cancel_token
+    );
+    transaction.execute(query).await?;
+    return Ok(());
+}
+
+/// Swap the address, including on issues that are still waiting to be delivered.
+#[tracing::instrument(name = "Change a subscriber's email address", skip(transaction))]
+async fn change_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    old_email: &str,
+    new_email: &str,
+) -> Result<(), sqlx::Error> {
+    let query = sqlx::query!(
+        "UPDATE subscriptions SET email = $2 WHERE id = $1",
+        subscriber_id,
+        new_email
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        "UPDATE issue_delivery_queue SET subscriber_email = $2 WHERE subscriber_email = $1",
+        old_email,
+        new_email
+    );
+    transaction.execute(query).await?;
+    let query = sqlx::query!(
+        "DELETE FROM email_change_requests WHERE subscriber_id = $1",
+        subscriber_id
+    );
+    transaction.execute(query).await?;
+    return Ok(());
+}
+
+async fn send_email_change_confirmation(
+    email_client: &EmailClient,
+    new_email: &SubscriberEmail,
+    base_url: &str,
+    token: &str,
+) -> Result<(), anyhow::Error> {
+    let confirmation_link = format!(
+        "{}/subscriptions/preferences/email/confirm?token={}",
+        base_url, token
+    );
+    let html_body = format!(
+        "Someone asked for the newsletter to be sent to this address.<br /> \
+        Click <a href=\"{}\">here</a> to confirm the change.",
+        confirmation_link
+    );
+    let text_body = format!(
+        "Someone asked for the newsletter to be sent to this address.\n\
+        Visit {} to confirm the change.",
+        confirmation_link
+    );
+    email_client
+        .send_email(
+            new_email,
+            "Confirm your new address",
+            &html_body,
+            &text_body,
+        )
+        .await?;
+    return Ok(());
+}
+
+async fn send_email_change_notice(
+    email_client: &EmailClient,
+    old_email: &SubscriberEmail,
+    new_email: &SubscriberEmail,
+    base_url: &str,
+    cancel_token: &str,
+) -> Result<(), anyhow::Error> {
+    let cancel_link = format!(
+        "{}/subscriptions/preferences/email/cancel?token={}",
+        base_url, cancel_token
+    );
+    let html_body = format!(
+        "Someone asked for the newsletter to be sent to {} instead of this address.<br /> \
+        If it was not you, click <a href=\"{}\">here</a> to cancel the change.",
+        htmlescape::encode_minimal(new_email.as_ref()),
+        cancel_link
+    );
+    let text_body = format!(
+        "Someone asked for the newsletter to be sent to {} instead of this address.\n\
+        If it was not you, visit {} to cancel the change.",
+        new_email.as_ref(),
+        cancel_link
+    );
+    email_client
+        .send_email(
+            old_email,
+            "Your address is about to change",
+            &html_body,
+            &text_body,
+        )
+        .await?;
+    return Ok(());
+}
@@ -0,0 +1,178 @@
+//! src/routes/subscriptions_preferences/get.rs
+
+use actix_web::{http::header::ContentType, web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use super::PreferencesParameters;
+use crate::lists::get_list_memberships;
+use crate::preference_links::PreferenceLinks;
+use crate::utils::{e400, e500};
+
+struct SubscriberPreferences {
+    name: String,
+    email: String,
+    paused_until: Option<DateTime<Utc>>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/subscriptions/preferences",
+    tag = "subscriptions",
+    params(PreferencesParameters),
+    responses(
+        (status = 200, description = "The subscriber's preference center", body = String, content_type = "text/html"),
+        (status = 400, description = "The signature is invalid or has expired"),
+        (status = 404, description = "The subscriber no longer exists")
+    )
+)]
+#[tracing::instrument(
+    name = "Show the preference center",
+    skip(parameters, pool, preference_links, flash_messages),
+    fields(subscriber_id = %parameters.subscriber)
+)]
+pub async fn preferences_form(
+    parameters: web::Query<PreferencesParameters>,
+    pool: web::Data<PgPool>,
+    preference_links: web::Data<PreferenceLinks>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let PreferencesParameters {
+        subscriber,
+        expires,
+        signature,
+    } = parameters.into_inner();
+    preference_links
+        .verify(subscriber, expires, &signature)
+        .map_err(e400)?;
+    let Some(preferences) = get_preferences(&pool, subscriber).await.map_err(e500)? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let mut msg_html = String::new();
+    for m in flash_messages.iter() {
+        writeln!(
+            msg_html,
+            "<p><i>{}</i></p>",
+            htmlescape::encode_minimal(m.content())
+        )
+        .unwrap();
+    }
+    let lists = get_list_memberships(&pool, subscriber)
+        .await
+        .context("Failed to retrieve the subscriber's lists")
+        .map_err(e500)?;
+    let mut lists_html = String::new();
+    for list in lists {
+        writeln!(
+            lists_html,
+            r#"<label>
+            <input type="checkbox" name="lists" value="{}"{}>
+            {}
+        </label>
+        <br>"#,
+            list.list_id,
+            if list.is_member { " checked" } else { "" },
+            htmlescape::encode_minimal(&list.name),
+        )
+        .unwrap();
+    }
+    let pause_html = match preferences.paused_until.filter(|until| *until > Utc::now()) {
+        Some(until) => format!(
+            r#"<option value="" selected>Stay paused until {}</option>
+            <option value="resume">Resume delivery now</option>"#,
+            until.format("%Y-%m-%d")
+        ),
+        None => r#"<option value="" selected>Keep receiving issues</option>"#.to_string(),
+    };
+    let name = htmlescape::encode_minimal(&preferences.name);
+    let email = htmlescape::encode_minimal(&preferences.email);
+
+    return Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>Subscription Preferences</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    {msg_html}
+    <p>Preferences for {email}</p>
+    <form action="/subscriptions/preferences" method="post">
+        <input hidden type="text" name="subscriber" value="{subscriber}">
+        <input hidden type="text" name="expires" value="{expires}">
+        <input hidden type="text" name="signature" value="{signature}">
+        <label>
+            Name
+            <input type="text" name="name" value="{name}">
+        </label>
+        <br>
+        <p>Lists you receive:</p>
+        {lists_html}
+        <label>
+            Pause delivery
+            <select name="pause">
+            {pause_html}
+            <option value="7">For a week</option>
+            <option value="30">For a month</option>
+            <option value="90">For three months</option>
+            </select>
+        </label>
+        <br>
+        <button type="submit">Save preferences</button>
+    </form>
+    <form action="/subscriptions/preferences/email" method="post">
+        <input hidden type="text" name="subscriber" value="{subscriber}">
+        <input hidden type="text" name="expires" value="{expires}">
+        <input hidden type="text" name="signature" value="{signature}">
+        <label>
+            New email address
+            <input type="email" name="email" placeholder="Enter your new address">
+        </label>
+        <button type="submit">Change address</button>
+    </form>
+    <form action="/subscriptions/preferences/unsubscribe" method="post">
+        <input hidden type="text" name="subscriber" value="{subscriber}">
+        <input hidden type="text" name="expires" value="{expires}">
+        <input hidden type="text" name="signature" value="{signature}">
+        <button type="submit">Unsubscribe</button>
+    </form>
+    <form action="/subscriptions/preferences/export" method="post">
+        <input hidden type="text" name="subscriber" value="{subscriber}">
+        <input hidden type="text" name="expires" value="{expires}">
+        <input hidden type="text" name="signature" value="{signature}">
+        <button type="submit">Download your data</button>
+    </form>
+    <form action="/subscriptions/preferences/erase" method="post">
+        <input hidden type="text" name="subscriber" value="{subscriber}">
+        <input hidden type="text" name="expires" value="{expires}">
+        <input hidden type="text" name="signature" value="{signature}">
+        <button type="submit">Unsubscribe and erase your data</button>
+    </form>
+</body>
+</html>"#,
+            signature = htmlescape::encode_minimal(&signature),
+        )));
+}
+
+#[tracing::instrument(name = "Get a subscriber's preferences", skip(pool))]
+async fn get_preferences(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberPreferences>, anyhow::Error> {
+    let preferences = sqlx::query_as!(
+        SubscriberPreferences,
+        "SELECT name, email, paused_until FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the subscriber's preferences")?;
+    return Ok(preferences);
+}
@@ -0,0 +1,38 @@
+//! src/routes/subscriptions_preferences/mod.rs
+
+mod data;
+mod email_change;
+mod get;
+mod post;
+
+pub use data::{
+    __path_data_request_form, __path_fulfil_data_request, __path_request_data_export,
+    __path_request_erasure, data_request_form, fulfil_data_request, request_data_export,
+    request_erasure,
+};
+pub use email_change::{
+    __path_cancel_email_change, __path_confirm_email_change, __path_email_change_cancellation_form,
+    __path_email_change_confirmation_form, __path_request_email_change, cancel_email_change,
+    confirm_email_change, email_change_cancellation_form, email_change_confirmation_form,
+    request_email_change,
+};
+pub use get::{__path_preferences_form, preferences_form};
+pub use post::{__path_unsubscribe, __path_update_preferences, unsubscribe, update_preferences};
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PreferencesParameters {
+    subscriber: uuid::Uuid,
+    /// Unix timestamp after which the link stops working.
+    expires: i64,
+    /// Signs `subscriber` and `expires`, from the link at the bottom of each issue.
+    signature: String,
+}
+
+/// The preference center of the subscriber, as linked from issues.
+fn preferences_path(subscriber_id: uuid::Uuid, expires: i64, signature: &str) -> String {
+    return format!(
+        "/subscriptions/preferences?subscriber={}&expires={}&signature={}",
+        subscriber_id, expires, signature
+    );
+}
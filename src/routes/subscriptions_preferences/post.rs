@@ -0,0 +1,202 @@
+//! src/routes/subscriptions_preferences/post.rs
+
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use actix_web_lab::extract::UrlEncodedForm;
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::preferences_path;
+use crate::consent::{ConsentContext, ConsentSource};
+use crate::domain::SubscriberName;
+use crate::lists::set_list_memberships;
+use crate::preference_links::PreferenceLinks;
+use crate::routes::remove_subscriber;
+use crate::utils::{e400, e500, see_other};
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = PreferencesForm)]
+pub struct FormData {
+    subscriber: Uuid,
+    expires: i64,
+    signature: String,
+    name: String,
+    /// The lists to receive. The subscriber leaves every list that is not included.
+    #[serde(default)]
+    lists: Vec<Uuid>,
+    /// Empty to leave delivery as it is, `resume`, or a number of days to pause for.
+    #[serde(default)]
+    pause: String,
+}
+
+#[derive(serde::Deserialize, utoipa::ToSchema)]
+#[schema(as = UnsubscribeForm)]
+pub struct UnsubscribeData {
+    subscriber: Uuid,
+    expires: i64,
+    signature: String,
+}
+
+enum PauseChoice {
+    Keep,
+    Resume,
+    Days(u16),
+}
+
+impl PauseChoice {
+    fn parse(s: &str) -> Result<Self, String> {
+        return match s {
+            "" => Ok(Self::Keep),
+            "resume" => Ok(Self::Resume),
+            days => match days.parse::<u16>() {
+                Ok(days) if (1..=365).contains(&days) => Ok(Self::Days(days)),
+                _ => Err("Delivery can be paused for up to a year".into()),
+            },
+        };
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/subscriptions/preferences",
+    tag = "subscriptions",
+    request_body(content = FormData, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirect back to the preference center", headers(("Location" = String))),
+        (status = 400, description = "The signature is invalid or has expired"),
+        (status = 404, description = "The subscriber no longer exists")
+    )
+)]
+#[tracing::instrument(
+    name = "Update a subscriber's preferences",
+    skip(form, pool, preference_links),
+    fields(subscriber_id = %form.subscriber)
+)]
+pub async fn update_preferences(
+    // Checkboxes for several lists repeat the `lists` field, which `web::Form` rejects
+    form: UrlEncodedForm<FormData>,
+    pool: web::Data<PgPool>,
+    preference_links: web::Data<PreferenceLinks>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData {
+        subscriber,
+        expires,
+        signature,
+        name,
+        lists,
+        pause,
+    } = form.0;
+    preference_links
+        .verify(subscriber, expires, &signature)
+        .map_err(e400)?;
+    let preferences_path = preferences_path(subscriber, expires, &signature);
+    let parsed =
+        SubscriberName::parse(name).and_then(|name| Ok((name, PauseChoice::parse(&pause)?)));
+    let (name, pause) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other(&preferences_path));
+        }
+    };
+    let (update_pause, paused_until): (bool, Option<DateTime<Utc>>) = match pause {
+        PauseChoice::Keep => (false, None),
+        PauseChoice::Resume => (true, None),
+        PauseChoice::Days(days) => (true, Some(Utc::now() + Duration::days(days.into()))),
+    };
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let updated = sqlx::query_scalar!(
+        r#"
+        UPDATE subscriptions
+        SET name = $2, paused_until = CASE WHEN $3 THEN $4 ELSE paused_until END
+        WHERE id = $1
+        RETURNING id
+        "#,
+        subscriber,
+        name.as_ref(),
+        update_pause,
+        paused_until
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to update the subscriber's preferences")
+    .map_err(e500)?;
+    if updated.is_none() {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    set_list_memberships(&mut transaction, subscriber, &lists)
+        .await
+        .context("Failed to update the subscriber's lists")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update a subscriber's preferences")
+        .map_err(e500)?;
+
+    FlashMessage::info("Your preferences have been updated").send();
+    return Ok(see_other(&preferences_path));
+}
+
+#[utoipa::path(
+    post,
+    path = "/subscriptions/preferences/unsubscribe",
+    tag = "subscriptions",
+    request_body(content = UnsubscribeData, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "The subscriber was removed", body = String, content_type = "text/html"),
+        (status = 400, description = "The signature is invalid or has expired")
+    )
+)]
+#[tracing::instrument(
+    name = "Unsubscribe from the preference center",
+    skip(form, request, pool, preference_links),
+    fields(subscriber_id = %form.subscriber)
+)]
+pub async fn unsubscribe(
+    form: web::Form<UnsubscribeData>,
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    preference_links: web::Data<PreferenceLinks>,
+) -> Result<HttpResponse, actix_web::Error> {
+    preference_links
+        .verify(form.subscriber, form.expires, &form.signature)
+        .map_err(e400)?;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    // Unsubscribing twice, e.g. by resubmitting the form, is not an error
+    let consent = ConsentContext::from_request(ConsentSource::PreferenceCenter, &request);
+    remove_subscriber(&mut transaction, form.subscriber, &consent)
+        .await
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to unsubscribe a subscriber")
+        .map_err(e500)?;
+
+    return Ok(HttpResponse::Ok().content_type(ContentType::html()).body(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <title>Unsubscribed</title>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+</head>
+<body>
+    <p>You have been unsubscribed and will not receive any more issues.</p>
+</body>
+</html>"#,
+    ));
+}
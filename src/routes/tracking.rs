@@ -0,0 +1,131 @@
+//! src/routes/tracking.rs
+
+use actix_web::http::header::{CacheControl, CacheDirective, LOCATION};
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::tracking::Tracker;
+use crate::utils::e400;
+
+/// A transparent 1x1 GIF.
+const PIXEL: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OpenParameters {
+    issue: Uuid,
+    subscriber: Uuid,
+    signature: String,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ClickParameters {
+    issue: Uuid,
+    subscriber: Uuid,
+    /// The link the subscriber clicked on.
+    url: String,
+    signature: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/track/open",
+    tag = "tracking",
+    params(OpenParameters),
+    responses(
+        (status = 200, description = "A transparent tracking pixel", content_type = "image/gif"),
+        (status = 400, description = "The signature is invalid")
+    )
+)]
+#[tracing::instrument(name = "Track a newsletter open", skip(parameters, pool, tracker))]
+pub async fn track_open(
+    parameters: web::Query<OpenParameters>,
+    pool: web::Data<PgPool>,
+    tracker: web::Data<Tracker>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let OpenParameters {
+        issue,
+        subscriber,
+        signature,
+    } = parameters.into_inner();
+    tracker
+        .verify_open(issue, subscriber, &signature)
+        .map_err(e400)?;
+
+    record_engagement(&pool, issue, subscriber, "open", None).await;
+    return Ok(HttpResponse::Ok()
+        .content_type("image/gif")
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .body(PIXEL));
+}
+
+#[utoipa::path(
+    get,
+    path = "/track/click",
+    tag = "tracking",
+    params(ClickParameters),
+    responses(
+        (status = 302, description = "Redirect to the clicked link", headers(("Location" = String))),
+        (status = 400, description = "The signature is invalid")
+    )
+)]
+#[tracing::instrument(name = "Track a newsletter click", skip(parameters, pool, tracker))]
+pub async fn track_click(
+    parameters: web::Query<ClickParameters>,
+    pool: web::Data<PgPool>,
+    tracker: web::Data<Tracker>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let ClickParameters {
+        issue,
+        subscriber,
+        url,
+        signature,
+    } = parameters.into_inner();
+    tracker
+        .verify_click(issue, subscriber, &url, &signature)
+        .map_err(e400)?;
+
+    record_engagement(&pool, issue, subscriber, "click", Some(&url)).await;
+    return Ok(HttpResponse::Found()
+        .insert_header((LOCATION, url))
+        .finish());
+}
+
+/// Failing to record engagement must not break the reader's experience, so errors are only logged.
+#[tracing::instrument(skip(pool))]
+async fn record_engagement(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    subscriber_id: Uuid,
+    kind: &str,
+    url: Option<&str>,
+) {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO issue_engagements (newsletter_issue_id, subscriber_id, kind, url, occurred_at)
+        SELECT $1, $2, $3, $4, now()
+        WHERE
+            EXISTS (SELECT 1 FROM newsletter_issues WHERE newsletter_issue_id = $1) AND
+            EXISTS (SELECT 1 FROM subscriptions WHERE id = $2)
+        "#,
+        newsletter_issue_id,
+        subscriber_id,
+        kind,
+        url
+    )
+    .execute(pool)
+    .await;
+    if let Err(e) = result {
+        tracing::warn!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to record newsletter engagement"
+        );
+    }
+}
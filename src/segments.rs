@@ -0,0 +1,316 @@
+//! src/segments.rs
+
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use sqlx::{Executor, PgPool, Postgres};
+use uuid::Uuid;
+
+use crate::domain::{AttributeKey, AttributeValue, SubscriberTag};
+
+/// A condition a subscriber must meet to be in a segment.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SegmentRule {
+    HasTag(SubscriberTag),
+    AttributeEquals(AttributeKey, AttributeValue),
+    SubscribedAfter(DateTime<Utc>),
+    SubscribedBefore(DateTime<Utc>),
+}
+
+impl SegmentRule {
+    /// Accepts `tag = <tag>`, `attribute <key> = <value>`, `subscribed_at after <date>`
+    /// and `subscribed_at before <date>`. Values may be wrapped in double quotes, and
+    /// dates are either `YYYY-MM-DD` (midnight UTC) or RFC 3339 timestamps.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if let Some(tag) = s.strip_prefix("tag") {
+            let tag = tag
+                .trim_start()
+                .strip_prefix('=')
+                .ok_or_else(|| format!("Expected `tag = <tag>` in: {}", s))?;
+            return Ok(Self::HasTag(SubscriberTag::parse(unquote(tag))?));
+        }
+        if let Some(attribute) = s.strip_prefix("attribute ") {
+            let (key, value) = attribute
+                .split_once('=')
+                .ok_or_else(|| format!("Expected `attribute <key> = <value>` in: {}", s))?;
+            return Ok(Self::AttributeEquals(
+                AttributeKey::parse(key.trim())?,
+                AttributeValue::parse(unquote(value))?,
+            ));
+        }
+        if let Some(condition) = s.strip_prefix("subscribed_at ") {
+            if let Some(date) = condition.trim_start().strip_prefix("after ") {
+                return Ok(Self::SubscribedAfter(parse_date(unquote(date))?));
+            }
+            if let Some(date) = condition.trim_start().strip_prefix("before ") {
+                return Ok(Self::SubscribedBefore(parse_date(unquote(date))?));
+            }
+        }
+        return Err(format!("{} is not a valid segment rule", s));
+    }
+
+    fn kind(&self) -> &'static str {
+        return match self {
+            SegmentRule::HasTag(_) => "tag",
+            SegmentRule::AttributeEquals(_, _) => "attribute",
+            SegmentRule::SubscribedAfter(_) => "subscribed_after",
+            SegmentRule::SubscribedBefore(_) => "subscribed_before",
+        };
+    }
+
+    fn attribute_key(&self) -> Option<&str> {
+        return match self {
+            SegmentRule::AttributeEquals(key, _) => Some(key.as_ref()),
+            _ => None,
+        };
+    }
+
+    fn value(&self) -> String {
+        return match self {
+            SegmentRule::HasTag(tag) => tag.to_string(),
+            SegmentRule::AttributeEquals(_, value) => value.to_string(),
+            SegmentRule::SubscribedAfter(timestamp) | SegmentRule::SubscribedBefore(timestamp) => {
+                timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)
+            }
+        };
+    }
+
+    fn from_stored(kind: &str, attribute_key: Option<&str>, value: &str) -> Result<Self, String> {
+        return match (kind, attribute_key) {
+            ("tag", None) => Ok(Self::HasTag(SubscriberTag::parse(value)?)),
+            ("attribute", Some(key)) => Ok(Self::AttributeEquals(
+                AttributeKey::parse(key)?,
+                AttributeValue::parse(value)?,
+            )),
+            ("subscribed_after", None) => Ok(Self::SubscribedAfter(parse_date(value)?)),
+            ("subscribed_before", None) => Ok(Self::SubscribedBefore(parse_date(value)?)),
+            _ => Err(format!("{} is not a known kind of segment rule", kind)),
+        };
+    }
+}
+
+impl std::fmt::Display for SegmentRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            SegmentRule::HasTag(tag) => write!(f, "tag = {}", tag),
+            SegmentRule::AttributeEquals(key, value) => {
+                write!(f, "attribute {} = \"{}\"", key, value)
+            }
+            SegmentRule::SubscribedAfter(_) => write!(f, "subscribed_at after {}", self.value()),
+            SegmentRule::SubscribedBefore(_) => write!(f, "subscribed_at before {}", self.value()),
+        };
+    }
+}
+
+/// Parse rules separated by newlines or ` AND `. All of them have to match.
+pub fn parse_rules(s: &str) -> Result<Vec<SegmentRule>, String> {
+    let rules = s
+        .lines()
+        .flat_map(|line| line.split(" AND "))
+        .filter(|rule| !rule.trim().is_empty())
+        .map(SegmentRule::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    if rules.is_empty() {
+        return Err("A segment needs at least one rule".into());
+    }
+    return Ok(rules);
+}
+
+fn unquote(s: &str) -> &str {
+    let s = s.trim();
+    return s
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s);
+}
+
+fn parse_date(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    }
+    return DateTime::parse_from_rfc3339(s)
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .map_err(|_| format!("{} is not a valid date", s));
+}
+
+/// The name of a segment, as shown on the publish form.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SegmentName(String);
+
+impl SegmentName {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("The segment name cannot be empty".into());
+        }
+        let max_length = 64;
+        if s.chars().count() > max_length {
+            return Err(format!(
+                "The segment name must be at most {max_length} characters long"
+            ));
+        }
+        return Ok(Self(s.to_string()));
+    }
+}
+
+impl AsRef<str> for SegmentName {
+    fn as_ref(&self) -> &str {
+        return &self.0;
+    }
+}
+
+pub struct SegmentSummary {
+    pub segment_id: Uuid,
+    pub name: String,
+    pub rules: Vec<SegmentRule>,
+    /// How many subscribers an issue sent to just this segment would reach right now.
+    pub n_recipients: i64,
+}
+
+/// Store a new segment, returning `None` if one with the same name already exists.
+#[tracing::instrument(name = "Create a segment", skip(pool))]
+pub async fn create_segment(
+    pool: &PgPool,
+    name: &SegmentName,
+    rules: &[SegmentRule],
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let Some(segment_id) = sqlx::query_scalar!(
+        r#"
+        INSERT INTO segments (segment_id, name, created_at)
+        VALUES ($1, $2, now())
+        ON CONFLICT (name) DO NOTHING
+        RETURNING segment_id
+        "#,
+        Uuid::new_v4(),
+        name.as_ref()
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to store the segment")?
+    else {
+        return Ok(None);
+    };
+    for (position, rule) in rules.iter().enumerate() {
+        sqlx::query!(
+            r#"
+            INSERT INTO segment_rules (segment_id, position, kind, attribute_key, value)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            segment_id,
+            position as i16,
+            rule.kind(),
+            rule.attribute_key(),
+            rule.value()
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to store a segment rule")?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a new segment")?;
+    return Ok(Some(segment_id));
+}
+
+#[tracing::instrument(name = "Get segments", skip(pool))]
+pub async fn get_segments(pool: &PgPool) -> Result<Vec<SegmentSummary>, anyhow::Error> {
+    let segments = sqlx::query!(
+        r#"
+        SELECT segment_id, name, (
+            SELECT COUNT(*)
+            FROM issue_recipients('{}', segments.segment_id)
+            WHERE NOT email_is_suppressed(email)
+        ) AS "n_recipients!"
+        FROM segments
+        ORDER BY name
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve segments")?;
+    let rules = sqlx::query!(
+        r#"
+        SELECT segment_id, kind, attribute_key, value
+        FROM segment_rules
+        ORDER BY segment_id, position
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve segment rules")?;
+
+    let mut summaries = Vec::with_capacity(segments.len());
+    for segment in segments {
+        let rules = rules
+            .iter()
+            .filter(|r| r.segment_id == segment.segment_id)
+            .map(|r| SegmentRule::from_stored(&r.kind, r.attribute_key.as_deref(), &r.value))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!(e))
+            .context("A stored segment rule is invalid")?;
+        summaries.push(SegmentSummary {
+            segment_id: segment.segment_id,
+            name: segment.name,
+            rules,
+            n_recipients: segment.n_recipients,
+        });
+    }
+    return Ok(summaries);
+}
+
+#[tracing::instrument(name = "Check whether a segment exists", skip(executor))]
+pub async fn segment_exists<'e>(
+    executor: impl Executor<'e, Database = Postgres>,
+    segment_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    return sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM segments WHERE segment_id = $1) AS "exists!""#,
+        segment_id
+    )
+    .fetch_one(executor)
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_rules, SegmentRule};
+    use chrono::{TimeZone, Utc};
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn rules_can_be_split_by_lines_or_and() {
+        let rules =
+            parse_rules("tag = \"beta\" AND subscribed_at after 2024-01-01\nattribute plan = pro")
+                .unwrap();
+        assert_eq!(rules.len(), 3);
+        assert_eq!(
+            rules[1],
+            SegmentRule::SubscribedAfter(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn rules_round_trip_through_display() {
+        let rules = parse_rules(
+            "tag = beta\nattribute plan = \"pro plus\"\nsubscribed_at before 2024-06-01T12:00:00+02:00",
+        )
+        .unwrap();
+        for rule in rules {
+            assert_eq!(SegmentRule::parse(&rule.to_string()), Ok(rule));
+        }
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        assert_err!(parse_rules(""));
+        assert_err!(parse_rules("tag beta"));
+        assert_err!(parse_rules("subscribed_at after yesterday"));
+        assert_err!(parse_rules("name = alice"));
+        assert_ok!(parse_rules("tag=beta"));
+    }
+}
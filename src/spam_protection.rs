@@ -0,0 +1,226 @@
+//! src/spam_protection.rs
+
+use anyhow::Context;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+
+use crate::configuration::SpamProtectionSettings;
+use crate::domain::SubscriberEmail;
+
+const RATE_LIMIT_WINDOW_SECONDS: u64 = 60 * 60;
+
+/// Keeps bots from using the public subscribe form to mail-bomb arbitrary addresses.
+///
+/// The form carries a signed timestamp of when it was rendered, so submissions that come
+/// back faster than a person could type, or that never loaded the form at all, can be
+/// told apart. Each token is good for one submission, and attempts are counted per IP
+/// address and per email address, both in Redis.
+#[derive(Clone)]
+pub struct SpamGuard {
+    hmac_secret: Secret<String>,
+    settings: SpamProtectionSettings,
+}
+
+impl SpamGuard {
+    pub fn new(hmac_secret: Secret<String>, settings: SpamProtectionSettings) -> Self {
+        return Self {
+            hmac_secret,
+            settings,
+        };
+    }
+
+    /// A token to embed in the subscribe form as it is rendered.
+    pub fn form_token(&self) -> String {
+        let nonce = hex::encode(rand::random::<[u8; 16]>());
+        return self.form_token_at(Utc::now().timestamp(), &nonce);
+    }
+
+    pub fn verify_form_token(&self, token: &str) -> Result<(), String> {
+        return self.verify_form_token_at(token, Utc::now().timestamp());
+    }
+
+    /// Whether the address belongs to, or is a subdomain of, a disposable email provider.
+    pub fn is_disposable(&self, email: &SubscriberEmail) -> bool {
+        let Some((_, domain)) = email.as_ref().rsplit_once('@') else {
+            return false;
+        };
+        let domain = domain.to_lowercase();
+        return self
+            .settings
+            .disposable_email_domains
+            .iter()
+            .any(|blocked| {
+                let blocked = blocked.to_lowercase();
+                return domain == blocked || domain.ends_with(&format!(".{}", blocked));
+            });
+    }
+
+    /// Mark a verified form token as used, returning whether it had not been used before.
+    #[tracing::instrument(name = "Claim a form token", skip(self, redis_client, token))]
+    pub async fn claim_form_token(
+        &self,
+        redis_client: &redis::Client,
+        token: &str,
+    ) -> Result<bool, anyhow::Error> {
+        let mut connection = redis_client
+            .get_async_connection()
+            .await
+            .context("Failed to connect to Redis")?;
+        // Kept for as long as the token would be accepted, after which it expires anyway
+        let claimed: Option<String> = redis::cmd("SET")
+            .arg(format!("used_form_tokens:{}", token))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(self.settings.form_token_max_age_hours * 60 * 60)
+            .query_async(&mut connection)
+            .await
+            .context("Failed to claim the form token in Redis")?;
+        return Ok(claimed.is_some());
+    }
+
+    /// Count an attempt to subscribe `email` from `ip_address`, returning whether both are
+    /// still within their hourly limits.
+    #[tracing::instrument(name = "Check subscription rate limits", skip(self, redis_client))]
+    pub async fn check_rate_limits(
+        &self,
+        redis_client: &redis::Client,
+        ip_address: Option<&str>,
+        email: &SubscriberEmail,
+    ) -> Result<bool, anyhow::Error> {
+        let mut connection = redis_client
+            .get_async_connection()
+            .await
+            .context("Failed to connect to Redis")?;
+        if let Some(ip_address) = ip_address {
+            let key = format!("subscribe_attempts:ip:{}", ip_address);
+            if count_attempt(&mut connection, &key).await?
+                > self.settings.max_attempts_per_ip_per_hour
+            {
+                return Ok(false);
+            }
+        }
+        let key = format!("subscribe_attempts:email:{}", email.as_ref().to_lowercase());
+        return Ok(count_attempt(&mut connection, &key).await?
+            <= self.settings.max_attempts_per_email_per_hour);
+    }
+
+    fn form_token_at(&self, issued_at: i64, nonce: &str) -> String {
+        return format!("{}.{}.{}", issued_at, nonce, self.sign(issued_at, nonce));
+    }
+
+    fn verify_form_token_at(&self, token: &str, now: i64) -> Result<(), String> {
+        let invalid = || "The form is invalid, please reload the page".to_string();
+        let mut parts = token.splitn(3, '.');
+        let (Some(issued_at), Some(nonce), Some(signature)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let issued_at: i64 = issued_at.parse().map_err(|_| invalid())?;
+        let signature = hex::decode(signature).map_err(|_| invalid())?;
+        let mut mac = self.mac();
+        mac.update(form_message(issued_at, nonce).as_bytes());
+        mac.verify_slice(&signature).map_err(|_| invalid())?;
+
+        let age = now - issued_at;
+        if age < self.settings.min_form_fill_seconds as i64 {
+            return Err("The form was submitted too quickly".into());
+        }
+        if age > (self.settings.form_token_max_age_hours * 60 * 60) as i64 {
+            return Err("The form has expired, please reload the page".into());
+        }
+        return Ok(());
+    }
+
+    fn sign(&self, issued_at: i64, nonce: &str) -> String {
+        let mut mac = self.mac();
+        mac.update(form_message(issued_at, nonce).as_bytes());
+        return hex::encode(mac.finalize().into_bytes());
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        return Hmac::<Sha256>::new_from_slice(self.hmac_secret.expose_secret().as_bytes())
+            .unwrap();
+    }
+}
+
+fn form_message(issued_at: i64, nonce: &str) -> String {
+    return format!("subscribe_form:{}:{}", issued_at, nonce);
+}
+
+/// Increment a fixed-window counter, returning the number of attempts in the window so far.
+async fn count_attempt(
+    connection: &mut redis::aio::Connection,
+    key: &str,
+) -> Result<u64, anyhow::Error> {
+    let attempts: u64 = redis::cmd("INCR")
+        .arg(key)
+        .query_async(connection)
+        .await
+        .context("Failed to count the attempt in Redis")?;
+    if attempts == 1 {
+        redis::cmd("EXPIRE")
+            .arg(key)
+            .arg(RATE_LIMIT_WINDOW_SECONDS)
+            .query_async::<_, ()>(connection)
+            .await
+            .context("Failed to set the rate limit window in Redis")?;
+    }
+    return Ok(attempts);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpamGuard;
+    use crate::configuration::SpamProtectionSettings;
+    use crate::domain::SubscriberEmail;
+    use claims::{assert_err, assert_ok};
+    use secrecy::Secret;
+
+    fn guard() -> SpamGuard {
+        return SpamGuard::new(
+            Secret::new("secret".into()),
+            SpamProtectionSettings {
+                min_form_fill_seconds: 3,
+                form_token_max_age_hours: 24,
+                max_attempts_per_ip_per_hour: 20,
+                max_attempts_per_email_per_hour: 3,
+                disposable_email_domains: vec!["mailinator.com".into()],
+            },
+        );
+    }
+
+    #[test]
+    fn form_tokens_are_accepted_between_the_minimum_and_maximum_age() {
+        let token = guard().form_token_at(1_000, "nonce");
+
+        assert_err!(guard().verify_form_token_at(&token, 1_001));
+        assert_ok!(guard().verify_form_token_at(&token, 1_003));
+        assert_ok!(guard().verify_form_token_at(&token, 1_000 + 24 * 60 * 60));
+        assert_err!(guard().verify_form_token_at(&token, 1_001 + 24 * 60 * 60));
+    }
+
+    #[test]
+    fn tampered_form_tokens_are_rejected() {
+        let token = guard().form_token_at(1_000, "nonce");
+        let (_, signature) = token.rsplit_once('.').unwrap();
+
+        assert_err!(guard().verify_form_token_at(&format!("900.nonce.{}", signature), 1_010));
+        assert_err!(guard().verify_form_token_at(&format!("1000.other.{}", signature), 1_010));
+        assert_err!(guard().verify_form_token_at("1000.nonce", 1_010));
+        assert_err!(guard().verify_form_token_at("", 1_010));
+    }
+
+    #[test]
+    fn disposable_domains_and_their_subdomains_are_detected() {
+        let email = |e: &str| SubscriberEmail::parse(e.into()).unwrap();
+
+        assert!(guard().is_disposable(&email("bot@mailinator.com")));
+        assert!(guard().is_disposable(&email("bot@eu.MAILINATOR.com")));
+        assert!(!guard().is_disposable(&email("someone@notmailinator.com")));
+        assert!(!guard().is_disposable(&email("someone@gmail.com")));
+    }
+}
@@ -3,21 +3,38 @@
 use actix_session::storage::RedisSessionStore;
 use actix_session::SessionMiddleware;
 use actix_web::cookie::Key;
-use actix_web::dev::Server;
-use actix_web::{web, App, HttpServer};
+use actix_web::dev::{Server, ServerHandle};
+use actix_web::http::Method;
+use actix_web::{web, App, HttpServer, Route};
 use actix_web_flash_messages::storage::CookieMessageStore;
 use actix_web_flash_messages::FlashMessagesFramework;
 use actix_web_lab::middleware::from_fn;
+use anyhow::Context;
+use p256::ecdsa::VerifyingKey;
 use secrecy::{ExposeSecret, Secret};
+use sqlx::migrate::Migrator;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
 use std::net::TcpListener;
+use std::time::Duration;
 use tracing_actix_web::TracingLogger;
 
-use crate::authentication::reject_anonymous_users;
-use crate::configuration::{DatabaseSettings, Settings};
+use crate::authentication::{reject_anonymous_users, reject_invalid_api_tokens, DummyPasswordHash};
+use crate::bootstrap::bootstrap_admin;
+use crate::client_ip::TrustedProxies;
+use crate::configuration::{
+    DatabaseSettings, IdempotencySettings, PasswordHashingSettings, Settings, SubscriptionSettings,
+};
 use crate::email_client::EmailClient;
+use crate::idempotency::idempotent;
+use crate::metrics::{init_metrics, record_http_metrics};
+use crate::preference_links::PreferenceLinks;
 use crate::routes::*;
+use crate::spam_protection::SpamGuard;
+use crate::tracking::Tracker;
+
+/// The migrations in `./migrations`, embedded at compile time.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
 
 pub struct Application {
     port: u16,
@@ -32,6 +49,14 @@ pub struct HmacSecret(pub Secret<String>);
 impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
         let connection_pool = get_connection_pool(&configuration.database);
+        bootstrap_admin(
+            &connection_pool,
+            configuration.initial_admin.as_ref(),
+            &configuration.application.base_url,
+            &configuration.password_hashing,
+        )
+        .await?;
+        let event_webhook_key = configuration.email_client.event_webhook_key()?;
         let sender_email = configuration
             .email_client
             .sender()
@@ -50,6 +75,7 @@ impl Application {
         );
         let listener = TcpListener::bind(address).expect("Failed to bind to random port");
         let port = listener.local_addr().unwrap().port();
+        let shutdown_grace_period = configuration.application.shutdown_grace_period();
         let server = run(
             listener,
             connection_pool,
@@ -57,6 +83,12 @@ impl Application {
             configuration.application.base_url,
             configuration.application.hmac_secret,
             configuration.redis_uri,
+            configuration.password_hashing,
+            configuration.idempotency,
+            configuration.subscriptions,
+            TrustedProxies(configuration.application.trusted_proxies),
+            event_webhook_key,
+            shutdown_grace_period,
         )
         .await?;
 
@@ -67,6 +99,54 @@ impl Application {
         return self.port;
     }
 
+    /// Used to stop accepting new connections. In-flight requests get the shutdown grace period to finish.
+    pub fn server_handle(&self) -> ServerHandle {
+        return self.server.handle();
+    }
+
+    pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
+        return self.server.await;
+    }
+}
+
+/// Serves `/metrics` on its own port, away from the public routes.
+pub struct MetricsServer {
+    port: u16,
+    server: Server,
+}
+
+impl MetricsServer {
+    pub fn build(configuration: &Settings) -> Result<Self, anyhow::Error> {
+        init_metrics();
+        let db_pool = web::Data::new(get_connection_pool(&configuration.database));
+        let address = format!(
+            "{}:{}",
+            configuration.application.host, configuration.application.metrics_port
+        );
+        let listener = TcpListener::bind(address).context("Failed to bind the metrics port")?;
+        let port = listener.local_addr()?.port();
+        let server = HttpServer::new(move || {
+            App::new()
+                .wrap(TracingLogger::default())
+                .route("/metrics", web::get().to(metrics))
+                .app_data(db_pool.clone())
+        })
+        .listen(listener)?
+        .workers(1)
+        .disable_signals()
+        .run();
+
+        return Ok(Self { port, server });
+    }
+
+    pub fn port(&self) -> u16 {
+        return self.port;
+    }
+
+    pub fn server_handle(&self) -> ServerHandle {
+        return self.server.handle();
+    }
+
     pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
         return self.server.await;
     }
@@ -76,6 +156,21 @@ pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
     return PgPoolOptions::new().connect_lazy_with(configuration.with_db());
 }
 
+/// How many of the embedded migrations have not been applied to the database yet.
+pub async fn count_pending_migrations(pool: &PgPool) -> Result<usize, anyhow::Error> {
+    // `_sqlx_migrations` belongs to sqlx rather than our schema, hence the unchecked query.
+    let applied: Vec<i64> =
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(pool)
+            .await
+            .context("Failed to read the applied migrations")?;
+    return Ok(MIGRATOR
+        .iter()
+        .filter(|m| !applied.contains(&m.version))
+        .count());
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn run(
     listener: TcpListener,
     db_pool: PgPool,
@@ -83,14 +178,38 @@ async fn run(
     base_url: String,
     hmac_secret: Secret<String>,
     redis_uri: Secret<String>,
+    password_hashing: PasswordHashingSettings,
+    idempotency: IdempotencySettings,
+    subscriptions: SubscriptionSettings,
+    trusted_proxies: TrustedProxies,
+    event_webhook_key: VerifyingKey,
+    shutdown_grace_period: Duration,
 ) -> Result<Server, anyhow::Error> {
+    init_metrics();
     let db_pool = web::Data::new(db_pool);
     let email_client = web::Data::new(email_client);
+    let tracker = web::Data::new(Tracker::new(base_url.clone(), hmac_secret.clone()));
+    let preference_links = web::Data::new(PreferenceLinks::new(
+        base_url.clone(),
+        hmac_secret.clone(),
+        subscriptions.preference_link_validity_days,
+    ));
     let base_url = web::Data::new(ApplicationBaseUrl(base_url));
+    let dummy_password_hash = web::Data::new(DummyPasswordHash::new(&password_hashing).await?);
+    let password_hashing = web::Data::new(password_hashing);
+    let idempotency = web::Data::new(idempotency);
+    let spam_guard = web::Data::new(SpamGuard::new(
+        hmac_secret.clone(),
+        subscriptions.spam_protection.clone(),
+    ));
+    let subscriptions = web::Data::new(subscriptions);
+    let trusted_proxies = web::Data::new(trusted_proxies);
+    let event_webhook_key = web::Data::new(EventWebhookKey(event_webhook_key));
     let secret_key = Key::from(hmac_secret.expose_secret().as_bytes());
     let message_store = CookieMessageStore::builder(secret_key.clone()).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
     let redis_store = RedisSessionStore::new(redis_uri.expose_secret()).await?;
+    let redis_client = web::Data::new(redis::Client::open(redis_uri.expose_secret().as_str())?);
 
     let server = HttpServer::new(move || {
         App::new()
@@ -100,29 +219,241 @@ async fn run(
                 secret_key.clone(),
             ))
             .wrap(TracingLogger::default())
-            .route("/", web::get().to(home))
-            .route("/login", web::get().to(login_form))
-            .route("/login", web::post().to(login))
-            .route("/health_check", web::get().to(health_check))
-            // .route("/newsletters", web::post().to(publish_newsletter))
-            .route("/subscriptions", web::post().to(subscribe))
-            .route("/subscriptions/confirm", web::get().to(confirm))
+            .wrap(from_fn(record_http_metrics))
+            .configure(register(public_routes()))
             .service(
-                web::scope("/admin")
+                web::scope(ADMIN_SCOPE)
                     .wrap(from_fn(reject_anonymous_users))
-                    .route("/dashboard", web::get().to(admin_dashboard))
-                    .route("/newsletters", web::get().to(publish_newsletter_form))
-                    .route("/newsletters", web::post().to(publish_newsletter))
-                    .route("/password", web::get().to(change_password_form))
-                    .route("/password", web::post().to(change_password))
-                    .route("/logout", web::post().to(log_out)),
+                    .configure(register(admin_routes())),
+            )
+            .service(
+                web::scope(API_SCOPE)
+                    .wrap(from_fn(reject_invalid_api_tokens))
+                    .app_data(json_config())
+                    .app_data(query_config())
+                    .app_data(path_config())
+                    .configure(register(api_routes())),
             )
             .app_data(db_pool.clone())
             .app_data(email_client.clone())
             .app_data(base_url.clone())
+            .app_data(password_hashing.clone())
+            .app_data(dummy_password_hash.clone())
+            .app_data(idempotency.clone())
+            .app_data(subscriptions.clone())
+            .app_data(trusted_proxies.clone())
+            .app_data(event_webhook_key.clone())
+            .app_data(tracker.clone())
+            .app_data(preference_links.clone())
+            .app_data(spam_guard.clone())
+            .app_data(redis_client.clone())
     })
     .listen(listener)?
+    // `main` owns signal handling so the API and the workers shut down together
+    .disable_signals()
+    .shutdown_timeout(shutdown_grace_period.as_secs())
     .run();
 
     return Ok(server);
 }
+
+const ADMIN_SCOPE: &str = "/admin";
+const API_SCOPE: &str = "/api/v1";
+
+/// A route kept together with its method and path, so the route table can be listed
+/// without starting a server.
+pub struct AppRoute {
+    pub method: Method,
+    pub path: &'static str,
+    route: Route,
+}
+
+fn route(method: Method, path: &'static str, build: impl FnOnce(Route) -> Route) -> AppRoute {
+    let route = build(web::method(method.clone()));
+    return AppRoute {
+        method,
+        path,
+        route,
+    };
+}
+
+fn register(routes: Vec<AppRoute>) -> impl FnOnce(&mut web::ServiceConfig) {
+    return move |config| {
+        for AppRoute { path, route, .. } in routes {
+            config.route(path, route);
+        }
+    };
+}
+
+/// Every `(method, path)` the application serves, with the scope prefixes applied.
+pub fn registered_routes() -> Vec<(Method, String)> {
+    return [
+        ("", public_routes()),
+        (ADMIN_SCOPE, admin_routes()),
+        (API_SCOPE, api_routes()),
+    ]
+    .into_iter()
+    .flat_map(|(prefix, routes)| {
+        return routes
+            .into_iter()
+            .map(move |r| (r.method, format!("{}{}", prefix, r.path)));
+    })
+    .collect();
+}
+
+fn public_routes() -> Vec<AppRoute> {
+    return vec![
+        route(Method::GET, "/", |r| r.to(home)),
+        route(Method::GET, "/login", |r| r.to(login_form)),
+        route(Method::POST, "/login", |r| r.to(login)),
+        route(Method::GET, "/setup", |r| r.to(setup_form)),
+        route(Method::POST, "/setup", |r| r.to(setup)),
+        route(Method::GET, "/health_check", |r| r.to(health_check)),
+        route(Method::GET, "/ready", |r| r.to(ready)),
+        route(Method::POST, "/subscriptions", |r| r.to(subscribe)),
+        route(Method::GET, "/subscriptions/confirm", |r| r.to(confirm)),
+        route(Method::GET, "/subscriptions/preferences", |r| {
+            r.to(preferences_form)
+        }),
+        route(Method::POST, "/subscriptions/preferences", |r| {
+            r.to(update_preferences)
+        }),
+        route(
+            Method::POST,
+            "/subscriptions/preferences/unsubscribe",
+            |r| r.to(unsubscribe),
+        ),
+        route(Method::POST, "/subscriptions/preferences/email", |r| {
+            r.to(request_email_change)
+        }),
+        route(
+            Method::GET,
+            "/subscriptions/preferences/email/confirm",
+            |r| r.to(email_change_confirmation_form),
+        ),
+        route(
+            Method::POST,
+            "/subscriptions/preferences/email/confirm",
+            |r| r.to(confirm_email_change),
+        ),
+        route(
+            Method::GET,
+            "/subscriptions/preferences/email/cancel",
+            |r| r.to(email_change_cancellation_form),
+        ),
+        route(
+            Method::POST,
+            "/subscriptions/preferences/email/cancel",
+            |r| r.to(cancel_email_change),
+        ),
+        route(Method::POST, "/subscriptions/preferences/export", |r| {
+            r.to(request_data_export)
+        }),
+        route(Method::POST, "/subscriptions/preferences/erase", |r| {
+            r.to(request_erasure)
+        }),
+        route(Method::GET, "/subscriptions/preferences/data", |r| {
+            r.to(data_request_form)
+        }),
+        route(Method::POST, "/subscriptions/preferences/data", |r| {
+            r.to(fulfil_data_request)
+        }),
+        route(Method::GET, "/api/openapi.json", |r| r.to(openapi_json)),
+        route(Method::GET, "/track/open", |r| r.to(track_open)),
+        route(Method::GET, "/track/click", |r| r.to(track_click)),
+        route(Method::POST, "/email-events/sendgrid", |r| {
+            r.to(receive_email_events)
+        }),
+    ];
+}
+
+/// Served under `/admin`, to logged-in users only.
+fn admin_routes() -> Vec<AppRoute> {
+    return vec![
+        route(Method::GET, "/dashboard", |r| r.to(admin_dashboard)),
+        route(Method::GET, "/newsletters", |r| {
+            r.to(publish_newsletter_form)
+        }),
+        route(Method::POST, "/newsletters", |r| {
+            r.to(publish_newsletter)
+                .wrap(from_fn(idempotent_newsletter_submission))
+        }),
+        route(Method::GET, "/issues", |r| r.to(issues_list)),
+        route(Method::GET, "/subscribers", |r| r.to(subscribers_list)),
+        route(Method::GET, "/subscribers/{subscriber_id}", |r| {
+            r.to(subscriber_detail)
+        }),
+        route(Method::GET, "/lists", |r| r.to(lists_form)),
+        route(Method::POST, "/lists", |r| r.to(create_subscriber_list)),
+        route(Method::GET, "/segments", |r| r.to(segments_form)),
+        route(Method::POST, "/segments", |r| {
+            r.to(create_subscriber_segment)
+        }),
+        route(Method::GET, "/issues/{newsletter_issue_id}", |r| {
+            r.to(issue_detail)
+        }),
+        route(Method::GET, "/password", |r| r.to(change_password_form)),
+        route(Method::POST, "/password", |r| {
+            r.to(change_password).wrap(from_fn(idempotent))
+        }),
+        route(Method::POST, "/logout", |r| r.to(log_out)),
+        route(Method::GET, "/api-tokens", |r| r.to(api_tokens_form)),
+        route(Method::POST, "/api-tokens", |r| r.to(create_api_token)),
+        route(Method::POST, "/api-tokens/{api_token_id}/revoke", |r| {
+            r.to(revoke_api_token)
+        }),
+        route(Method::GET, "/webhooks", |r| r.to(webhooks_form)),
+        route(Method::POST, "/webhooks", |r| r.to(create_webhook_endpoint)),
+        route(
+            Method::POST,
+            "/webhooks/{webhook_endpoint_id}/delete",
+            |r| r.to(delete_webhook_endpoint),
+        ),
+        route(Method::GET, "/suppressions", |r| r.to(suppressions_form)),
+        route(Method::POST, "/suppressions", |r| {
+            r.to(add_suppression_entry)
+        }),
+        route(Method::POST, "/suppressions/{suppression_id}/delete", |r| {
+            r.to(delete_suppression)
+        }),
+        route(Method::GET, "/data-requests", |r| r.to(data_requests_form)),
+        route(Method::GET, "/data-requests/export", |r| {
+            r.to(export_subscriber)
+        }),
+        route(Method::POST, "/data-requests/erase", |r| {
+            r.to(erase_subscriber_data)
+        }),
+    ];
+}
+
+/// Served under `/api/v1`, to requests bearing a valid API token.
+fn api_routes() -> Vec<AppRoute> {
+    return vec![
+        route(Method::GET, "/subscribers", |r| r.to(list_subscribers)),
+        route(Method::POST, "/subscribers/import", |r| {
+            r.to(import_subscribers).wrap(from_fn(idempotent))
+        }),
+        route(Method::POST, "/subscribers", |r| {
+            r.to(create_subscriber).wrap(from_fn(idempotent))
+        }),
+        route(Method::GET, "/subscribers/{subscriber_id}", |r| {
+            r.to(get_subscriber)
+        }),
+        route(Method::PATCH, "/subscribers/{subscriber_id}", |r| {
+            r.to(update_subscriber)
+        }),
+        route(Method::DELETE, "/subscribers/{subscriber_id}", |r| {
+            r.to(delete_subscriber)
+        }),
+        route(Method::GET, "/issues", |r| r.to(list_issues)),
+        route(Method::POST, "/issues", |r| {
+            r.to(publish_issue).wrap(from_fn(idempotent))
+        }),
+        route(Method::GET, "/issues/{newsletter_issue_id}", |r| {
+            r.to(get_issue)
+        }),
+        route(Method::GET, "/issues/{newsletter_issue_id}/stats", |r| {
+            r.to(get_issue_stats)
+        }),
+    ];
+}
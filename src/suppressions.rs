@@ -0,0 +1,116 @@
+//! src/suppressions.rs
+
+use sqlx::{Executor, PgPool, Postgres};
+use uuid::Uuid;
+
+use crate::domain::SubscriberEmail;
+
+/// Why an address or domain must never be emailed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SuppressionReason {
+    Bounced,
+    Complained,
+    Manual,
+}
+
+impl SuppressionReason {
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            SuppressionReason::Bounced => "bounced",
+            SuppressionReason::Complained => "complained",
+            SuppressionReason::Manual => "manual",
+        };
+    }
+}
+
+/// An email address or a whole domain, normalised to lowercase.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SuppressionPattern(String);
+
+impl SuppressionPattern {
+    /// Accepts either `someone@example.com` or `example.com`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim().to_lowercase();
+        if s.contains('@') {
+            let email = SubscriberEmail::parse(s)?;
+            return Ok(Self(email.as_ref().to_string()));
+        }
+        let is_domain = s.contains('.')
+            && !s.starts_with('.')
+            && !s.ends_with('.')
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+        if is_domain {
+            return Ok(Self(s));
+        } else {
+            return Err(format!("{} is not a valid email address or domain", s));
+        }
+    }
+}
+
+impl AsRef<str> for SuppressionPattern {
+    fn as_ref(&self) -> &str {
+        return &self.0;
+    }
+}
+
+/// Whether `email`, or the domain it belongs to, is on the suppression list.
+#[tracing::instrument(name = "Check the suppression list", skip(pool))]
+pub async fn is_suppressed(pool: &PgPool, email: &str) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(r#"SELECT email_is_suppressed($1) AS "suppressed!""#, email)
+        .fetch_one(pool)
+        .await?;
+    return Ok(row.suppressed);
+}
+
+/// Add `pattern` to the suppression list. Existing entries are left untouched.
+#[tracing::instrument(name = "Add a suppression", skip(executor))]
+pub async fn add_suppression<'e>(
+    executor: impl Executor<'e, Database = Postgres>,
+    pattern: &SuppressionPattern,
+    reason: SuppressionReason,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO suppressions (suppression_id, pattern, reason, created_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (pattern) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        pattern.as_ref(),
+        reason.as_str()
+    )
+    .execute(executor)
+    .await?;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SuppressionPattern;
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn email_addresses_are_lowercased() {
+        assert_ok_eq!(
+            SuppressionPattern::parse(" Someone@Example.com "),
+            SuppressionPattern("someone@example.com".into())
+        );
+    }
+
+    #[test]
+    fn bare_domains_are_accepted() {
+        assert_ok_eq!(
+            SuppressionPattern::parse("Mailinator.com"),
+            SuppressionPattern("mailinator.com".into())
+        );
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        assert_err!(SuppressionPattern::parse(""));
+        assert_err!(SuppressionPattern::parse("localhost"));
+        assert_err!(SuppressionPattern::parse("not a domain.com"));
+        assert_err!(SuppressionPattern::parse("@example.com"));
+    }
+}
@@ -1,23 +1,56 @@
 //! src/telemetry.rs
+use std::collections::HashMap;
+
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::Resource;
 use tokio::task::JoinHandle;
-use tracing::{subscriber::set_global_default, Subscriber};
+use tracing::{subscriber::set_global_default, Span, Subscriber};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, EnvFilter, Registry};
 
+use crate::configuration::TelemetrySettings;
+
+/// The W3C header carrying the trace and span IDs of the caller.
+const TRACEPARENT: &str = "traceparent";
+
 pub fn get_subscriber<Sink>(
     name: String,
     env_filter: String,
     sink: Sink,
+    telemetry: &TelemetrySettings,
 ) -> impl Subscriber + Send + Sync
 where
     Sink: for<'a> MakeWriter<'a> + Send + Sync + 'static,
 {
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
+    let otlp_layer = telemetry.otlp_endpoint.as_ref().map(|endpoint| {
+        let tracer = opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(endpoint),
+            )
+            .with_trace_config(
+                opentelemetry_sdk::trace::config().with_resource(Resource::new(vec![
+                    KeyValue::new("service.name", name.clone()),
+                ])),
+            )
+            .install_batch(opentelemetry_sdk::runtime::Tokio)
+            .expect("Failed to install the OTLP trace exporter");
+        tracing_opentelemetry::layer().with_tracer(tracer)
+    });
     let formatting_layer = BunyanFormattingLayer::new(name, sink);
     let subscriber = Registry::default()
         .with(env_filter)
+        .with(otlp_layer)
         .with(JsonStorageLayer)
         .with(formatting_layer);
     return subscriber;
@@ -26,6 +59,30 @@ where
 pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
     LogTracer::init().expect("Failed to set logger");
     set_global_default(subscriber).expect("Failed to set subscriber");
+    // `TracingLogger` uses the global propagator to continue traces started by our callers.
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+}
+
+/// Flush spans that are still waiting to be exported.
+pub fn shutdown_tracing() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// The `traceparent` of the current span, if it is being exported.
+pub fn current_trace_context() -> Option<String> {
+    let mut carrier = HashMap::new();
+    TraceContextPropagator::new().inject_context(&Span::current().context(), &mut carrier);
+    return carrier.remove(TRACEPARENT);
+}
+
+/// Link `span` to the span a `traceparent` was taken from, e.g. in another process.
+pub fn link_to_trace_context(span: &Span, traceparent: &str) {
+    let carrier = HashMap::from([(TRACEPARENT.to_string(), traceparent.to_string())]);
+    let context = TraceContextPropagator::new().extract(&carrier);
+    let span_context = context.span().span_context().clone();
+    if span_context.is_valid() {
+        span.add_link(span_context);
+    }
 }
 
 pub fn spawn_blocking_with_tracing<F, R>(f: F) -> JoinHandle<R>
@@ -36,3 +93,39 @@ where
     let current_span = tracing::Span::current();
     return tokio::task::spawn_blocking(move || current_span.in_scope(f));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{current_trace_context, link_to_trace_context};
+    use claims::assert_none;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::trace::TracerProvider;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Registry;
+
+    #[test]
+    fn no_trace_context_is_captured_when_spans_are_not_exported() {
+        let span = tracing::info_span!("request");
+        let _guard = span.enter();
+
+        assert_none!(current_trace_context());
+    }
+
+    #[test]
+    fn the_trace_context_of_the_current_span_is_captured() {
+        let provider = TracerProvider::builder().build();
+        let tracer = provider.tracer("test");
+        let subscriber =
+            Registry::default().with(tracing_opentelemetry::layer().with_tracer(tracer));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!("request");
+            let traceparent = request.in_scope(current_trace_context).unwrap();
+            assert!(traceparent.starts_with("00-"));
+
+            // Linking must not panic, whichever span it is attached to.
+            link_to_trace_context(&tracing::info_span!("worker"), &traceparent);
+            link_to_trace_context(&tracing::info_span!("worker"), "garbage");
+        });
+    }
+}
@@ -0,0 +1,191 @@
+//! src/tracking.rs
+
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+
+/// Builds and verifies the signed open and click tracking URLs embedded in newsletter
+/// issues.
+///
+/// Signing stops the click endpoint from being used as an open redirect and
+/// stops anyone from recording engagement on behalf of other subscribers.
+#[derive(Clone)]
+pub struct Tracker {
+    base_url: String,
+    hmac_secret: Secret<String>,
+}
+
+impl Tracker {
+    pub fn new(base_url: String, hmac_secret: Secret<String>) -> Self {
+        return Self {
+            base_url,
+            hmac_secret,
+        };
+    }
+
+    pub fn open_url(&self, newsletter_issue_id: Uuid, subscriber_id: Uuid) -> String {
+        let signature = self.sign(&open_message(newsletter_issue_id, subscriber_id));
+        return format!(
+            "{}/track/open?issue={}&subscriber={}&signature={}",
+            self.base_url, newsletter_issue_id, subscriber_id, signature
+        );
+    }
+
+    pub fn click_url(&self, newsletter_issue_id: Uuid, subscriber_id: Uuid, url: &str) -> String {
+        let signature = self.sign(&click_message(newsletter_issue_id, subscriber_id, url));
+        return format!(
+            "{}/track/click?issue={}&subscriber={}&url={}&signature={}",
+            self.base_url,
+            newsletter_issue_id,
+            subscriber_id,
+            urlencoding::encode(url),
+            signature
+        );
+    }
+
+    pub fn verify_open(
+        &self,
+        newsletter_issue_id: Uuid,
+        subscriber_id: Uuid,
+        signature: &str,
+    ) -> Result<(), anyhow::Error> {
+        return self.verify(&open_message(newsletter_issue_id, subscriber_id), signature);
+    }
+
+    pub fn verify_click(
+        &self,
+        newsletter_issue_id: Uuid,
+        subscriber_id: Uuid,
+        url: &str,
+        signature: &str,
+    ) -> Result<(), anyhow::Error> {
+        return self.verify(
+            &click_message(newsletter_issue_id, subscriber_id, url),
+            signature,
+        );
+    }
+
+    /// Point every absolute link in `html` at the click tracker and append an open pixel.
+    pub fn instrument_html(
+        &self,
+        html: &str,
+        newsletter_issue_id: Uuid,
+        subscriber_id: Uuid,
+    ) -> String {
+        let mut output = String::with_capacity(html.len());
+        let mut rest = html;
+        while let Some(start) = rest.find("href=") {
+            let (before, after) = rest.split_at(start + "href=".len());
+            output.push_str(before);
+            let quote = match after.chars().next() {
+                Some(q @ ('"' | '\'')) => q,
+                _ => {
+                    rest = after;
+                    continue;
+                }
+            };
+            let Some(end) = after[1..].find(quote) else {
+                rest = after;
+                break;
+            };
+            let link = &after[1..end + 1];
+            let target = htmlescape::decode_html(link).unwrap_or_else(|_| link.to_string());
+            if target.starts_with("http://") || target.starts_with("https://") {
+                let tracked = self.click_url(newsletter_issue_id, subscriber_id, &target);
+                output.push(quote);
+                output.push_str(&htmlescape::encode_minimal(&tracked));
+                output.push(quote);
+            } else {
+                output.push_str(&after[..end + 2]);
+            }
+            rest = &after[end + 2..];
+        }
+        output.push_str(rest);
+
+        let pixel = format!(
+            r#"<img src="{}" width="1" height="1" alt="" style="display:none">"#,
+            htmlescape::encode_minimal(&self.open_url(newsletter_issue_id, subscriber_id))
+        );
+        match output.rfind("</body>") {
+            Some(i) => output.insert_str(i, &pixel),
+            None => output.push_str(&pixel),
+        }
+        return output;
+    }
+
+    fn sign(&self, message: &str) -> String {
+        let mut mac = self.mac();
+        mac.update(message.as_bytes());
+        return hex::encode(mac.finalize().into_bytes());
+    }
+
+    fn verify(&self, message: &str, signature: &str) -> Result<(), anyhow::Error> {
+        let signature = hex::decode(signature)?;
+        let mut mac = self.mac();
+        mac.update(message.as_bytes());
+        mac.verify_slice(&signature)?;
+        return Ok(());
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        return Hmac::<Sha256>::new_from_slice(self.hmac_secret.expose_secret().as_bytes())
+            .unwrap();
+    }
+}
+
+fn open_message(newsletter_issue_id: Uuid, subscriber_id: Uuid) -> String {
+    return format!("open:{}:{}", newsletter_issue_id, subscriber_id);
+}
+
+fn click_message(newsletter_issue_id: Uuid, subscriber_id: Uuid, url: &str) -> String {
+    return format!("click:{}:{}:{}", newsletter_issue_id, subscriber_id, url);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tracker;
+    use claims::{assert_err, assert_ok};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn tracker() -> Tracker {
+        return Tracker::new(
+            "https://newsletter.example".into(),
+            Secret::new("secret".into()),
+        );
+    }
+
+    fn signature(url: &str) -> String {
+        return url.rsplit_once("signature=").unwrap().1.to_string();
+    }
+
+    #[test]
+    fn click_signatures_are_bound_to_the_target_url() {
+        let (issue, subscriber) = (Uuid::new_v4(), Uuid::new_v4());
+        let url = tracker().click_url(issue, subscriber, "https://example.com/a");
+        let signature = signature(&url);
+
+        assert_ok!(tracker().verify_click(issue, subscriber, "https://example.com/a", &signature));
+        assert_err!(tracker().verify_click(issue, subscriber, "https://evil.com", &signature));
+        assert_err!(tracker().verify_open(issue, subscriber, &signature));
+    }
+
+    #[test]
+    fn absolute_links_are_rewritten_and_a_pixel_is_added() {
+        let (issue, subscriber) = (Uuid::new_v4(), Uuid::new_v4());
+        let html = r##"<body><a href="https://example.com/?a=1&amp;b=2">Read</a> <a href='#top'>Top</a></body>"##;
+
+        let output = tracker().instrument_html(html, issue, subscriber);
+
+        let expected_link = htmlescape::encode_minimal(&tracker().click_url(
+            issue,
+            subscriber,
+            "https://example.com/?a=1&b=2",
+        ));
+        assert!(output.contains(&format!(r#"<a href="{}">Read</a>"#, expected_link)));
+        assert!(output.contains(r#"<a href='#top'>Top</a>"#));
+        assert!(output.contains("/track/open?"));
+        assert!(output.ends_with(r#"style="display:none"></body>"#));
+    }
+}
@@ -1,6 +1,7 @@
 //! src/utils.rs
 
 use actix_web::http::header::LOCATION;
+use actix_web::http::StatusCode;
 use actix_web::HttpResponse;
 
 pub fn e400<T>(e: T) -> actix_web::Error
@@ -22,3 +23,15 @@ pub fn see_other(location: &str) -> HttpResponse {
         .insert_header((LOCATION, location))
         .finish();
 }
+
+/// Error body returned by every JSON API endpoint.
+#[derive(serde::Serialize, utoipa::ToSchema)]
+pub struct ErrorBody {
+    pub error: String,
+}
+
+pub fn json_error(status_code: StatusCode, message: impl Into<String>) -> HttpResponse {
+    return HttpResponse::build(status_code).json(ErrorBody {
+        error: message.into(),
+    });
+}
@@ -0,0 +1,259 @@
+//! src/webhooks/delivery_worker.rs
+
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use tokio_util::sync::CancellationToken;
+use tracing::{field::display, Span};
+use uuid::Uuid;
+
+use crate::{
+    configuration::Settings,
+    issue_delivery_worker::ExecutionOutcome,
+    shutdown::sleep_unless_shutdown,
+    startup::get_connection_pool,
+    worker_heartbeat::{Heartbeat, WEBHOOK_DELIVERY_WORKER},
+};
+
+/// Deliveries are abandoned after this many failed retries.
+const MAX_RETRIES: i16 = 8;
+
+type PgTransaction = Transaction<'static, Postgres>;
+
+struct WebhookTask {
+    webhook_delivery_id: Uuid,
+    event_id: Uuid,
+    event_type: String,
+    payload: String,
+    n_retries: i16,
+    url: String,
+    secret: Secret<String>,
+}
+
+pub fn webhook_client() -> reqwest::Client {
+    return reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap();
+}
+
+/// HMAC-SHA256 of `<timestamp>.<payload>`, hex encoded. Receivers recompute it
+/// with the endpoint secret to check the request came from us and was not replayed.
+pub fn sign_payload(secret: &Secret<String>, timestamp: i64, payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.expose_secret().as_bytes()).unwrap();
+    mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+    return hex::encode(mac.finalize().into_bytes());
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        webhook_delivery_id=tracing::field::Empty,
+        event_type=tracing::field::Empty
+    ),
+    err
+)]
+pub async fn try_execute_webhook_task(
+    pool: &PgPool,
+    http_client: &reqwest::Client,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let task = dequeue_task(pool).await?;
+    if task.is_none() {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    }
+
+    let (transaction, task) = task.unwrap();
+
+    Span::current()
+        .record("webhook_delivery_id", display(task.webhook_delivery_id))
+        .record("event_type", display(&task.event_type));
+
+    match send_webhook(http_client, &task).await {
+        Ok(()) => delete_task(transaction, task.webhook_delivery_id).await?,
+        Err(e) if task.n_retries < MAX_RETRIES => {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to deliver a webhook. Retrying later."
+            );
+            reschedule_task(transaction, &task).await?;
+        }
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to deliver a webhook. Giving up."
+            );
+            delete_task(transaction, task.webhook_delivery_id).await?;
+        }
+    }
+
+    return Ok(ExecutionOutcome::TaskCompleted);
+}
+
+async fn send_webhook(
+    http_client: &reqwest::Client,
+    task: &WebhookTask,
+) -> Result<(), anyhow::Error> {
+    let timestamp = Utc::now().timestamp();
+    let signature = sign_payload(&task.secret, timestamp, &task.payload);
+    http_client
+        .post(&task.url)
+        .header("Content-Type", "application/json")
+        .header("X-Webhook-Id", task.event_id.to_string())
+        .header("X-Webhook-Event", &task.event_type)
+        .header("X-Webhook-Timestamp", timestamp.to_string())
+        .header("X-Webhook-Signature", format!("sha256={}", signature))
+        .body(task.payload.clone())
+        .send()
+        .await
+        .context("Failed to send the webhook request")?
+        .error_for_status()
+        .context("The webhook endpoint returned an error")?;
+    return Ok(());
+}
+
+/// Exponential backoff: 30s, 1m, 2m, 4m, ... after each failed attempt.
+fn retry_delay(n_retries: i16) -> chrono::Duration {
+    return chrono::Duration::seconds(30 * 2_i64.pow(n_retries as u32));
+}
+
+#[tracing::instrument(skip_all)]
+async fn dequeue_task(
+    pool: &PgPool,
+) -> Result<Option<(PgTransaction, WebhookTask)>, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let r = sqlx::query!(
+        r#"
+        SELECT
+            q.webhook_delivery_id,
+            q.event_id,
+            q.event_type,
+            q.payload,
+            q.n_retries,
+            e.url,
+            e.secret
+        FROM webhook_delivery_queue q
+        JOIN webhook_endpoints e ON e.webhook_endpoint_id = q.webhook_endpoint_id
+        WHERE q.execute_after <= now()
+        FOR UPDATE OF q
+        SKIP LOCKED
+        LIMIT 1
+        "#
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    if let Some(r) = r {
+        return Ok(Some((
+            transaction,
+            WebhookTask {
+                webhook_delivery_id: r.webhook_delivery_id,
+                event_id: r.event_id,
+                event_type: r.event_type,
+                payload: r.payload,
+                n_retries: r.n_retries,
+                url: r.url,
+                secret: Secret::new(r.secret),
+            },
+        )));
+    } else {
+        return Ok(None);
+    }
+}
+
+#[tracing::instrument(skip_all)]
+async fn reschedule_task(
+    mut transaction: PgTransaction,
+    task: &WebhookTask,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE webhook_delivery_queue
+        SET n_retries = n_retries + 1, execute_after = $2
+        WHERE webhook_delivery_id = $1
+        "#,
+        task.webhook_delivery_id,
+        Utc::now() + retry_delay(task.n_retries)
+    );
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+    return Ok(());
+}
+
+#[tracing::instrument(skip_all)]
+async fn delete_task(
+    mut transaction: PgTransaction,
+    webhook_delivery_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM webhook_delivery_queue
+        WHERE webhook_delivery_id = $1
+        "#,
+        webhook_delivery_id
+    );
+    transaction.execute(query).await?;
+    transaction.commit().await?;
+    return Ok(());
+}
+
+async fn worker_loop(
+    pool: PgPool,
+    http_client: reqwest::Client,
+    shutdown: CancellationToken,
+) -> Result<(), anyhow::Error> {
+    let mut heartbeat = Heartbeat::new(WEBHOOK_DELIVERY_WORKER);
+    let mut n_completed: u64 = 0;
+    while !shutdown.is_cancelled() {
+        heartbeat.beat(&pool).await;
+        match try_execute_webhook_task(&pool, &http_client).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                sleep_unless_shutdown(Duration::from_secs(10), &shutdown).await;
+            }
+            Err(_) => {
+                sleep_unless_shutdown(Duration::from_secs(1), &shutdown).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => n_completed += 1,
+        }
+    }
+    tracing::info!(n_completed, "Webhook delivery worker drained");
+    return Ok(());
+}
+
+pub async fn run_webhook_worker_until_stopped(
+    configuration: Settings,
+    shutdown: CancellationToken,
+) -> Result<(), anyhow::Error> {
+    let connection_pool = get_connection_pool(&configuration.database);
+    return worker_loop(connection_pool, webhook_client(), shutdown).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{retry_delay, sign_payload};
+    use secrecy::Secret;
+
+    #[test]
+    fn retries_back_off_exponentially() {
+        assert_eq!(retry_delay(0).num_seconds(), 30);
+        assert_eq!(retry_delay(1).num_seconds(), 60);
+        assert_eq!(retry_delay(4).num_seconds(), 480);
+    }
+
+    #[test]
+    fn signatures_depend_on_the_secret_timestamp_and_payload() {
+        let secret = Secret::new("whsec_secret".to_string());
+        let signature = sign_payload(&secret, 1700000000, "{}");
+        assert_eq!(signature.len(), 64);
+        assert_eq!(signature, sign_payload(&secret, 1700000000, "{}"));
+        assert_ne!(signature, sign_payload(&secret, 1700000001, "{}"));
+        assert_ne!(signature, sign_payload(&secret, 1700000000, "{ }"));
+        let other_secret = Secret::new("whsec_other".to_string());
+        assert_ne!(signature, sign_payload(&other_secret, 1700000000, "{}"));
+    }
+}
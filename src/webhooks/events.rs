@@ -0,0 +1,177 @@
+//! src/webhooks/events.rs
+
+use chrono::{SecondsFormat, Utc};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use sqlx::{Executor, Postgres, Transaction};
+use uuid::Uuid;
+
+const WEBHOOK_SECRET_PREFIX: &str = "whsec_";
+
+/// The kinds of event a webhook endpoint can subscribe to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WebhookEventType {
+    SubscriberCreated,
+    SubscriberConfirmed,
+    SubscriberUnsubscribed,
+    IssuePublished,
+    DeliveryFailed,
+}
+
+impl WebhookEventType {
+    pub const ALL: [WebhookEventType; 5] = [
+        WebhookEventType::SubscriberCreated,
+        WebhookEventType::SubscriberConfirmed,
+        WebhookEventType::SubscriberUnsubscribed,
+        WebhookEventType::IssuePublished,
+        WebhookEventType::DeliveryFailed,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            WebhookEventType::SubscriberCreated => "subscriber.created",
+            WebhookEventType::SubscriberConfirmed => "subscriber.confirmed",
+            WebhookEventType::SubscriberUnsubscribed => "subscriber.unsubscribed",
+            WebhookEventType::IssuePublished => "issue.published",
+            WebhookEventType::DeliveryFailed => "delivery.failed",
+        };
+    }
+}
+
+impl TryFrom<String> for WebhookEventType {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        return WebhookEventType::ALL
+            .into_iter()
+            .find(|event_type| event_type.as_str() == s)
+            .ok_or_else(|| format!("{} is not a valid webhook event type", s));
+    }
+}
+
+impl std::fmt::Display for WebhookEventType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return self.as_str().fmt(f);
+    }
+}
+
+/// Something that happened which external systems may want to hear about.
+#[derive(Clone, Debug)]
+pub enum WebhookEvent {
+    SubscriberCreated {
+        subscriber_id: Uuid,
+        email: String,
+    },
+    SubscriberConfirmed {
+        subscriber_id: Uuid,
+        email: String,
+    },
+    SubscriberUnsubscribed {
+        subscriber_id: Uuid,
+        email: String,
+    },
+    IssuePublished {
+        newsletter_issue_id: Uuid,
+        title: String,
+    },
+    DeliveryFailed {
+        newsletter_issue_id: Uuid,
+        subscriber_email: String,
+    },
+}
+
+impl WebhookEvent {
+    pub fn event_type(&self) -> WebhookEventType {
+        return match self {
+            WebhookEvent::SubscriberCreated { .. } => WebhookEventType::SubscriberCreated,
+            WebhookEvent::SubscriberConfirmed { .. } => WebhookEventType::SubscriberConfirmed,
+            WebhookEvent::SubscriberUnsubscribed { .. } => WebhookEventType::SubscriberUnsubscribed,
+            WebhookEvent::IssuePublished { .. } => WebhookEventType::IssuePublished,
+            WebhookEvent::DeliveryFailed { .. } => WebhookEventType::DeliveryFailed,
+        };
+    }
+
+    fn data(&self) -> serde_json::Value {
+        return match self {
+            WebhookEvent::SubscriberCreated {
+                subscriber_id,
+                email,
+            }
+            | WebhookEvent::SubscriberConfirmed {
+                subscriber_id,
+                email,
+            }
+            | WebhookEvent::SubscriberUnsubscribed {
+                subscriber_id,
+                email,
+            } => serde_json::json!({
+                "subscriber_id": subscriber_id,
+                "email": email,
+            }),
+            WebhookEvent::IssuePublished {
+                newsletter_issue_id,
+                title,
+            } => serde_json::json!({
+                "newsletter_issue_id": newsletter_issue_id,
+                "title": title,
+            }),
+            WebhookEvent::DeliveryFailed {
+                newsletter_issue_id,
+                subscriber_email,
+            } => serde_json::json!({
+                "newsletter_issue_id": newsletter_issue_id,
+                "subscriber_email": subscriber_email,
+            }),
+        };
+    }
+}
+
+/// Queue `event` for delivery to every endpoint subscribed to its type.
+///
+/// Runs inside the caller's transaction, so the event is only sent if the
+/// change it describes is committed.
+#[tracing::instrument(skip(transaction))]
+pub async fn enqueue_webhook_event(
+    transaction: &mut Transaction<'_, Postgres>,
+    event: &WebhookEvent,
+) -> Result<(), sqlx::Error> {
+    let event_id = Uuid::new_v4();
+    let event_type = event.event_type();
+    let payload = serde_json::json!({
+        "id": event_id,
+        "type": event_type.as_str(),
+        "created_at": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        "data": event.data(),
+    })
+    .to_string();
+
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO webhook_delivery_queue (
+            webhook_delivery_id,
+            webhook_endpoint_id,
+            event_id,
+            event_type,
+            payload,
+            execute_after
+        )
+        SELECT gen_random_uuid(), webhook_endpoint_id, $1, $2, $3, now()
+        FROM webhook_endpoints
+        WHERE $2 = ANY(event_types)
+        "#,
+        event_id,
+        event_type.as_str(),
+        payload
+    );
+    transaction.execute(query).await?;
+    return Ok(());
+}
+
+/// Generate the secret used to sign the payloads sent to a webhook endpoint.
+pub fn generate_webhook_secret() -> String {
+    let mut rng = thread_rng();
+    let secret: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect();
+    return format!("{}{}", WEBHOOK_SECRET_PREFIX, secret);
+}
@@ -0,0 +1,9 @@
+//! src/webhooks/mod.rs
+
+mod delivery_worker;
+mod events;
+
+pub use delivery_worker::{
+    run_webhook_worker_until_stopped, sign_payload, try_execute_webhook_task, webhook_client,
+};
+pub use events::{enqueue_webhook_event, generate_webhook_secret, WebhookEvent, WebhookEventType};
@@ -0,0 +1,61 @@
+//! src/worker_heartbeat.rs
+
+use std::time::{Duration, Instant};
+
+use sqlx::PgPool;
+
+pub const ISSUE_DELIVERY_WORKER: &str = "issue_delivery_worker";
+pub const WEBHOOK_DELIVERY_WORKER: &str = "webhook_delivery_worker";
+pub const WORKERS: [&str; 2] = [ISSUE_DELIVERY_WORKER, WEBHOOK_DELIVERY_WORKER];
+
+/// A busy worker refreshes its heartbeat at most this often.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Tracks when a worker last told Postgres it was alive.
+pub struct Heartbeat {
+    worker_name: &'static str,
+    last_recorded_at: Option<Instant>,
+}
+
+impl Heartbeat {
+    pub fn new(worker_name: &'static str) -> Self {
+        return Self {
+            worker_name,
+            last_recorded_at: None,
+        };
+    }
+
+    /// Called on every iteration of a worker loop. A failure is only logged - the
+    /// readiness check will notice if heartbeats stop arriving.
+    pub async fn beat(&mut self, pool: &PgPool) {
+        if self
+            .last_recorded_at
+            .is_some_and(|t| t.elapsed() < HEARTBEAT_INTERVAL)
+        {
+            return;
+        }
+        match record_heartbeat(pool, self.worker_name).await {
+            Ok(()) => self.last_recorded_at = Some(Instant::now()),
+            Err(e) => tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to record a worker heartbeat"
+            ),
+        }
+    }
+}
+
+#[tracing::instrument(skip(pool))]
+pub async fn record_heartbeat(pool: &PgPool, worker_name: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO worker_heartbeats (worker_name, last_seen_at)
+        VALUES ($1, now())
+        ON CONFLICT (worker_name) DO UPDATE SET last_seen_at = EXCLUDED.last_seen_at
+        "#,
+        worker_name
+    )
+    .execute(pool)
+    .await?;
+    return Ok(());
+}
@@ -0,0 +1,146 @@
+//! tests/api/api_issues.rs
+
+use reqwest::Method;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+
+async fn publish_issue(app: &TestApp, token: &str, title: &str) -> String {
+    let response = app
+        .post_api_issues(
+            Some(token),
+            Some(&Uuid::new_v4().to_string()),
+            &serde_json::json!({
+                "title": title,
+                "text_content": "Newsletter body as plain text",
+                "html_content": "<p>Newsletter body as HTML</p>",
+            }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let body: serde_json::Value = response.json().await.unwrap();
+    return body["newsletter_issue_id"].as_str().unwrap().to_string();
+}
+
+#[tokio::test]
+async fn published_issues_can_be_listed_and_fetched() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["issues:read", "issues:write"]).await;
+    let first = publish_issue(&app, &token, "First issue").await;
+    let second = publish_issue(&app, &token, "Second issue").await;
+
+    let response = app
+        .api_request(Method::GET, "/issues?limit=1", &token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["data"][0]["newsletter_issue_id"], first);
+    let next_cursor = body["next_cursor"].as_str().unwrap();
+
+    let response = app
+        .api_request(
+            Method::GET,
+            &format!("/issues?limit=1&cursor={}", next_cursor),
+            &token,
+        )
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["data"][0]["newsletter_issue_id"], second);
+    assert!(body["next_cursor"].is_null());
+
+    let response = app
+        .api_request(Method::GET, &format!("/issues/{}", second), &token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["title"], "Second issue");
+    assert_eq!(body["html_content"], "<p>Newsletter body as HTML</p>");
+}
+
+#[tokio::test]
+async fn delivery_stats_track_the_queue() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["issues:read", "issues:write"]).await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let issue_id = publish_issue(&app, &token, "Newsletter title").await;
+    let stats_path = format!("/issues/{}/stats", issue_id);
+
+    let response = app
+        .api_request(Method::GET, &stats_path, &token)
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["pending"], 1);
+    assert_eq!(body["delivered"], 0);
+
+    app.dispatch_all_pending_emails().await;
+
+    let response = app
+        .api_request(Method::GET, &stats_path, &token)
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["pending"], 0);
+    assert_eq!(body["delivered"], 1);
+    assert_eq!(body["failed"], 0);
+}
+
+#[tokio::test]
+async fn unknown_issues_return_404() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["issues:read"]).await;
+
+    for path in [
+        format!("/issues/{}", Uuid::new_v4()),
+        format!("/issues/{}/stats", Uuid::new_v4()),
+    ] {
+        let response = app
+            .api_request(Method::GET, &path, &token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+    }
+}
+
+#[tokio::test]
+async fn publishing_requires_the_write_scope() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["issues:read"]).await;
+
+    let response = app
+        .post_api_issues(
+            Some(&token),
+            Some(&Uuid::new_v4().to_string()),
+            &serde_json::json!({
+                "title": "Newsletter title",
+                "text_content": "Newsletter body as plain text",
+                "html_content": "<p>Newsletter body as HTML</p>",
+            }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 403);
+}
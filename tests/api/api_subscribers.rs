@@ -0,0 +1,418 @@
+//! tests/api/api_subscribers.rs
+
+use reqwest::Method;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{spawn_app, TestApp};
+
+async fn insert_subscribers(app: &TestApp, n: usize) {
+    for i in 0..n {
+        sqlx::query!(
+            r#"
+            INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+            VALUES ($1, $2, $3, now() - make_interval(mins => $4), 'confirmed')
+            "#,
+            Uuid::new_v4(),
+            format!("subscriber{}@example.com", i),
+            format!("Subscriber {}", i),
+            (n - i) as i32,
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+}
+
+async fn read_write_token(app: &TestApp) -> String {
+    app.test_user.login(app).await;
+    return app
+        .create_api_token(&["subscribers:read", "subscribers:write"])
+        .await;
+}
+
+#[tokio::test]
+async fn subscribers_cannot_be_listed_without_the_read_scope() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["issues:write"]).await;
+
+    let response = app
+        .api_request(Method::GET, "/subscribers", &token)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 403);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        body["error"],
+        "The API token is missing the `subscribers:read` scope"
+    );
+}
+
+#[tokio::test]
+async fn creating_a_subscriber_sends_a_confirmation_email() {
+    let app = spawn_app().await;
+    let token = read_write_token(&app).await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .api_request(Method::POST, "/subscribers", &token)
+        .json(&serde_json::json!({"name": "Test User", "email": "test@email.com"}))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 201);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["email"], "test@email.com");
+    assert_eq!(body["name"], "Test User");
+    assert_eq!(body["status"], "pending_confirmation");
+}
+
+#[tokio::test]
+async fn creating_an_invalid_subscriber_returns_a_json_error() {
+    let app = spawn_app().await;
+    let token = read_write_token(&app).await;
+    let test_cases = vec![
+        (
+            serde_json::json!({"name": "Test User", "email": "not-an-email"}),
+            "invalid email",
+        ),
+        (
+            serde_json::json!({"name": "", "email": "test@email.com"}),
+            "empty name",
+        ),
+        (serde_json::json!({"name": "Test User"}), "missing email"),
+    ];
+
+    for (body, description) in test_cases {
+        let response = app
+            .api_request(Method::POST, "/subscribers", &token)
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not return a 400 Bad Request when the payload had an {}",
+            description
+        );
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert!(body["error"].is_string());
+    }
+}
+
+#[tokio::test]
+async fn a_subscriber_can_be_fetched_updated_and_deleted() {
+    let app = spawn_app().await;
+    let token = read_write_token(&app).await;
+    insert_subscribers(&app, 1).await;
+    let id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    let path = format!("/subscribers/{}", id);
+
+    let response = app
+        .api_request(Method::GET, &path, &token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "confirmed");
+
+    let response = app
+        .api_request(Method::PATCH, &path, &token)
+        .json(&serde_json::json!({"status": "pending_confirmation"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "pending_confirmation");
+
+    let response = app
+        .api_request(Method::DELETE, &path, &token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app
+        .api_request(Method::GET, &path, &token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn updating_to_an_unknown_status_is_rejected() {
+    let app = spawn_app().await;
+    let token = read_write_token(&app).await;
+    insert_subscribers(&app, 1).await;
+    let id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+
+    let response = app
+        .api_request(Method::PATCH, &format!("/subscribers/{}", id), &token)
+        .json(&serde_json::json!({"status": "vip"}))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn tags_and_attributes_can_be_set_via_the_api() {
+    let app = spawn_app().await;
+    let token = read_write_token(&app).await;
+    insert_subscribers(&app, 1).await;
+    let id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    let path = format!("/subscribers/{}", id);
+
+    let response = app
+        .api_request(Method::PATCH, &path, &token)
+        .json(&serde_json::json!({
+            "tags": ["Beta", "vip"],
+            "attributes": {"plan": "pro", "source": "conference"}
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "confirmed");
+    assert_eq!(body["tags"], serde_json::json!(["beta", "vip"]));
+    assert_eq!(
+        body["attributes"],
+        serde_json::json!({"plan": "pro", "source": "conference"})
+    );
+
+    let response = app
+        .api_request(Method::PATCH, &path, &token)
+        .json(&serde_json::json!({"attributes": {"source": null, "plan": "free"}}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["tags"], serde_json::json!(["beta", "vip"]));
+    assert_eq!(body["attributes"], serde_json::json!({"plan": "free"}));
+}
+
+#[tokio::test]
+async fn invalid_tags_and_attributes_are_rejected() {
+    let app = spawn_app().await;
+    let token = read_write_token(&app).await;
+    insert_subscribers(&app, 1).await;
+    let id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+
+    for update in [
+        serde_json::json!({"tags": ["early adopter"]}),
+        serde_json::json!({"attributes": {"signup source": "web"}}),
+    ] {
+        let response = app
+            .api_request(Method::PATCH, &format!("/subscribers/{}", id), &token)
+            .json(&update)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status().as_u16(), 400, "Accepted {}", update);
+    }
+}
+
+#[tokio::test]
+async fn unknown_subscribers_return_404() {
+    let app = spawn_app().await;
+    let token = read_write_token(&app).await;
+
+    let response = app
+        .api_request(
+            Method::GET,
+            &format!("/subscribers/{}", Uuid::new_v4()),
+            &token,
+        )
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["error"].is_string());
+}
+
+#[tokio::test]
+async fn subscribers_are_paginated_with_a_cursor() {
+    let app = spawn_app().await;
+    let token = read_write_token(&app).await;
+    insert_subscribers(&app, 3).await;
+
+    let response = app
+        .api_request(Method::GET, "/subscribers?limit=2", &token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let first_page = body["data"].as_array().unwrap();
+    assert_eq!(first_page.len(), 2);
+    assert_eq!(first_page[0]["email"], "subscriber0@example.com");
+    assert_eq!(first_page[1]["email"], "subscriber1@example.com");
+    let next_cursor = body["next_cursor"].as_str().unwrap();
+
+    let response = app
+        .api_request(
+            Method::GET,
+            &format!("/subscribers?limit=2&cursor={}", next_cursor),
+            &token,
+        )
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = response.json().await.unwrap();
+    let second_page = body["data"].as_array().unwrap();
+    assert_eq!(second_page.len(), 1);
+    assert_eq!(second_page[0]["email"], "subscriber2@example.com");
+    assert!(body["next_cursor"].is_null());
+}
+
+#[tokio::test]
+async fn invalid_pagination_parameters_are_rejected() {
+    let app = spawn_app().await;
+    let token = read_write_token(&app).await;
+
+    for query in ["cursor=garbage", "limit=0", "limit=1000", "status=vip"] {
+        let response = app
+            .api_request(Method::GET, &format!("/subscribers?{}", query), &token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not reject the query `{}`",
+            query
+        );
+    }
+}
+
+#[tokio::test]
+async fn subscribers_cannot_be_imported_without_the_import_scope() {
+    let app = spawn_app().await;
+    let token = read_write_token(&app).await;
+
+    let response = app
+        .api_request(Method::POST, "/subscribers/import", &token)
+        .json(
+            &serde_json::json!({"subscribers": [{"name": "Test User", "email": "test@email.com"}]}),
+        )
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn imported_subscribers_are_confirmed_without_an_email() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["subscribers:import"]).await;
+    insert_subscribers(&app, 1).await;
+    app.post_suppressions("blocked.com").await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .api_request(Method::POST, "/subscribers/import", &token)
+        .json(&serde_json::json!({"subscribers": [
+            {"name": "New User", "email": "new@example.com"},
+            {"name": "Subscriber 0", "email": "subscriber0@example.com"},
+            {"name": "Blocked User", "email": "someone@blocked.com"},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["imported"].as_array().unwrap().len(), 1);
+    assert_eq!(
+        body["skipped"],
+        serde_json::json!([
+            {"email": "subscriber0@example.com", "reason": "already_subscribed"},
+            {"email": "someone@blocked.com", "reason": "suppressed"},
+        ])
+    );
+    let saved = sqlx::query!("SELECT status FROM subscriptions WHERE email = 'new@example.com'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "confirmed");
+    let consent = sqlx::query!("SELECT source, ip_address, user_agent FROM consent_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(consent.source, "import");
+    // The importer's request says nothing about how the subscriber consented
+    assert_eq!(consent.ip_address, None);
+    assert_eq!(consent.user_agent, None);
+}
+
+#[tokio::test]
+async fn an_import_with_an_invalid_entry_imports_nothing() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["subscribers:import"]).await;
+
+    let response = app
+        .api_request(Method::POST, "/subscribers/import", &token)
+        .json(&serde_json::json!({"subscribers": [
+            {"name": "New User", "email": "new@example.com"},
+            {"name": "Broken User", "email": "not-an-email"},
+        ]}))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["error"].as_str().unwrap().starts_with("Subscriber 1:"));
+    let n_subscribers = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_subscribers, 0);
+}
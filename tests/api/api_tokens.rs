@@ -0,0 +1,166 @@
+//! tests/api/api_tokens.rs
+
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app};
+
+fn issue_request_body() -> serde_json::Value {
+    return serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+    });
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_api_tokens() {
+    let app = spawn_app().await;
+
+    let response = app.get_api_tokens().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.post_api_tokens("cms", &["issues:write"]).await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn api_tokens_are_stored_hashed_and_listed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let token = app.create_api_token(&["issues:write"]).await;
+
+    let saved = sqlx::query!("SELECT name, token_hash, scopes FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved API tokens");
+    assert_eq!(saved.name, "test token");
+    assert_ne!(saved.token_hash, token);
+    assert_eq!(saved.scopes, vec!["issues:write".to_string()]);
+
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("test token"));
+    assert!(!html_page.contains(&token));
+}
+
+#[tokio::test]
+async fn api_tokens_require_at_least_one_scope() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_api_tokens("cms", &[]).await;
+    assert_is_redirect_to(&response, "/admin/api-tokens");
+
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("<p><i>The API token must be granted at least one scope</i></p>"));
+}
+
+#[tokio::test]
+async fn publishing_via_the_api_delivers_to_confirmed_subscribers() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["issues:write"]).await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let idempotency_key = Uuid::new_v4().to_string();
+    let response = app
+        .post_api_issues(Some(&token), Some(&idempotency_key), &issue_request_body())
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["newsletter_issue_id"].is_string());
+
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn publishing_via_the_api_is_idempotent() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["issues:write"]).await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let idempotency_key = Uuid::new_v4().to_string();
+    let response1 = app
+        .post_api_issues(Some(&token), Some(&idempotency_key), &issue_request_body())
+        .await;
+    let response2 = app
+        .post_api_issues(Some(&token), Some(&idempotency_key), &issue_request_body())
+        .await;
+    assert_eq!(response1.status().as_u16(), 202);
+    assert_eq!(response2.status().as_u16(), 202);
+    assert_eq!(
+        response1.text().await.unwrap(),
+        response2.text().await.unwrap()
+    );
+
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop we only sent the newsletter email **once**
+}
+
+#[tokio::test]
+async fn publishing_via_the_api_requires_an_idempotency_key() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["issues:write"]).await;
+
+    let response = app
+        .post_api_issues(Some(&token), None, &issue_request_body())
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn requests_without_a_valid_token_are_rejected() {
+    let app = spawn_app().await;
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    for token in [None, Some("z2p_not-a-real-token")] {
+        let response = app
+            .post_api_issues(token, Some(&idempotency_key), &issue_request_body())
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(response.headers()["WWW-Authenticate"], "Bearer");
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert!(body["error"].is_string());
+    }
+}
+
+#[tokio::test]
+async fn revoked_tokens_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["issues:write"]).await;
+    let api_token_id = sqlx::query!("SELECT api_token_id FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .api_token_id;
+
+    let response = app.post_revoke_api_token(api_token_id).await;
+    assert_is_redirect_to(&response, "/admin/api-tokens");
+    let html_page = app.get_api_tokens_html().await;
+    assert!(html_page.contains("<p><i>The API token has been revoked</i></p>"));
+
+    let idempotency_key = Uuid::new_v4().to_string();
+    let response = app
+        .post_api_issues(Some(&token), Some(&idempotency_key), &issue_request_body())
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,118 @@
+//! tests/api/cli.rs
+
+use claims::assert_err;
+use secrecy::Secret;
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app};
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::cli::{add_user, migrate, queue_stats, reset_password};
+
+#[tokio::test]
+async fn migrate_is_a_no_op_on_an_up_to_date_database() {
+    let app = spawn_app().await;
+
+    assert_eq!(migrate(&app.db_pool).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn a_created_user_can_log_in() {
+    let app = spawn_app().await;
+    let password = "a-long-enough-password";
+
+    add_user(
+        "alice",
+        Secret::new(password.into()),
+        &app.db_pool,
+        &app.configuration.password_hashing,
+    )
+    .await
+    .unwrap();
+
+    let response = app
+        .post_login(&serde_json::json!({ "username": "alice", "password": password }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn usernames_must_be_unique() {
+    let app = spawn_app().await;
+
+    let outcome = add_user(
+        &app.test_user.username,
+        Secret::new("a-long-enough-password".into()),
+        &app.db_pool,
+        &app.configuration.password_hashing,
+    )
+    .await;
+
+    assert_err!(outcome);
+}
+
+#[tokio::test]
+async fn a_user_can_log_in_with_a_reset_password() {
+    let app = spawn_app().await;
+    let new_password = "a-brand-new-password";
+
+    reset_password(
+        &app.test_user.username,
+        Secret::new(new_password.into()),
+        &app.db_pool,
+        &app.configuration.password_hashing,
+    )
+    .await
+    .unwrap();
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": new_password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn resetting_the_password_of_an_unknown_user_fails() {
+    let app = spawn_app().await;
+
+    let outcome = reset_password(
+        "nobody",
+        Secret::new("a-brand-new-password".into()),
+        &app.db_pool,
+        &app.configuration.password_hashing,
+    )
+    .await;
+
+    assert_err!(outcome);
+}
+
+#[tokio::test]
+async fn queue_stats_count_pending_deliveries_per_issue() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    }))
+    .await;
+
+    let stats = queue_stats(&app.db_pool).await.unwrap();
+    assert_eq!(stats.issues.len(), 1);
+    assert_eq!(stats.issues[0].title, "Newsletter title");
+    assert_eq!(stats.issues[0].pending, 2);
+
+    app.dispatch_all_pending_emails().await;
+    let stats = queue_stats(&app.db_pool).await.unwrap();
+    assert!(stats.issues.is_empty());
+}
@@ -0,0 +1,280 @@
+//! tests/api/consent.rs
+
+use reqwest::Method;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{spawn_app, TestApp};
+
+struct ConsentRecord {
+    status: String,
+    source: String,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+}
+
+async fn consent_history(app: &TestApp) -> Vec<ConsentRecord> {
+    return sqlx::query_as!(
+        ConsentRecord,
+        r#"
+        SELECT status, source, ip_address, user_agent
+        FROM consent_events
+        ORDER BY occurred_at
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn subscribe_and_confirm(app: &TestApp) {
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.api_client
+        .post(format!("{}/subscriptions", app.address))
+        .header("User-Agent", "Mozilla/5.0 (subscribing)")
+        // Not sent by a trusted proxy, so it must not be recorded
+        .header("X-Forwarded-For", "203.0.113.9")
+        .form(&[
+            ("name", "le guin"),
+            ("email", "ursula_le_guin@gmail.com"),
+            ("form_token", &app.get_form_token().await),
+        ])
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::Client::new()
+        .get(confirmation_links.html)
+        .header("User-Agent", "Mozilla/5.0 (confirming)")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn subscribing_and_confirming_is_recorded_with_the_request_details() {
+    let app = spawn_app().await;
+
+    subscribe_and_confirm(&app).await;
+
+    let history = consent_history(&app).await;
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].status, "pending_confirmation");
+    assert_eq!(history[0].source, "form");
+    assert_eq!(history[0].ip_address.as_deref(), Some("127.0.0.1"));
+    assert_eq!(
+        history[0].user_agent.as_deref(),
+        Some("Mozilla/5.0 (subscribing)")
+    );
+    assert_eq!(history[1].status, "confirmed");
+    assert_eq!(history[1].source, "confirmation_link");
+    assert_eq!(
+        history[1].user_agent.as_deref(),
+        Some("Mozilla/5.0 (confirming)")
+    );
+}
+
+#[tokio::test]
+async fn status_changes_through_the_api_are_recorded_once() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app
+        .create_api_token(&["subscribers:read", "subscribers:write"])
+        .await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let body: serde_json::Value = app
+        .api_request(Method::POST, "/subscribers", &token)
+        .json(&serde_json::json!({"name": "Test User", "email": "test@email.com"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let path = format!("/subscribers/{}", body["id"].as_str().unwrap());
+
+    for _ in 0..2 {
+        app.api_request(Method::PATCH, &path, &token)
+            .json(&serde_json::json!({"status": "confirmed"}))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+
+    let history = consent_history(&app).await;
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].source, "api");
+    assert_eq!(history[1].status, "confirmed");
+    assert_eq!(history[1].source, "api");
+}
+
+#[tokio::test]
+async fn the_consent_history_is_shown_on_the_subscriber_detail_page() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    subscribe_and_confirm(&app).await;
+    let subscriber_id = sqlx::query_scalar!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    let html_page = app
+        .api_client
+        .get(format!("{}/admin/subscribers", app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(&format!(r#"href="/admin/subscribers/{}""#, subscriber_id)));
+
+    let html_page = app
+        .api_client
+        .get(format!(
+            "{}/admin/subscribers/{}",
+            app.address, subscriber_id
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<td>confirmation_link</td>"));
+    assert!(html_page.contains("<td>Mozilla/5.0 (subscribing)</td>"));
+}
+
+#[tokio::test]
+async fn the_consent_history_cannot_be_rewritten() {
+    let app = spawn_app().await;
+    subscribe_and_confirm(&app).await;
+
+    let result = sqlx::query!("UPDATE consent_events SET source = 'api'")
+        .execute(&app.db_pool)
+        .await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn the_consent_history_cannot_be_deleted() {
+    let app = spawn_app().await;
+    subscribe_and_confirm(&app).await;
+
+    let result = sqlx::query!("DELETE FROM consent_events")
+        .execute(&app.db_pool)
+        .await;
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn unsubscribing_is_recorded_and_keeps_the_consent_history() {
+    let app = spawn_app().await;
+    subscribe_and_confirm(&app).await;
+    let subscriber_id = sqlx::query_scalar!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let preferences_url = reqwest::Url::parse(&app.preference_links.url(subscriber_id)).unwrap();
+
+    app.api_client
+        .post(format!(
+            "{}/subscriptions/preferences/unsubscribe",
+            app.address
+        ))
+        .header("User-Agent", "Mozilla/5.0 (unsubscribing)")
+        .form(&preferences_url.query_pairs().collect::<Vec<_>>())
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    let history = consent_history(&app).await;
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[2].status, "unsubscribed");
+    assert_eq!(history[2].source, "preference_center");
+    assert_eq!(history[2].ip_address.as_deref(), Some("127.0.0.1"));
+    assert_eq!(
+        history[2].user_agent.as_deref(),
+        Some("Mozilla/5.0 (unsubscribing)")
+    );
+}
+
+#[tokio::test]
+async fn deleting_a_subscriber_through_the_api_is_recorded() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["subscribers:write"]).await;
+    subscribe_and_confirm(&app).await;
+    let subscriber_id = sqlx::query_scalar!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    app.api_request(
+        Method::DELETE,
+        &format!("/subscribers/{}", subscriber_id),
+        &token,
+    )
+    .send()
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap();
+
+    let history = consent_history(&app).await;
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[2].status, "unsubscribed");
+    assert_eq!(history[2].source, "api");
+}
+
+#[tokio::test]
+async fn erasure_removes_the_consent_history_of_an_unsubscribed_address() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["subscribers:write"]).await;
+    subscribe_and_confirm(&app).await;
+    let subscriber_id = sqlx::query_scalar!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    app.api_request(
+        Method::DELETE,
+        &format!("/subscribers/{}", subscriber_id),
+        &token,
+    )
+    .send()
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap();
+
+    let export: serde_json::Value = app
+        .get_data_export("ursula_le_guin@gmail.com")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(export["consent_history"].as_array().unwrap().len(), 3);
+    app.post_data_erasure("ursula_le_guin@gmail.com").await;
+
+    assert!(consent_history(&app).await.is_empty());
+}
@@ -0,0 +1,323 @@
+//! tests/api/data_requests.rs
+
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app, TestApp};
+
+struct Subscriber {
+    id: Uuid,
+    email: String,
+}
+
+async fn subscriber(app: &TestApp) -> Subscriber {
+    let record = sqlx::query!("SELECT id, email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    return Subscriber {
+        id: record.id,
+        email: record.email,
+    };
+}
+
+/// Publish an issue to the one subscriber, deliver it and record that they opened it.
+async fn deliver_issue(app: &TestApp, subscriber_id: Uuid) -> Uuid {
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+    let newsletter_issue_id =
+        sqlx::query_scalar!("SELECT newsletter_issue_id FROM newsletter_issues")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_engagements (newsletter_issue_id, subscriber_id, kind, occurred_at)
+        VALUES ($1, $2, 'open', now())
+        "#,
+        newsletter_issue_id,
+        subscriber_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    return newsletter_issue_id;
+}
+
+#[tokio::test]
+async fn admins_can_export_everything_stored_about_an_address() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber = subscriber(&app).await;
+    let newsletter_issue_id = deliver_issue(&app, subscriber.id).await;
+
+    let response = app.get_data_export(&subscriber.email).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Disposition"],
+        r#"attachment; filename="subscriber-data.json""#
+    );
+    let export: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(export["email"], subscriber.email);
+    assert_eq!(export["subscriber"]["id"], subscriber.id.to_string());
+    assert_eq!(export["subscriber"]["status"], "confirmed");
+    assert_eq!(export["subscription_tokens"].as_array().unwrap().len(), 1);
+    assert_eq!(
+        export["deliveries"][0]["newsletter_issue_id"],
+        newsletter_issue_id.to_string()
+    );
+    assert_eq!(export["deliveries"][0]["outcome"], "delivered");
+    assert_eq!(export["engagements"][0]["kind"], "open");
+}
+
+#[tokio::test]
+async fn exporting_an_unknown_address_returns_an_empty_export() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.get_data_export("nobody@example.com").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let export: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(export["subscriber"], serde_json::Value::Null);
+    assert_eq!(export["deliveries"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn data_requests_require_a_logged_in_admin() {
+    let app = spawn_app().await;
+
+    let response = app.get_data_export("nobody@example.com").await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app.post_data_erasure("nobody@example.com").await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn erasure_deletes_the_subscriber_but_keeps_issue_stats() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber = subscriber(&app).await;
+    let newsletter_issue_id = deliver_issue(&app, subscriber.id).await;
+    let stats_before = app.get_admin_issue_html(newsletter_issue_id).await;
+
+    let response = app.post_data_erasure(&subscriber.email).await;
+    assert_is_redirect_to(&response, "/admin/data-requests");
+    let html_page = app.get_data_requests_html().await;
+    assert!(html_page.contains(&format!(
+        "<p><i>Everything stored about {} has been erased</i></p>",
+        subscriber.email
+    )));
+
+    let n_subscribers = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_subscribers, 0);
+    let n_tokens = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscription_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_tokens, 0);
+    let export: serde_json::Value = app
+        .get_data_export(&subscriber.email)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(export["deliveries"], serde_json::json!([]));
+    assert_eq!(export["engagements"], serde_json::json!([]));
+    assert_eq!(
+        app.get_admin_issue_html(newsletter_issue_id).await,
+        stats_before
+    );
+    let engaged = sqlx::query_scalar!("SELECT subscriber_id FROM issue_engagements")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(engaged.len(), 1);
+    assert_ne!(engaged[0], subscriber.id);
+}
+
+#[tokio::test]
+async fn erasing_an_unknown_address_is_reported() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_data_erasure("nobody@example.com").await;
+    assert_is_redirect_to(&response, "/admin/data-requests");
+    let html_page = app.get_data_requests_html().await;
+    assert!(html_page.contains("<p><i>Nothing is stored about nobody@example.com</i></p>"));
+}
+
+/// Ask for `action` from the preference center and return the link emailed to confirm it.
+async fn request_own_data(app: &TestApp, subscriber_id: Uuid, action: &str) -> reqwest::Url {
+    let preferences_url = reqwest::Url::parse(&app.preference_links.url(subscriber_id)).unwrap();
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/subscriptions/preferences/{}",
+            app.address, action
+        ))
+        .form(&preferences_url.query_pairs().collect::<Vec<_>>())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 303);
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    return app.get_confirmation_links(&email_request).html;
+}
+
+async fn post_data_request_token(app: &TestApp, link: &reqwest::Url) -> reqwest::Response {
+    let token = link.query_pairs().find(|(k, _)| k == "token").unwrap().1;
+    return app
+        .api_client
+        .post(format!("{}/subscriptions/preferences/data", app.address))
+        .form(&[("token", token)])
+        .send()
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn subscribers_can_export_and_erase_their_own_data() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber = subscriber(&app).await;
+    let _mock_guard = Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    let export_link = request_own_data(&app, subscriber.id, "export").await;
+    let html_page = reqwest::get(export_link.clone())
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("Download your data"));
+    let export: serde_json::Value = post_data_request_token(&app, &export_link)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(export["email"], subscriber.email);
+    // The link only works once
+    let response = post_data_request_token(&app, &export_link).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let erasure_link = request_own_data(&app, subscriber.id, "erase").await;
+    // Following the link does not erase anything by itself
+    let response = reqwest::get(erasure_link.clone()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let n_subscribers = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_subscribers, 1);
+    let response = post_data_request_token(&app, &erasure_link).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let n_subscribers = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_subscribers, 0);
+}
+
+#[tokio::test]
+async fn data_request_links_expire_after_an_hour() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber = subscriber(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let erasure_link = request_own_data(&app, subscriber.id, "erase").await;
+    sqlx::query!("UPDATE data_request_tokens SET requested_at = now() - interval '61 minutes'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = reqwest::get(erasure_link.clone()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    let response = post_data_request_token(&app, &erasure_link).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let n_subscribers = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_subscribers, 1);
+}
+
+#[tokio::test]
+async fn no_data_request_link_is_sent_to_a_suppressed_address() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber = subscriber(&app).await;
+    app.post_suppressions(&subscriber.email).await;
+    let _mock_guard = Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let preferences_url = reqwest::Url::parse(&app.preference_links.url(subscriber.id)).unwrap();
+
+    let response = app
+        .api_client
+        .post(format!("{}/subscriptions/preferences/erase", app.address))
+        .form(&preferences_url.query_pairs().collect::<Vec<_>>())
+        .send()
+        .await
+        .unwrap();
+
+    // The same response as when the link is sent
+    assert_eq!(response.status().as_u16(), 303);
+    let html_page = app
+        .api_client
+        .get(format!(
+            "{}{}?{}",
+            app.address,
+            preferences_url.path(),
+            preferences_url.query().unwrap()
+        ))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(&format!("We sent a link to {}", subscriber.email)));
+    let n_tokens = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM data_request_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_tokens, 0);
+}
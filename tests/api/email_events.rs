@@ -0,0 +1,228 @@
+//! tests/api/email_events.rs
+
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+
+async fn subscriber_email_and_status(app: &TestApp) -> (String, String) {
+    let saved = sqlx::query!("SELECT email, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    return (saved.email, saved.status);
+}
+
+fn event(email: &str, event: &str) -> serde_json::Value {
+    return serde_json::json!({
+        "email": email,
+        "timestamp": 1700000000,
+        "event": event,
+        "sg_event_id": Uuid::new_v4().to_string(),
+        "sg_message_id": "message-id",
+    });
+}
+
+#[tokio::test]
+async fn events_without_a_valid_signature_are_rejected() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber_email_and_status(&app).await;
+    let body = serde_json::json!([event(&email, "spamreport")]);
+
+    let unsigned = reqwest::Client::new()
+        .post(format!("{}/email-events/sendgrid", &app.address))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unsigned.status().as_u16(), 401);
+
+    let forged = reqwest::Client::new()
+        .post(format!("{}/email-events/sendgrid", &app.address))
+        .header(
+            "X-Twilio-Email-Event-Webhook-Signature",
+            "MEUCIQCtIHJeH93Y+qpYeWrySphQgpNGNr/U+UyUlBkU6n7RAwIgJTz2C+8a8xonZGi6BpSzoQsbVRamr2nlxFDWYNH2j/0=",
+        )
+        .header("X-Twilio-Email-Event-Webhook-Timestamp", "1700000000")
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(forged.status().as_u16(), 401);
+
+    let (_, status) = subscriber_email_and_status(&app).await;
+    assert_eq!(status, "confirmed");
+}
+
+#[tokio::test]
+async fn signed_events_with_a_stale_timestamp_are_rejected() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber_email_and_status(&app).await;
+    let body = serde_json::json!([event(&email, "spamreport")]);
+
+    let replayed = app
+        .post_email_events_signed_at(&body, chrono::Utc::now().timestamp() - 60 * 60)
+        .await;
+
+    assert_eq!(replayed.status().as_u16(), 401);
+    let (_, status) = subscriber_email_and_status(&app).await;
+    assert_eq!(status, "confirmed");
+}
+
+#[tokio::test]
+async fn events_with_an_out_of_range_timestamp_are_skipped() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber_email_and_status(&app).await;
+    let mut broken = event(&email, "delivered");
+    broken["timestamp"] = i64::MAX.into();
+    broken["sg_event_id"] = "broken-event".into();
+
+    let response = app
+        .post_email_events(&serde_json::json!([broken, event(&email, "spamreport")]))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 204);
+    let (_, status) = subscriber_email_and_status(&app).await;
+    assert_eq!(status, "complained");
+    let event_types = sqlx::query_scalar!("SELECT event_type FROM email_events")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(event_types, vec!["spamreport".to_string()]);
+}
+
+#[tokio::test]
+async fn hard_bounces_move_subscribers_out_of_confirmed() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber_email_and_status(&app).await;
+
+    let mut bounce = event(&email, "bounce");
+    bounce["type"] = "bounce".into();
+    bounce["reason"] = "550 5.1.1 The email account does not exist".into();
+    let response = app.post_email_events(&serde_json::json!([bounce])).await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let (_, status) = subscriber_email_and_status(&app).await;
+    assert_eq!(status, "bounced");
+    let saved = sqlx::query!("SELECT event_type, reason, subscriber_id FROM email_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.event_type, "bounce");
+    assert_eq!(
+        saved.reason.as_deref(),
+        Some("550 5.1.1 The email account does not exist")
+    );
+    assert!(saved.subscriber_id.is_some());
+}
+
+#[tokio::test]
+async fn spam_reports_move_subscribers_out_of_confirmed() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber_email_and_status(&app).await;
+
+    let response = app
+        .post_email_events(&serde_json::json!([event(&email, "spamreport")]))
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let (_, status) = subscriber_email_and_status(&app).await;
+    assert_eq!(status, "complained");
+}
+
+#[tokio::test]
+async fn soft_bounces_and_deliveries_are_recorded_without_changing_the_status() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber_email_and_status(&app).await;
+
+    let mut blocked = event(&email, "bounce");
+    blocked["type"] = "blocked".into();
+    let events = serde_json::json!([event(&email, "delivered"), blocked]);
+    let response = app.post_email_events(&events).await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let (_, status) = subscriber_email_and_status(&app).await;
+    assert_eq!(status, "confirmed");
+    let n_events = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM email_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_events, 2);
+}
+
+#[tokio::test]
+async fn redelivered_events_are_only_recorded_once() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber_email_and_status(&app).await;
+    let events = serde_json::json!([event(&email, "delivered")]);
+
+    for _ in 0..2 {
+        let response = app.post_email_events(&events).await;
+        assert_eq!(response.status().as_u16(), 204);
+    }
+
+    let n_events = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM email_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_events, 1);
+}
+
+#[tokio::test]
+async fn newsletter_emails_carry_the_issue_id_for_event_attribution() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let (email, _) = subscriber_email_and_status(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let newsletter_issue_id = body["personalizations"][0]["custom_args"]["newsletter_issue_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let mut delivered = event(&email, "delivered");
+    delivered["newsletter_issue_id"] = newsletter_issue_id.clone().into();
+    app.post_email_events(&serde_json::json!([delivered])).await;
+
+    let saved = sqlx::query!("SELECT newsletter_issue_id FROM email_events")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        saved.newsletter_issue_id.map(|id| id.to_string()),
+        Some(newsletter_issue_id)
+    );
+}
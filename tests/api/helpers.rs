@@ -2,25 +2,48 @@
 
 use argon2::password_hash::SaltString;
 use argon2::{Argon2, Params, PasswordHasher, Version};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use fake::faker::internet::en::SafeEmail;
+use fake::faker::name::en::Name;
+use fake::Fake;
 use once_cell::sync::Lazy;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use p256::pkcs8::EncodePublicKey;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use uuid::Uuid;
-use wiremock::MockServer;
-use zero2prod::configuration::{get_configuration, DatabaseSettings};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use zero2prod::configuration::{get_configuration, DatabaseSettings, Settings, TelemetrySettings};
 use zero2prod::email_client::EmailClient;
 use zero2prod::issue_delivery_worker::{try_execute_task, ExecutionOutcome};
-use zero2prod::startup::{get_connection_pool, Application};
+use zero2prod::preference_links::PreferenceLinks;
+use zero2prod::startup::{get_connection_pool, Application, MetricsServer};
 use zero2prod::telemetry::{get_subscriber, init_subscriber};
+use zero2prod::tracking::Tracker;
+use zero2prod::webhooks::{try_execute_webhook_task, webhook_client};
 
 // Ensure that the `tracing` stack is only initialised once using `once_cell`
 static TRACING: Lazy<()> = Lazy::new(|| {
     let default_filter_level = "info".to_string();
     let subscriber_name = "test".to_string();
+    let telemetry = TelemetrySettings::default();
     if std::env::var("TEST_LOG").is_ok() {
-        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::stdout);
+        let subscriber = get_subscriber(
+            subscriber_name,
+            default_filter_level,
+            std::io::stdout,
+            &telemetry,
+        );
         init_subscriber(subscriber);
     } else {
-        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::sink);
+        let subscriber = get_subscriber(
+            subscriber_name,
+            default_filter_level,
+            std::io::sink,
+            &telemetry,
+        );
         init_subscriber(subscriber);
     }
 });
@@ -33,16 +56,52 @@ pub struct ConfirmationLinks {
 
 pub struct TestApp {
     pub address: String,
+    pub metrics_address: String,
     pub db_pool: PgPool,
     pub email_server: MockServer,
     pub port: u16,
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
     pub email_client: EmailClient,
+    pub event_webhook_key: SigningKey,
+    pub tracker: Tracker,
+    pub preference_links: PreferenceLinks,
+    pub configuration: Settings,
 }
 
 impl TestApp {
+    /// Submit the subscribe form, with a form token taken from the home page.
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
+        let form_token = format!(
+            "form_token={}",
+            urlencoding::encode(&self.get_form_token().await)
+        );
+        let body = if body.is_empty() {
+            form_token
+        } else {
+            format!("{}&{}", body, form_token)
+        };
+        return self.post_subscriptions_raw(body).await;
+    }
+
+    /// The signed timestamp embedded in the subscribe form on the home page.
+    pub async fn get_form_token(&self) -> String {
+        let html_page = self
+            .api_client
+            .get(&self.address)
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap();
+        let start = html_page.find(r#"name="form_token" value=""#).unwrap()
+            + r#"name="form_token" value=""#.len();
+        let end = start + html_page[start..].find('"').unwrap();
+        return html_page[start..end].to_string();
+    }
+
+    pub async fn post_subscriptions_raw(&self, body: String) -> reqwest::Response {
         return self
             .api_client
             .post(format!("{}/subscriptions", self.address))
@@ -130,6 +189,29 @@ impl TestApp {
             .unwrap();
     }
 
+    pub async fn get_setup(&self, token: &str) -> reqwest::Response {
+        return self
+            .api_client
+            .get(format!("{}/setup", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request");
+    }
+
+    pub async fn post_setup<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        return self
+            .api_client
+            .post(format!("{}/setup", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request");
+    }
+
     pub async fn get_admin_dashboard_html(&self) -> String {
         return self.get_admin_dashboard().await.text().await.unwrap();
     }
@@ -178,10 +260,317 @@ impl TestApp {
             .expect("Failed to execute request");
     }
 
-    pub async fn dispatch_all_pending_emails(&self) {
+    pub async fn get_api_tokens(&self) -> reqwest::Response {
+        return self
+            .api_client
+            .get(format!("{}/admin/api-tokens", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request");
+    }
+
+    pub async fn get_api_tokens_html(&self) -> String {
+        return self.get_api_tokens().await.text().await.unwrap();
+    }
+
+    /// Scopes are sent as repeated form fields, which `serde_urlencoded` cannot encode.
+    pub async fn post_api_tokens(&self, name: &str, scopes: &[&str]) -> reqwest::Response {
+        let mut body = format!("name={}", urlencoding::encode(name));
+        for scope in scopes {
+            body.push_str(&format!("&scopes={}", urlencoding::encode(scope)));
+        }
+        return self
+            .api_client
+            .post(format!("{}/admin/api-tokens", &self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request");
+    }
+
+    /// Create an API token through the admin UI and extract it from the page.
+    pub async fn create_api_token(&self, scopes: &[&str]) -> String {
+        let html_page = self
+            .post_api_tokens("test token", scopes)
+            .await
+            .text()
+            .await
+            .unwrap();
+        let start =
+            html_page.find(r#"<code id="api-token">"#).unwrap() + r#"<code id="api-token">"#.len();
+        let end = start + html_page[start..].find("</code>").unwrap();
+        return html_page[start..end].to_string();
+    }
+
+    pub async fn post_revoke_api_token(&self, api_token_id: Uuid) -> reqwest::Response {
+        return self
+            .api_client
+            .post(format!(
+                "{}/admin/api-tokens/{}/revoke",
+                &self.address, api_token_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request");
+    }
+
+    pub async fn post_api_issues<Body>(
+        &self,
+        token: Option<&str>,
+        idempotency_key: Option<&str>,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = reqwest::Client::new()
+            .post(format!("{}/api/v1/issues", &self.address))
+            .json(body);
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        if let Some(idempotency_key) = idempotency_key {
+            request = request.header("Idempotency-Key", idempotency_key);
+        }
+        return request.send().await.expect("Failed to execute request");
+    }
+
+    /// Build a request against the JSON API, authenticated with `token`.
+    pub fn api_request(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        token: &str,
+    ) -> reqwest::RequestBuilder {
+        return reqwest::Client::new()
+            .request(method, format!("{}/api/v1{}", &self.address, path))
+            .bearer_auth(token);
+    }
+
+    pub async fn get_webhooks(&self) -> reqwest::Response {
+        return self
+            .api_client
+            .get(format!("{}/admin/webhooks", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request");
+    }
+
+    pub async fn get_webhooks_html(&self) -> String {
+        return self.get_webhooks().await.text().await.unwrap();
+    }
+
+    pub async fn post_webhooks(&self, url: &str, event_types: &[&str]) -> reqwest::Response {
+        let mut body = format!("url={}", urlencoding::encode(url));
+        for event_type in event_types {
+            body.push_str(&format!("&event_types={}", urlencoding::encode(event_type)));
+        }
+        return self
+            .api_client
+            .post(format!("{}/admin/webhooks", &self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request");
+    }
+
+    /// Register a webhook endpoint through the admin UI and return its signing secret.
+    pub async fn create_webhook_endpoint(&self, url: &str, event_types: &[&str]) -> String {
+        let html_page = self
+            .post_webhooks(url, event_types)
+            .await
+            .text()
+            .await
+            .unwrap();
+        let start = html_page.find(r#"<code id="webhook-secret">"#).unwrap()
+            + r#"<code id="webhook-secret">"#.len();
+        let end = start + html_page[start..].find("</code>").unwrap();
+        return html_page[start..end].to_string();
+    }
+
+    pub async fn get_admin_issue_html(&self, newsletter_issue_id: Uuid) -> String {
+        return self
+            .api_client
+            .get(format!(
+                "{}/admin/issues/{}",
+                &self.address, newsletter_issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap();
+    }
+
+    pub async fn get_ready(&self) -> reqwest::Response {
+        return self
+            .api_client
+            .get(format!("{}/ready", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request");
+    }
+
+    pub async fn get_metrics(&self) -> String {
+        return self
+            .api_client
+            .get(format!("{}/metrics", &self.metrics_address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap();
+    }
+
+    pub async fn get_suppressions(&self) -> reqwest::Response {
+        return self
+            .api_client
+            .get(format!("{}/admin/suppressions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request");
+    }
+
+    pub async fn get_suppressions_html(&self) -> String {
+        return self.get_suppressions().await.text().await.unwrap();
+    }
+
+    pub async fn post_suppressions(&self, pattern: &str) -> reqwest::Response {
+        return self
+            .api_client
+            .post(format!("{}/admin/suppressions", &self.address))
+            .form(&[("pattern", pattern)])
+            .send()
+            .await
+            .expect("Failed to execute request");
+    }
+
+    pub async fn get_data_requests_html(&self) -> String {
+        return self
+            .api_client
+            .get(format!("{}/admin/data-requests", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap();
+    }
+
+    pub async fn get_data_export(&self, email: &str) -> reqwest::Response {
+        return self
+            .api_client
+            .get(format!("{}/admin/data-requests/export", &self.address))
+            .query(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to execute request");
+    }
+
+    pub async fn post_data_erasure(&self, email: &str) -> reqwest::Response {
+        return self
+            .api_client
+            .post(format!("{}/admin/data-requests/erase", &self.address))
+            .form(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to execute request");
+    }
+
+    pub async fn get_lists_html(&self) -> String {
+        return self
+            .api_client
+            .get(format!("{}/admin/lists", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap();
+    }
+
+    pub async fn post_lists(&self, name: &str) -> reqwest::Response {
+        return self
+            .api_client
+            .post(format!("{}/admin/lists", &self.address))
+            .form(&[("name", name)])
+            .send()
+            .await
+            .expect("Failed to execute request");
+    }
+
+    pub async fn post_lists_with_opt_in(&self, name: &str, opt_in: &str) -> reqwest::Response {
+        return self
+            .api_client
+            .post(format!("{}/admin/lists", &self.address))
+            .form(&[("name", name), ("opt_in", opt_in)])
+            .send()
+            .await
+            .expect("Failed to execute request");
+    }
+
+    pub async fn get_segments_html(&self) -> String {
+        return self
+            .api_client
+            .get(format!("{}/admin/segments", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap();
+    }
+
+    pub async fn post_segments(&self, name: &str, rules: &str) -> reqwest::Response {
+        return self
+            .api_client
+            .post(format!("{}/admin/segments", &self.address))
+            .form(&[("name", name), ("rules", rules)])
+            .send()
+            .await
+            .expect("Failed to execute request");
+    }
+
+    /// Post a SendGrid Event Webhook payload, signed like SendGrid would.
+    pub async fn post_email_events(&self, events: &serde_json::Value) -> reqwest::Response {
+        return self
+            .post_email_events_signed_at(events, chrono::Utc::now().timestamp())
+            .await;
+    }
+
+    pub async fn post_email_events_signed_at(
+        &self,
+        events: &serde_json::Value,
+        timestamp: i64,
+    ) -> reqwest::Response {
+        let body = events.to_string();
+        let timestamp = timestamp.to_string();
+        let signature: Signature = self
+            .event_webhook_key
+            .sign(format!("{}{}", timestamp, body).as_bytes());
+        return reqwest::Client::new()
+            .post(format!("{}/email-events/sendgrid", &self.address))
+            .header("Content-Type", "application/json")
+            .header(
+                "X-Twilio-Email-Event-Webhook-Signature",
+                STANDARD.encode(signature.to_der().as_bytes()),
+            )
+            .header("X-Twilio-Email-Event-Webhook-Timestamp", timestamp)
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request");
+    }
+
+    pub async fn dispatch_all_pending_webhooks(&self) {
+        let http_client = webhook_client();
         loop {
             if let ExecutionOutcome::EmptyQueue =
-                try_execute_task(&self.db_pool, &self.email_client)
+                try_execute_webhook_task(&self.db_pool, &http_client)
                     .await
                     .unwrap()
             {
@@ -189,6 +578,22 @@ impl TestApp {
             }
         }
     }
+
+    pub async fn dispatch_all_pending_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.tracker,
+                &self.preference_links,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
 }
 
 pub struct TestUser {
@@ -214,16 +619,16 @@ impl TestUser {
         .await;
     }
 
-    pub async fn store(&self, pool: &PgPool) {
+    pub fn password_hash(&self, params: Params) -> String {
         let salt = SaltString::generate(&mut rand::thread_rng());
-        let password_hash = Argon2::new(
-            argon2::Algorithm::Argon2id,
-            Version::V0x13,
-            Params::new(15000, 2, 1, None).unwrap(),
-        )
-        .hash_password(self.password.as_bytes(), &salt)
-        .unwrap()
-        .to_string();
+        return Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password(self.password.as_bytes(), &salt)
+            .unwrap()
+            .to_string();
+    }
+
+    pub async fn store(&self, pool: &PgPool) {
+        let password_hash = self.password_hash(Params::new(15000, 2, 1, None).unwrap());
         sqlx::query!(
             "INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, $3)",
             self.user_id,
@@ -239,9 +644,17 @@ impl TestUser {
 /// Spin up an instance of our application
 /// and returns its address (i.e. http://localhost:xxxx)
 pub async fn spawn_app() -> TestApp {
+    let app = spawn_app_without_users(|_| {}).await;
+    app.test_user.store(&app.db_pool).await;
+    return app;
+}
+
+/// Like `spawn_app`, but the test user is not stored and `customise` can adjust the configuration.
+pub async fn spawn_app_without_users(customise: impl FnOnce(&mut Settings)) -> TestApp {
     Lazy::force(&TRACING);
 
     let email_server = MockServer::start().await;
+    let event_webhook_key = SigningKey::random(&mut rand::thread_rng());
 
     // randomise configuration to ensure test isolation
     let configuration = {
@@ -250,8 +663,25 @@ pub async fn spawn_app() -> TestApp {
         c.database.database_name = Uuid::new_v4().to_string();
         // Use a random OS port
         c.application.port = 0;
+        c.application.metrics_port = 0;
         // Use mock server as email API
         c.email_client.base_url = email_server.uri();
+        // Sign provider events with a key only the test knows
+        c.email_client.event_webhook_public_key = STANDARD.encode(
+            event_webhook_key
+                .verifying_key()
+                .to_public_key_der()
+                .unwrap()
+                .as_bytes(),
+        );
+        // Submit forms as fast as the tests can
+        c.subscriptions.spam_protection.min_form_fill_seconds = 0;
+        // All tests share one Redis instance and subscribe from the same IP address
+        c.subscriptions.spam_protection.max_attempts_per_ip_per_hour = u64::MAX;
+        c.subscriptions
+            .spam_protection
+            .max_attempts_per_email_per_hour = u64::MAX;
+        customise(&mut c);
         c
     };
 
@@ -263,6 +693,10 @@ pub async fn spawn_app() -> TestApp {
     let application_port = application.port();
     let address = format!("http://127.0.0.1:{}", application.port());
     let _ = tokio::spawn(application.run_until_stopped());
+    let metrics_server =
+        MetricsServer::build(&configuration).expect("Failed to build the metrics server");
+    let metrics_address = format!("http://127.0.0.1:{}", metrics_server.port());
+    let _ = tokio::spawn(metrics_server.run_until_stopped());
     let client = reqwest::Client::builder()
         .redirect(reqwest::redirect::Policy::none())
         .cookie_store(true)
@@ -271,14 +705,25 @@ pub async fn spawn_app() -> TestApp {
 
     let test_app = TestApp {
         address,
+        metrics_address,
         db_pool: get_connection_pool(&configuration.database),
         email_server,
         port: application_port,
         test_user: TestUser::generate(),
         api_client: client,
-        email_client: configuration.email_client.client(),
+        email_client: configuration.email_client.clone().client(),
+        event_webhook_key,
+        tracker: Tracker::new(
+            configuration.application.base_url.clone(),
+            configuration.application.hmac_secret.clone(),
+        ),
+        preference_links: PreferenceLinks::new(
+            configuration.application.base_url.clone(),
+            configuration.application.hmac_secret.clone(),
+            configuration.subscriptions.preference_link_validity_days,
+        ),
+        configuration,
     };
-    test_app.test_user.store(&test_app.db_pool).await;
     return test_app;
 }
 
@@ -300,6 +745,44 @@ async fn configure_database(config: &DatabaseSettings) -> PgPool {
     return connection_pool;
 }
 
+pub async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
+    let name: String = Name().fake();
+    let email: String = SafeEmail().fake();
+    let body = serde_urlencoded::to_string(&serde_json::json!({
+        "name": name,
+        "email": email
+    }))
+    .unwrap();
+    let _mock_guard = Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .named("Create unconfirmed subscriber")
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions(body.into())
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    return app.get_confirmation_links(email_request);
+}
+
+pub async fn create_confirmed_subscriber(app: &TestApp) {
+    let confirmation_link = create_unconfirmed_subscriber(app).await;
+    reqwest::get(confirmation_link.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
 pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert_eq!(response.status().as_u16(), 303);
     assert_eq!(response.headers().get("Location").unwrap(), location);
@@ -0,0 +1,239 @@
+//! tests/api/idempotency.rs
+
+use std::time::Duration;
+
+use claims::assert_ok;
+use reqwest::Method;
+use zero2prod::idempotency::{
+    delete_expired_records, try_processing, IdempotencyError, IdempotencyKey,
+};
+
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn expire_idempotency_records(app: &TestApp, idempotency_keys: &[String]) {
+    let age = app.configuration.idempotency.retention() + Duration::from_secs(60 * 60);
+    sqlx::query!(
+        "UPDATE idempotency SET created_at = now() - make_interval(secs => $1) WHERE idempotency_key = ANY($2)",
+        age.as_secs_f64(),
+        idempotency_keys
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn count_idempotency_records(app: &TestApp) -> i64 {
+    return sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM idempotency"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+fn newsletter_request_body(idempotency_key: &str) -> serde_json::Value {
+    return serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": idempotency_key,
+    });
+}
+
+#[tokio::test]
+async fn an_expired_idempotency_key_is_treated_as_new() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+    let body = newsletter_request_body(&idempotency_key);
+
+    let response = app.post_publish_newsletter(&body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    expire_idempotency_records(&app, &[idempotency_key]).await;
+    let response = app.post_publish_newsletter(&body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let n_issues = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_issues, 2);
+}
+
+#[tokio::test]
+async fn only_expired_idempotency_records_are_deleted() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let idempotency_keys: Vec<String> = (0..5).map(|_| uuid::Uuid::new_v4().to_string()).collect();
+    for idempotency_key in &idempotency_keys {
+        app.post_publish_newsletter(&newsletter_request_body(idempotency_key))
+            .await;
+    }
+    expire_idempotency_records(&app, &idempotency_keys[..3]).await;
+    let retention = app.configuration.idempotency.retention();
+
+    // Deleted in batches of two: 2 + 1, then nothing is left to delete
+    assert_eq!(
+        delete_expired_records(&app.db_pool, retention, 2)
+            .await
+            .unwrap(),
+        2
+    );
+    assert_eq!(
+        delete_expired_records(&app.db_pool, retention, 2)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        delete_expired_records(&app.db_pool, retention, 2)
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(count_idempotency_records(&app).await, 2);
+}
+
+#[tokio::test]
+async fn reusing_an_idempotency_key_for_a_different_newsletter_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+
+    let response = app
+        .post_publish_newsletter(&newsletter_request_body(&idempotency_key))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    let mut other_newsletter = newsletter_request_body(&idempotency_key);
+    other_newsletter["title"] = "A different title".into();
+    let response = app.post_publish_newsletter(&other_newsletter).await;
+
+    assert_eq!(response.status().as_u16(), 422);
+}
+
+#[tokio::test]
+async fn reusing_an_idempotency_key_for_a_different_api_issue_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["issues:write"]).await;
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+    let issue = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+    });
+
+    let response = app
+        .post_api_issues(Some(&token), Some(&idempotency_key), &issue)
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let mut other_issue = issue.clone();
+    other_issue["text_content"] = "Something else entirely".into();
+    let response = app
+        .post_api_issues(Some(&token), Some(&idempotency_key), &other_issue)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 422);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        body["error"],
+        "The idempotency key has already been used for a different request"
+    );
+}
+
+#[tokio::test]
+async fn a_key_is_in_progress_until_the_first_request_finishes() {
+    let app = spawn_app().await;
+    let retention = app.configuration.idempotency.retention();
+    let idempotency_key: IdempotencyKey = uuid::Uuid::new_v4().to_string().try_into().unwrap();
+
+    let first = try_processing(
+        &app.db_pool,
+        &idempotency_key,
+        Some(app.test_user.user_id),
+        "request-hash",
+        retention,
+    )
+    .await;
+    assert_ok!(&first);
+
+    let second = try_processing(
+        &app.db_pool,
+        &idempotency_key,
+        Some(app.test_user.user_id),
+        "request-hash",
+        retention,
+    )
+    .await;
+    assert!(matches!(second, Err(IdempotencyError::RequestInProgress)));
+}
+
+#[tokio::test]
+async fn a_retried_subscription_is_only_processed_once() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["subscribers:write"]).await;
+    let idempotency_key = uuid::Uuid::new_v4().to_string();
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    for _ in 0..2 {
+        let response = app
+            .api_request(Method::POST, "/subscribers", &token)
+            .header("Idempotency-Key", &idempotency_key)
+            .json(&serde_json::json!({"name": "Test User", "email": "test@email.com"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 201);
+    }
+
+    assert_eq!(count_idempotency_records(&app).await, 1);
+    // wiremock asserts on drop that a single confirmation email was sent
+}
+
+#[tokio::test]
+async fn an_invalid_idempotency_key_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["subscribers:write"]).await;
+
+    let response = app
+        .api_request(Method::POST, "/subscribers", &token)
+        .header("Idempotency-Key", "a".repeat(60))
+        .json(&serde_json::json!({"name": "Test User", "email": "test@email.com"}))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(count_idempotency_records(&app).await, 0);
+}
+
+#[tokio::test]
+async fn the_public_subscribe_form_is_not_idempotent() {
+    let app = spawn_app().await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions(format!(
+            "name=Test%20User&email=test%40email.com&idempotency_key={}",
+            uuid::Uuid::new_v4()
+        ))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(count_idempotency_records(&app).await, 0);
+}
@@ -0,0 +1,197 @@
+//! tests/api/lists.rs
+
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app, TestApp};
+
+async fn create_list(app: &TestApp, name: &str) -> Uuid {
+    let response = app.post_lists(name).await;
+    assert_is_redirect_to(&response, "/admin/lists");
+    return sqlx::query_scalar!("SELECT list_id FROM lists WHERE name = $1", name)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+/// Subscribe `email` to `list` and confirm the subscription.
+async fn create_confirmed_list_member(app: &TestApp, email: &str, list: &str) {
+    let _mock_guard = Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let body = serde_urlencoded::to_string([("name", "Le Guin"), ("email", email), ("list", list)])
+        .unwrap();
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = &app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+async fn queued_emails(app: &TestApp) -> Vec<String> {
+    return sqlx::query_scalar!("SELECT subscriber_email FROM issue_delivery_queue")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+fn newsletter_form(lists: &[Uuid]) -> Vec<(&'static str, String)> {
+    let mut form = vec![
+        ("title", "Newsletter title".to_string()),
+        ("text_content", "Newsletter body as plain text".to_string()),
+        ("html_content", "<p>Newsletter body as HTML</p>".to_string()),
+        ("idempotency_key", Uuid::new_v4().to_string()),
+    ];
+    form.extend(lists.iter().map(|list_id| ("lists", list_id.to_string())));
+    return form;
+}
+
+#[tokio::test]
+async fn lists_can_be_created_from_the_admin_page() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    create_list(&app, "Weekly digest").await;
+
+    let html_page = app.get_lists_html().await;
+    assert!(html_page.contains("<p><i>The list Weekly digest has been created</i></p>"));
+    assert!(html_page.contains("<td>Weekly digest</td>"));
+}
+
+#[tokio::test]
+async fn list_names_must_be_unique() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_list(&app, "Weekly digest").await;
+
+    let response = app.post_lists("Weekly digest").await;
+    assert_is_redirect_to(&response, "/admin/lists");
+
+    let html_page = app.get_lists_html().await;
+    assert!(html_page.contains("<p><i>A list named Weekly digest already exists</i></p>"));
+}
+
+#[tokio::test]
+async fn subscribing_to_an_unknown_list_is_rejected() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com&list=nope".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let n_subscribers = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_subscribers, 0);
+}
+
+#[tokio::test]
+async fn an_issue_sent_to_a_list_only_reaches_its_members() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let digest = create_list(&app, "Weekly digest").await;
+    let announcements = create_list(&app, "Announcements").await;
+    create_confirmed_list_member(&app, "digest@example.com", "Weekly digest").await;
+    create_confirmed_list_member(&app, "announcements@example.com", "Announcements").await;
+    create_confirmed_subscriber(&app).await;
+
+    let response = app
+        .post_publish_newsletter(&newsletter_form(&[digest]))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    assert_eq!(queued_emails(&app).await, vec!["digest@example.com"]);
+
+    sqlx::query!("DELETE FROM issue_delivery_queue")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = app
+        .post_publish_newsletter(&newsletter_form(&[digest, announcements]))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let mut emails = queued_emails(&app).await;
+    emails.sort();
+    assert_eq!(
+        emails,
+        vec!["announcements@example.com", "digest@example.com"]
+    );
+}
+
+#[tokio::test]
+async fn an_issue_sent_to_no_list_reaches_every_confirmed_subscriber() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_list(&app, "Weekly digest").await;
+    create_confirmed_list_member(&app, "digest@example.com", "Weekly digest").await;
+    create_confirmed_subscriber(&app).await;
+
+    let response = app.post_publish_newsletter(&newsletter_form(&[])).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    assert_eq!(queued_emails(&app).await.len(), 2);
+}
+
+#[tokio::test]
+async fn publishing_to_an_unknown_list_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+
+    let response = app
+        .post_publish_newsletter(&newsletter_form(&[Uuid::new_v4()]))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(queued_emails(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn a_list_can_override_the_default_opt_in() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let response = app.post_lists_with_opt_in("Weekly digest", "single").await;
+    assert_is_redirect_to(&response, "/admin/lists");
+    let html_page = app.get_lists_html().await;
+    assert!(html_page.contains("<td>single</td>"));
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let body = serde_urlencoded::to_string([
+        ("name", "Le Guin"),
+        ("email", "ursula_le_guin@gmail.com"),
+        ("list", "Weekly digest"),
+    ])
+    .unwrap();
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let status = sqlx::query_scalar!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "confirmed");
+}
@@ -1,4 +1,6 @@
 //! tests/api/login.rs
+use argon2::Params;
+
 use crate::helpers::{assert_is_redirect_to, spawn_app};
 
 #[tokio::test]
@@ -38,3 +40,64 @@ async fn redirect_to_admin_dashboard_after_login_success() {
     let html_page = app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
 }
+
+#[tokio::test]
+async fn login_upgrades_password_hash_computed_with_weaker_parameters() {
+    let app = spawn_app().await;
+    let weak_password_hash = app
+        .test_user
+        .password_hash(Params::new(4096, 1, 1, None).unwrap());
+    sqlx::query!(
+        "UPDATE users SET password_hash = $1 WHERE user_id = $2",
+        weak_password_hash,
+        app.test_user.user_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    });
+    let response = app.post_login(&login_body).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let saved = sqlx::query!(
+        "SELECT password_hash FROM users WHERE user_id = $1",
+        app.test_user.user_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert!(saved.password_hash.contains("m=15000,t=2,p=1"));
+
+    // The upgraded hash must still verify the same password
+    let response = app.post_logout().await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app.post_login(&login_body).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn login_keeps_password_hash_computed_with_current_parameters() {
+    let app = spawn_app().await;
+    let before = sqlx::query!(
+        "SELECT password_hash FROM users WHERE user_id = $1",
+        app.test_user.user_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+
+    app.test_user.login(&app).await;
+
+    let after = sqlx::query!(
+        "SELECT password_hash FROM users WHERE user_id = $1",
+        app.test_user.user_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(before.password_hash, after.password_hash);
+}
@@ -1,10 +1,30 @@
 //! tests/api/main.rs
 
 mod admin_dashboard;
+mod api_issues;
+mod api_subscribers;
+mod api_tokens;
 mod change_password;
+mod cli;
+mod consent;
+mod data_requests;
+mod email_events;
 mod health_check;
 mod helpers;
+mod idempotency;
+mod lists;
 mod login;
+mod metrics;
 mod newsletter;
+mod openapi;
+mod ready;
+mod segments;
+mod setup;
+mod shutdown;
+mod spam_protection;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_preferences;
+mod suppressions;
+mod tracking;
+mod webhooks;
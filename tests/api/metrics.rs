@@ -0,0 +1,79 @@
+//! tests/api/metrics.rs
+
+use crate::helpers::{create_confirmed_subscriber, spawn_app};
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn requests_are_counted_by_route_pattern() {
+    let app = spawn_app().await;
+    app.get_admin_issue_html(uuid::Uuid::new_v4()).await;
+
+    let metrics = app.get_metrics().await;
+
+    assert!(metrics.contains(
+        r#"http_requests_total{method="GET",route="/admin/issues/{newsletter_issue_id}",status="303"}"#
+    ));
+    assert!(metrics.contains(
+        r#"http_request_duration_seconds_count{method="GET",route="/admin/issues/{newsletter_issue_id}"}"#
+    ));
+}
+
+#[tokio::test]
+async fn metrics_are_not_served_on_the_public_port() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/metrics", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn non_standard_methods_share_one_label() {
+    let app = spawn_app().await;
+    let method = reqwest::Method::from_bytes(b"PURGE").unwrap();
+    app.api_client
+        .request(method, format!("{}/", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    let metrics = app.get_metrics().await;
+
+    assert!(!metrics.contains(r#"method="PURGE""#));
+    assert!(metrics.contains(r#"http_requests_total{method="other""#));
+}
+
+#[tokio::test]
+async fn the_delivery_queue_depth_is_exported() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    app.post_publish_newsletter(&newsletter_request_body).await;
+    assert!(app
+        .get_metrics()
+        .await
+        .contains("\nissue_delivery_queue_depth 1\n"));
+
+    app.dispatch_all_pending_emails().await;
+    let metrics = app.get_metrics().await;
+    assert!(metrics.contains("\nissue_delivery_queue_depth 0\n"));
+    assert!(metrics.contains("\nemails_sent_total "));
+    assert!(metrics.contains(r#"issue_deliveries_total{outcome="delivered"}"#));
+}
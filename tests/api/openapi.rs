@@ -0,0 +1,66 @@
+//! tests/api/openapi.rs
+
+use utoipa::OpenApi;
+use zero2prod::routes::ApiDoc;
+use zero2prod::startup::registered_routes;
+
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn the_openapi_document_is_served() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/api/openapi.json", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    let document: serde_json::Value = response.json().await.unwrap();
+    assert!(document["openapi"].as_str().unwrap().starts_with("3."));
+    assert!(document["components"]["securitySchemes"]["api_token"].is_object());
+}
+
+fn documented_routes() -> Vec<(String, String)> {
+    let document = serde_json::to_value(ApiDoc::openapi()).unwrap();
+    let mut routes = Vec::new();
+    for (path, item) in document["paths"].as_object().unwrap() {
+        for method in ["get", "put", "post", "delete", "patch"] {
+            if item.get(method).is_some() {
+                routes.push((method.to_string(), path.clone()));
+            }
+        }
+    }
+    return routes;
+}
+
+#[test]
+fn every_registered_route_is_documented() {
+    let documented = documented_routes();
+    let registered: Vec<_> = registered_routes()
+        .into_iter()
+        .map(|(method, path)| (method.as_str().to_lowercase(), path))
+        .collect();
+    assert!(registered.contains(&("post".into(), "/api/v1/issues".into())));
+
+    let undocumented: Vec<_> = registered
+        .iter()
+        .filter(|route| !documented.contains(route))
+        .collect();
+    assert!(
+        undocumented.is_empty(),
+        "Routes missing from the OpenAPI document: {:?}",
+        undocumented
+    );
+    let unregistered: Vec<_> = documented
+        .iter()
+        .filter(|route| !registered.contains(route))
+        .collect();
+    assert!(
+        unregistered.is_empty(),
+        "Documented routes the application does not serve: {:?}",
+        unregistered
+    );
+}
@@ -0,0 +1,75 @@
+//! tests/api/ready.rs
+
+use crate::helpers::spawn_app;
+use zero2prod::worker_heartbeat::{record_heartbeat, ISSUE_DELIVERY_WORKER, WORKERS};
+
+#[tokio::test]
+async fn ready_returns_503_until_the_workers_have_reported_in() {
+    let app = spawn_app().await;
+
+    let response = app.get_ready().await;
+    assert_eq!(response.status().as_u16(), 503);
+
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["status"], "unavailable");
+    assert_eq!(report["checks"]["postgres"]["status"], "up");
+    assert_eq!(report["checks"]["redis"]["status"], "up");
+    assert_eq!(report["checks"]["migrations"]["status"], "up");
+    assert_eq!(report["checks"][ISSUE_DELIVERY_WORKER]["status"], "down");
+    assert_eq!(
+        report["checks"][ISSUE_DELIVERY_WORKER]["error"],
+        "No heartbeat has been recorded"
+    );
+}
+
+#[tokio::test]
+async fn ready_returns_200_when_every_dependency_is_healthy() {
+    let app = spawn_app().await;
+    for worker in WORKERS {
+        record_heartbeat(&app.db_pool, worker).await.unwrap();
+    }
+
+    let response = app.get_ready().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["status"], "ready");
+    for worker in WORKERS {
+        assert_eq!(report["checks"][worker]["status"], "up");
+    }
+}
+
+#[tokio::test]
+async fn a_stale_worker_heartbeat_makes_the_app_unready() {
+    let app = spawn_app().await;
+    for worker in WORKERS {
+        record_heartbeat(&app.db_pool, worker).await.unwrap();
+    }
+    sqlx::query!(
+        "UPDATE worker_heartbeats SET last_seen_at = now() - interval '5 minutes' WHERE worker_name = $1",
+        ISSUE_DELIVERY_WORKER
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = app.get_ready().await;
+    assert_eq!(response.status().as_u16(), 503);
+
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["checks"][ISSUE_DELIVERY_WORKER]["status"], "down");
+}
+
+#[tokio::test]
+async fn health_check_stays_live_when_the_app_is_not_ready() {
+    let app = spawn_app().await;
+    assert_eq!(app.get_ready().await.status().as_u16(), 503);
+
+    let response = app
+        .api_client
+        .get(format!("{}/health_check", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -0,0 +1,208 @@
+//! tests/api/segments.rs
+
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+/// Insert a confirmed subscriber directly, so it can be given a subscription date.
+async fn insert_subscriber(app: &TestApp, email: &str, subscribed_at: &str, tags: &[&str]) {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, 'Subscriber', $3::text::timestamptz, 'confirmed')
+        "#,
+        subscriber_id,
+        email,
+        subscribed_at
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    for tag in tags {
+        sqlx::query!(
+            "INSERT INTO subscriber_tags (subscriber_id, tag) VALUES ($1, $2)",
+            subscriber_id,
+            tag
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+}
+
+async fn create_segment(app: &TestApp, name: &str, rules: &str) -> Uuid {
+    let response = app.post_segments(name, rules).await;
+    assert_is_redirect_to(&response, "/admin/segments");
+    return sqlx::query_scalar!("SELECT segment_id FROM segments WHERE name = $1", name)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+fn newsletter_form(segment: &str) -> serde_json::Value {
+    return serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+        "segment": segment,
+    });
+}
+
+async fn queued_emails(app: &TestApp) -> Vec<String> {
+    return sqlx::query_scalar!(
+        "SELECT subscriber_email FROM issue_delivery_queue ORDER BY subscriber_email"
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn segments_show_their_rules_and_recipient_count() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscriber(
+        &app,
+        "old-beta@example.com",
+        "2023-06-01T00:00:00Z",
+        &["beta"],
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "new-beta@example.com",
+        "2024-06-01T00:00:00Z",
+        &["beta"],
+    )
+    .await;
+    insert_subscriber(&app, "new@example.com", "2024-06-01T00:00:00Z", &[]).await;
+
+    create_segment(
+        &app,
+        "Recent beta testers",
+        "tag = \"beta\" AND subscribed_at after 2024-01-01",
+    )
+    .await;
+
+    let html_page = app.get_segments_html().await;
+    assert!(html_page.contains("<p><i>The segment Recent beta testers has been created</i></p>"));
+    assert!(
+        html_page.contains("<td>tag = beta<br>AND subscribed_at after 2024-01-01T00:00:00Z</td>")
+    );
+    assert!(html_page.contains("<td>1</td>"));
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("Recent beta testers (1 recipients)"));
+}
+
+#[tokio::test]
+async fn recipient_counts_leave_out_paused_and_suppressed_subscribers() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    for email in [
+        "active@example.com",
+        "paused@example.com",
+        "someone@blocked.com",
+    ] {
+        insert_subscriber(&app, email, "2024-06-01T00:00:00Z", &["beta"]).await;
+    }
+    sqlx::query!(
+        "UPDATE subscriptions SET paused_until = now() + interval '1 day' WHERE email = 'paused@example.com'"
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.post_suppressions("blocked.com").await;
+
+    create_segment(&app, "Beta testers", "tag = \"beta\"").await;
+
+    let html_page = app.get_publish_newsletter_html().await;
+    assert!(html_page.contains("Beta testers (1 recipients)"));
+}
+
+#[tokio::test]
+async fn invalid_segment_rules_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_segments("Broken", "tag beta").await;
+    assert_is_redirect_to(&response, "/admin/segments");
+
+    let html_page = app.get_segments_html().await;
+    assert!(html_page.contains("<p><i>Expected `tag = &lt;tag&gt;` in: tag beta</i></p>"));
+    let n_segments = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM segments"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_segments, 0);
+}
+
+#[tokio::test]
+async fn an_issue_sent_to_a_segment_only_reaches_matching_subscribers() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscriber(
+        &app,
+        "old-beta@example.com",
+        "2023-06-01T00:00:00Z",
+        &["beta"],
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "new-beta@example.com",
+        "2024-06-01T00:00:00Z",
+        &["beta"],
+    )
+    .await;
+    insert_subscriber(&app, "new@example.com", "2024-06-01T00:00:00Z", &["vip"]).await;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_attributes (subscriber_id, key, value)
+        SELECT id, 'plan', 'pro' FROM subscriptions WHERE email LIKE '%beta@example.com'
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let segment_id = create_segment(
+        &app,
+        "Recent pro beta testers",
+        "tag = beta\nattribute plan = pro\nsubscribed_at after 2024-01-01",
+    )
+    .await;
+
+    let response = app
+        .post_publish_newsletter(&newsletter_form(&segment_id.to_string()))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    assert_eq!(queued_emails(&app).await, vec!["new-beta@example.com"]);
+}
+
+#[tokio::test]
+async fn an_issue_sent_to_everyone_ignores_segments() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    insert_subscriber(&app, "beta@example.com", "2024-06-01T00:00:00Z", &["beta"]).await;
+    insert_subscriber(&app, "other@example.com", "2024-06-01T00:00:00Z", &[]).await;
+    create_segment(&app, "Beta testers", "tag = beta").await;
+
+    let response = app.post_publish_newsletter(&newsletter_form("")).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    assert_eq!(queued_emails(&app).await.len(), 2);
+}
+
+#[tokio::test]
+async fn publishing_to_an_unknown_segment_is_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_publish_newsletter(&newsletter_form(&Uuid::new_v4().to_string()))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
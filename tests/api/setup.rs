@@ -0,0 +1,216 @@
+//! tests/api/setup.rs
+
+use secrecy::{ExposeSecret, Secret};
+use uuid::Uuid;
+use zero2prod::authentication::change_password;
+use zero2prod::bootstrap::{bootstrap_admin, issue_setup_token};
+use zero2prod::configuration::InitialAdminSettings;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_without_users, TestApp};
+
+async fn count_setup_tokens(app: &TestApp) -> i64 {
+    return sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM admin_setup_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+fn setup_body(token: &Secret<String>) -> serde_json::Value {
+    return serde_json::json!({
+        "token": token.expose_secret(),
+        "username": "alice",
+        "password": "a-long-enough-password",
+        "password_check": "a-long-enough-password",
+    });
+}
+
+#[tokio::test]
+async fn the_seed_admin_is_not_created() {
+    let app = spawn_app().await;
+
+    let seed_user = sqlx::query!("SELECT user_id FROM users WHERE username = 'admin'")
+        .fetch_optional(&app.db_pool)
+        .await
+        .unwrap();
+
+    assert!(seed_user.is_none());
+}
+
+const SEED_ADMIN_ID: Uuid = uuid::uuid!("85fbb63b-2a00-48f8-8e73-8b5e339da33c");
+
+/// Restore the seed admin, as it might still exist in an old deployment.
+async fn store_seed_admin(app: &TestApp) {
+    sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash)
+        VALUES (
+            $1,
+            'admin',
+            '$argon2id$v=19$m=15000,t=2,p=1$YPkfny0tcIJyiEhJKtdlWA$mwtPk3rLajPcqsweLieIJKqQqCzQ+3/3B1WUe98Xsbw'
+        )
+        "#,
+        SEED_ADMIN_ID
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn change_seed_admin_password(app: &TestApp, password: &str) {
+    change_password(
+        SEED_ADMIN_ID,
+        Secret::new(password.to_string()),
+        &app.db_pool,
+        &app.configuration.password_hashing,
+    )
+    .await
+    .unwrap();
+}
+
+async fn bootstrap(app: &TestApp) {
+    bootstrap_admin(
+        &app.db_pool,
+        None,
+        &app.configuration.application.base_url,
+        &app.configuration.password_hashing,
+    )
+    .await
+    .unwrap();
+}
+
+async fn seed_admin_exists(app: &TestApp) -> bool {
+    return sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM users WHERE user_id = $1) AS "exists!""#,
+        SEED_ADMIN_ID
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn an_unchanged_seed_admin_is_removed_on_startup() {
+    let app = spawn_app_without_users(|_| {}).await;
+    store_seed_admin(&app).await;
+
+    bootstrap(&app).await;
+
+    assert!(!seed_admin_exists(&app).await);
+}
+
+#[tokio::test]
+async fn the_seed_admin_is_removed_even_after_its_password_was_rehashed() {
+    let app = spawn_app_without_users(|_| {}).await;
+    store_seed_admin(&app).await;
+    // A fresh hash of the public password, like the one logging in stores
+    change_seed_admin_password(&app, "everythinghastostartsomewhere").await;
+
+    bootstrap(&app).await;
+
+    assert!(!seed_admin_exists(&app).await);
+}
+
+#[tokio::test]
+async fn the_seed_admin_is_kept_once_its_password_was_changed() {
+    let app = spawn_app_without_users(|_| {}).await;
+    store_seed_admin(&app).await;
+    change_seed_admin_password(&app, "a-private-replacement-password").await;
+
+    bootstrap(&app).await;
+
+    assert!(seed_admin_exists(&app).await);
+}
+
+#[tokio::test]
+async fn a_setup_token_is_issued_on_startup_when_there_are_no_users() {
+    let app = spawn_app_without_users(|_| {}).await;
+
+    assert_eq!(count_setup_tokens(&app).await, 1);
+}
+
+#[tokio::test]
+async fn the_setup_page_is_not_found_once_a_user_exists() {
+    let app = spawn_app().await;
+
+    let response = app.get_setup("any-token").await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn the_first_admin_can_be_created_with_a_setup_token() {
+    let app = spawn_app_without_users(|_| {}).await;
+    let token = issue_setup_token(&app.db_pool).await.unwrap();
+
+    let html_page = app
+        .get_setup(token.expose_secret())
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(token.expose_secret()));
+
+    let response = app.post_setup(&setup_body(&token)).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": "alice",
+            "password": "a-long-enough-password",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    assert_eq!(count_setup_tokens(&app).await, 0);
+}
+
+#[tokio::test]
+async fn an_invalid_setup_token_is_rejected() {
+    let app = spawn_app_without_users(|_| {}).await;
+
+    let response = app
+        .post_setup(&setup_body(&Secret::new("not-a-real-token".into())))
+        .await;
+    assert_is_redirect_to(&response, "/setup");
+
+    let html_page = app.get_setup("").await.text().await.unwrap();
+    assert!(html_page.contains("<p><i>The setup token is invalid</i></p>"));
+    let n_users = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM users"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_users, 0);
+}
+
+#[tokio::test]
+async fn setup_can_only_be_completed_once() {
+    let app = spawn_app_without_users(|_| {}).await;
+    let token = issue_setup_token(&app.db_pool).await.unwrap();
+    let other_token = issue_setup_token(&app.db_pool).await.unwrap();
+
+    let response = app.post_setup(&setup_body(&token)).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.post_setup(&setup_body(&other_token)).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn the_initial_admin_from_configuration_is_created_on_startup() {
+    let app = spawn_app_without_users(|c| {
+        c.initial_admin = Some(InitialAdminSettings {
+            username: "alice".into(),
+            password: Secret::new("a-long-enough-password".into()),
+        });
+    })
+    .await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": "alice",
+            "password": "a-long-enough-password",
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    assert_eq!(count_setup_tokens(&app).await, 0);
+}
@@ -0,0 +1,84 @@
+//! tests/api/shutdown.rs
+
+use std::time::Duration;
+
+use crate::helpers::{create_confirmed_subscriber, spawn_app};
+use tokio_util::sync::CancellationToken;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::issue_delivery_worker::run_worker_until_stopped;
+use zero2prod::webhooks::run_webhook_worker_until_stopped;
+
+#[tokio::test]
+async fn idle_workers_exit_promptly_when_shutdown_is_requested() {
+    let app = spawn_app().await;
+    let shutdown = CancellationToken::new();
+    let worker = tokio::spawn(run_worker_until_stopped(
+        app.configuration.clone(),
+        shutdown.clone(),
+    ));
+    let webhook_worker = tokio::spawn(run_webhook_worker_until_stopped(
+        app.configuration.clone(),
+        shutdown.clone(),
+    ));
+    // Let both workers find an empty queue and go to sleep
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    shutdown.cancel();
+
+    let (worker, webhook_worker) = tokio::time::timeout(Duration::from_secs(2), async {
+        tokio::join!(worker, webhook_worker)
+    })
+    .await
+    .expect("The workers did not stop in time");
+    assert!(worker.unwrap().is_ok());
+    assert!(webhook_worker.unwrap().is_ok());
+}
+
+#[tokio::test]
+async fn a_task_in_flight_is_completed_before_the_worker_exits() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(1)))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    app.post_publish_newsletter(&newsletter_request_body).await;
+
+    let n_sent_before = app.email_server.received_requests().await.unwrap().len();
+    let shutdown = CancellationToken::new();
+    let worker = tokio::spawn(run_worker_until_stopped(
+        app.configuration.clone(),
+        shutdown.clone(),
+    ));
+    // Request a shutdown while the email provider is still handling the send
+    while app.email_server.received_requests().await.unwrap().len() == n_sent_before {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    shutdown.cancel();
+
+    worker.await.unwrap().unwrap();
+    let n_queued = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_queued, 0);
+    let outcome = sqlx::query!("SELECT outcome FROM issue_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .outcome;
+    assert_eq!(outcome, "delivered");
+}
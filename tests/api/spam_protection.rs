@@ -0,0 +1,203 @@
+//! tests/api/spam_protection.rs
+
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{spawn_app, spawn_app_without_users, TestApp};
+
+async fn count_subscribers(app: &TestApp) -> i64 {
+    return sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+async fn post_subscriptions_from(
+    app: &TestApp,
+    ip_address: &str,
+    email: &str,
+) -> reqwest::Response {
+    return app
+        .api_client
+        .post(format!("{}/subscriptions", app.address))
+        .header("X-Forwarded-For", ip_address)
+        .form(&[
+            ("name", "le guin"),
+            ("email", email),
+            ("form_token", &app.get_form_token().await),
+        ])
+        .send()
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn the_home_page_embeds_a_form_token() {
+    let app = spawn_app().await;
+
+    let form_token = app.get_form_token().await;
+
+    assert!(!form_token.is_empty());
+}
+
+#[tokio::test]
+async fn bots_filling_in_the_honeypot_are_silently_ignored() {
+    let app = spawn_app().await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions(
+            "name=le%20guin&email=ursula_le_guin%40gmail.com&website=http%3A%2F%2Fspam.com".into(),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(count_subscribers(&app).await, 0);
+}
+
+#[tokio::test]
+async fn submissions_without_a_valid_form_token_are_rejected() {
+    let app = spawn_app().await;
+    let test_cases = vec![
+        ("", "missing"),
+        ("&form_token=garbage", "malformed"),
+        ("&form_token=1000.nonce.abcdef", "forged"),
+    ];
+
+    for (form_token, description) in test_cases {
+        let response = app
+            .post_subscriptions_raw(format!(
+                "name=le%20guin&email=ursula_le_guin%40gmail.com{}",
+                form_token
+            ))
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not reject a {} form token",
+            description
+        );
+    }
+    assert_eq!(count_subscribers(&app).await, 0);
+}
+
+#[tokio::test]
+async fn a_form_token_can_only_be_used_once() {
+    let app = spawn_app().await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let form_token = app.get_form_token().await;
+
+    let response = app
+        .post_subscriptions_raw(format!(
+            "name=le%20guin&email=ursula_le_guin%40gmail.com&form_token={}",
+            form_token
+        ))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app
+        .post_subscriptions_raw(format!(
+            "name=le%20guin&email=someone_else%40gmail.com&form_token={}",
+            form_token
+        ))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "The form was already submitted, please reload the page"
+    );
+    assert_eq!(count_subscribers(&app).await, 1);
+}
+
+#[tokio::test]
+async fn instant_submissions_are_rejected() {
+    let app = spawn_app_without_users(|c| {
+        c.subscriptions.spam_protection.min_form_fill_seconds = 60;
+    })
+    .await;
+
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "The form was submitted too quickly"
+    );
+}
+
+#[tokio::test]
+async fn disposable_email_addresses_are_rejected() {
+    let app = spawn_app_without_users(|c| {
+        c.subscriptions.spam_protection.disposable_email_domains = vec!["mailinator.com".into()];
+    })
+    .await;
+
+    let response = app
+        .post_subscriptions("name=bot&email=bot%40mailinator.com".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(count_subscribers(&app).await, 0);
+}
+
+#[tokio::test]
+async fn repeated_attempts_for_the_same_email_are_rate_limited() {
+    let app = spawn_app_without_users(|c| {
+        c.subscriptions
+            .spam_protection
+            .max_attempts_per_email_per_hour = 2;
+    })
+    .await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    // Redis is shared between tests, so each run needs an address of its own
+    let body = format!("name=victim&email={}%40example.com", Uuid::new_v4());
+
+    for _ in 0..2 {
+        let response = app.post_subscriptions(body.clone()).await;
+        assert_ne!(response.status().as_u16(), 429);
+    }
+    let response = app.post_subscriptions(body).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+#[tokio::test]
+async fn repeated_attempts_from_the_same_ip_address_are_rate_limited() {
+    let app = spawn_app_without_users(|c| {
+        c.subscriptions.spam_protection.max_attempts_per_ip_per_hour = 1;
+        // The test client stands in for a reverse proxy naming the client
+        c.application.trusted_proxies = vec!["127.0.0.1".parse().unwrap()];
+    })
+    .await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    // Redis is shared between tests, so each run needs an address of its own
+    let [a, b, c]: [u8; 3] = rand::random();
+    let ip_address = format!("10.{}.{}.{}", a, b, c);
+
+    let response = post_subscriptions_from(&app, &ip_address, "ursula_le_guin@gmail.com").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = post_subscriptions_from(&app, &ip_address, "someone_else@gmail.com").await;
+
+    assert_eq!(response.status().as_u16(), 429);
+}
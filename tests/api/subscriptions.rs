@@ -1,8 +1,9 @@
 //! tests/api/subscriptions.rs
 
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_without_users};
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
+use zero2prod::domain::OptIn;
 
 #[tokio::test]
 async fn subscribe_returns_200_for_valid_form_data() {
@@ -145,3 +146,41 @@ async fn subscribe_fails_if_there_is_fatal_database_error() {
 
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[tokio::test]
+async fn subscribe_returns_a_plain_text_error_for_invalid_data() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_subscriptions("name=Test&email=defs-not-an-email".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "defs-not-an-email is not a valid subscriber email"
+    );
+}
+
+#[tokio::test]
+async fn subscribe_confirms_straight_away_with_single_opt_in() {
+    let app = spawn_app_without_users(|c| c.subscriptions.opt_in = OptIn::Single).await;
+    app.test_user.store(&app.db_pool).await;
+    let body = "name=Matt%20B&email=matt_b%40gmail.com";
+
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app.post_subscriptions(body.into()).await;
+
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscriptions");
+    assert_eq!(saved.status, "confirmed");
+}
@@ -18,6 +18,28 @@ async fn confirmations_without_token_is_rejected_with_400() {
     assert_eq!(response.status().as_u16(), 400);
 }
 
+#[tokio::test]
+async fn an_unknown_token_is_rejected_with_a_plain_text_401() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!(
+        "{}/subscriptions/confirm?subscription_token=unknown",
+        app.address
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    assert_eq!(
+        response.text().await.unwrap(),
+        "No subscriber with associated token"
+    );
+}
+
 #[tokio::test]
 async fn link_returned_by_subscribe_returns_200_when_called() {
     let app = spawn_app().await;
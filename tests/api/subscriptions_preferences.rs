@@ -0,0 +1,497 @@
+//! tests/api/subscriptions_preferences.rs
+
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app, TestApp};
+
+async fn subscriber_id(app: &TestApp) -> Uuid {
+    return sqlx::query_scalar!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+/// The subscriber's preference center, as a request URL against `app`.
+fn preferences_url(app: &TestApp, subscriber_id: Uuid) -> String {
+    let url = reqwest::Url::parse(&app.preference_links.url(subscriber_id)).unwrap();
+    return format!("{}{}?{}", app.address, url.path(), url.query().unwrap());
+}
+
+/// The `subscriber`, `expires` and `signature` fields every preference center form carries.
+fn link_fields(app: &TestApp, subscriber_id: Uuid) -> Vec<(&'static str, String)> {
+    let url = reqwest::Url::parse(&app.preference_links.url(subscriber_id)).unwrap();
+    let field = |name: &str| {
+        return url
+            .query_pairs()
+            .find(|(k, _)| k == name)
+            .unwrap()
+            .1
+            .to_string();
+    };
+    return vec![
+        ("subscriber", field("subscriber")),
+        ("expires", field("expires")),
+        ("signature", field("signature")),
+    ];
+}
+
+async fn get_preferences_html(app: &TestApp, subscriber_id: Uuid) -> String {
+    return app
+        .api_client
+        .get(preferences_url(app, subscriber_id))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+}
+
+async fn post_preferences(app: &TestApp, form: &[(&str, String)]) -> reqwest::Response {
+    return app
+        .api_client
+        .post(format!("{}/subscriptions/preferences", app.address))
+        .form(form)
+        .send()
+        .await
+        .unwrap();
+}
+
+fn preferences_form(app: &TestApp, subscriber_id: Uuid, name: &str) -> Vec<(&'static str, String)> {
+    let mut form = link_fields(app, subscriber_id);
+    form.push(("name", name.to_string()));
+    return form;
+}
+
+async fn publish_newsletter(app: &TestApp) {
+    let response = app
+        .post_publish_newsletter(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<html><body><p>Newsletter body as HTML</p></body></html>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+}
+
+#[tokio::test]
+async fn issues_link_to_the_preference_center() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = subscriber_id(&app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    publish_newsletter(&app).await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let text = email["content"][1]["value"].as_str().unwrap();
+    let preferences_url = text.rsplit_once("Manage your subscription: ").unwrap().1;
+    let html = email["content"][0]["value"].as_str().unwrap();
+    assert!(html.contains(&format!(
+        r#"<a href="{}">Manage your subscription</a></p></body>"#,
+        htmlescape::encode_minimal(preferences_url)
+    )));
+    let url = reqwest::Url::parse(preferences_url).unwrap();
+    let field = |name: &str| url.query_pairs().find(|(k, _)| k == name).unwrap().1;
+    assert_eq!(field("subscriber"), subscriber_id.to_string());
+    app.preference_links
+        .verify(
+            subscriber_id,
+            field("expires").parse().unwrap(),
+            &field("signature"),
+        )
+        .unwrap();
+}
+
+#[tokio::test]
+async fn the_preference_center_requires_a_valid_signature() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = subscriber_id(&app).await;
+    let other_subscriber_fields = link_fields(&app, Uuid::new_v4());
+    let own_fields = link_fields(&app, subscriber_id);
+
+    for (expires, signature) in [
+        // Another subscriber's link
+        (&other_subscriber_fields[1].1, &other_subscriber_fields[2].1),
+        // An extended expiry
+        (&"99999999999".to_string(), &own_fields[2].1),
+    ] {
+        let response = app
+            .api_client
+            .get(format!(
+                "{}/subscriptions/preferences?subscriber={}&expires={}&signature={}",
+                app.address, subscriber_id, expires, signature
+            ))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 400);
+    }
+
+    let mut form = preferences_form(&app, subscriber_id, "Mallory");
+    form[1].1 = other_subscriber_fields[1].1.clone();
+    form[2].1 = other_subscriber_fields[2].1.clone();
+    let response = post_preferences(&app, &form).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn subscribers_can_update_their_name_and_lists() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_lists("Weekly digest").await;
+    app.post_lists("Announcements").await;
+    let digest = sqlx::query_scalar!("SELECT list_id FROM lists WHERE name = 'Weekly digest'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = subscriber_id(&app).await;
+
+    let html_page = get_preferences_html(&app, subscriber_id).await;
+    assert!(html_page.contains("Announcements"));
+    assert!(html_page.contains(&format!(r#"value="{}">"#, digest)));
+
+    let mut form = preferences_form(&app, subscriber_id, "Ursula Le Guin");
+    form.push(("lists", digest.to_string()));
+    let response = post_preferences(&app, &form).await;
+    assert_is_redirect_to(
+        &response,
+        &preferences_url(&app, subscriber_id).replace(&app.address, ""),
+    );
+
+    let html_page = get_preferences_html(&app, subscriber_id).await;
+    assert!(html_page.contains("<p><i>Your preferences have been updated</i></p>"));
+    assert!(html_page.contains(r#"value="Ursula Le Guin""#));
+    assert!(html_page.contains(&format!(r#"value="{}" checked>"#, digest)));
+    let lists = sqlx::query_scalar!(
+        "SELECT list_id FROM list_memberships WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(lists, vec![digest]);
+}
+
+#[tokio::test]
+async fn an_invalid_name_is_rejected() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = subscriber_id(&app).await;
+    let name_before = sqlx::query_scalar!("SELECT name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = post_preferences(&app, &preferences_form(&app, subscriber_id, "  ")).await;
+    assert_eq!(response.status().as_u16(), 303);
+
+    let html_page = get_preferences_html(&app, subscriber_id).await;
+    assert!(html_page.contains("is not a valid subscriber name</i></p>"));
+    let name_after = sqlx::query_scalar!("SELECT name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(name_before, name_after);
+}
+
+#[tokio::test]
+async fn paused_subscribers_do_not_receive_issues_until_they_resume() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = subscriber_id(&app).await;
+
+    let mut form = preferences_form(&app, subscriber_id, "Ursula Le Guin");
+    form.push(("pause", "30".into()));
+    post_preferences(&app, &form).await;
+    let html_page = get_preferences_html(&app, subscriber_id).await;
+    assert!(html_page.contains("Stay paused until"));
+
+    publish_newsletter(&app).await;
+    let n_queued = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_queued, 0);
+
+    let mut form = preferences_form(&app, subscriber_id, "Ursula Le Guin");
+    form.push(("pause", "resume".into()));
+    post_preferences(&app, &form).await;
+    publish_newsletter(&app).await;
+    let n_queued = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM issue_delivery_queue"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_queued, 1);
+}
+
+#[tokio::test]
+async fn subscribers_can_unsubscribe() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = subscriber_id(&app).await;
+
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/subscriptions/preferences/unsubscribe",
+            app.address
+        ))
+        .form(&link_fields(&app, subscriber_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("You have been unsubscribed"));
+
+    let n_subscribers = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_subscribers, 0);
+    let response = app
+        .api_client
+        .get(preferences_url(&app, subscriber_id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+/// The last email sent to `address`.
+async fn last_email_to(app: &TestApp, address: &str) -> wiremock::Request {
+    return app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .into_iter()
+        .rev()
+        .find(|r| {
+            let body: serde_json::Value = serde_json::from_slice(&r.body).unwrap();
+            return body["personalizations"][0]["to"][0]["email"] == address;
+        })
+        .unwrap();
+}
+
+async fn post_email_change(app: &TestApp, subscriber_id: Uuid, email: &str) -> reqwest::Response {
+    let mut form = link_fields(app, subscriber_id);
+    form.push(("email", email.to_string()));
+    return app
+        .api_client
+        .post(format!("{}/subscriptions/preferences/email", app.address))
+        .form(&form)
+        .send()
+        .await
+        .unwrap();
+}
+
+/// Presses the button on the page behind an emailed link.
+async fn post_link_token(app: &TestApp, link: &reqwest::Url) -> reqwest::Response {
+    let token = link.query_pairs().find(|(k, _)| k == "token").unwrap().1;
+    return app
+        .api_client
+        .post(format!("{}{}", app.address, link.path()))
+        .form(&[("token", token)])
+        .send()
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn the_email_address_changes_once_the_new_address_is_confirmed() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = subscriber_id(&app).await;
+    publish_newsletter(&app).await;
+    let _mock_guard = Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        // The confirmation to the new address and the notice to the current one
+        .expect(2)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    let response = post_email_change(&app, subscriber_id, "ursula@example.com").await;
+    assert_eq!(response.status().as_u16(), 303);
+    let html_page = get_preferences_html(&app, subscriber_id).await;
+    assert!(html_page.contains("We sent a confirmation link to ursula@example.com"));
+
+    let email_request = last_email_to(&app, "ursula@example.com").await;
+    let n_changed = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM subscriptions WHERE email = 'ursula@example.com'"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(n_changed, 0);
+
+    let confirmation_link = app.get_confirmation_links(&email_request).html;
+    let html_page = reqwest::get(confirmation_link.clone())
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page
+        .contains(r#"<form action="/subscriptions/preferences/email/confirm" method="post">"#));
+    // Following the link alone changes nothing
+    let email = sqlx::query_scalar!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_ne!(email, "ursula@example.com");
+    let response = post_link_token(&app, &confirmation_link).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let email = sqlx::query_scalar!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(email, "ursula@example.com");
+    let queued_email = sqlx::query_scalar!("SELECT subscriber_email FROM issue_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued_email, "ursula@example.com");
+    // The link only works once
+    let response = reqwest::get(confirmation_link.clone()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    let response = post_link_token(&app, &confirmation_link).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn the_current_address_can_cancel_an_email_change() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber = sqlx::query!("SELECT id, email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let _mock_guard = Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    post_email_change(&app, subscriber.id, "mallory@example.com").await;
+    let confirmation_link = app
+        .get_confirmation_links(&last_email_to(&app, "mallory@example.com").await)
+        .html;
+
+    let notice = last_email_to(&app, &subscriber.email).await;
+    let body: serde_json::Value = serde_json::from_slice(&notice.body).unwrap();
+    assert!(body["content"][1]["value"]
+        .as_str()
+        .unwrap()
+        .contains("mallory@example.com"));
+    let cancel_link = app.get_confirmation_links(&notice).html;
+    let html_page = reqwest::get(cancel_link.clone())
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page
+        .contains(r#"<form action="/subscriptions/preferences/email/cancel" method="post">"#));
+    let response = post_link_token(&app, &cancel_link).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = post_link_token(&app, &confirmation_link).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let email = sqlx::query_scalar!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(email, subscriber.email);
+    let response = post_link_token(&app, &cancel_link).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn a_suppressed_current_address_is_not_notified_of_an_email_change() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber = sqlx::query!("SELECT id, email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    app.post_suppressions(&subscriber.email).await;
+    let _mock_guard = Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    let response = post_email_change(&app, subscriber.id, "ursula@example.com").await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    last_email_to(&app, "ursula@example.com").await;
+}
+
+#[tokio::test]
+async fn no_confirmation_is_sent_for_an_address_that_is_already_subscribed() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let subscribers = sqlx::query!("SELECT id, email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    let _mock_guard = Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    let response = post_email_change(&app, subscribers[0].id, &subscribers[1].email).await;
+    assert_eq!(response.status().as_u16(), 303);
+    let n_requests =
+        sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM email_change_requests"#)
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(n_requests, 0);
+}
+
+#[tokio::test]
+async fn an_invalid_new_address_is_rejected() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let subscriber_id = subscriber_id(&app).await;
+
+    let response = post_email_change(&app, subscriber_id, "not-an-email").await;
+    assert_eq!(response.status().as_u16(), 303);
+
+    let html_page = get_preferences_html(&app, subscriber_id).await;
+    assert!(html_page.contains("is not a valid subscriber email"));
+}
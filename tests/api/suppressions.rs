@@ -0,0 +1,143 @@
+//! tests/api/suppressions.rs
+
+use uuid::Uuid;
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_suppressions() {
+    let app = spawn_app().await;
+
+    let response = app.get_suppressions().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.post_suppressions("example.com").await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn admins_can_add_and_remove_suppressions() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_suppressions("Blocked@Example.com").await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+    let html_page = app.get_suppressions_html().await;
+    assert!(html_page.contains("<p><i>blocked@example.com has been suppressed</i></p>"));
+
+    let suppression_id = sqlx::query!("SELECT suppression_id, reason FROM suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .suppression_id;
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/admin/suppressions/{}/delete",
+            app.address, suppression_id
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/admin/suppressions");
+    let html_page = app.get_suppressions_html().await;
+    assert!(html_page.contains("<p><i>The suppression has been removed</i></p>"));
+    assert!(!html_page.contains("<td>blocked@example.com</td>"));
+}
+
+#[tokio::test]
+async fn invalid_suppressions_are_rejected() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app.post_suppressions("not a domain").await;
+    assert_is_redirect_to(&response, "/admin/suppressions");
+
+    let html_page = app.get_suppressions_html().await;
+    assert!(html_page.contains("is not a valid email address or domain</i></p>"));
+}
+
+#[tokio::test]
+async fn subscribing_with_a_suppressed_domain_gets_a_neutral_response() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.post_suppressions("blocked.com").await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let body = "name=le%20guin&email=ursula%40Blocked.com";
+    let response = app.post_subscriptions(body.into()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_optional(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(saved.is_none());
+}
+
+#[tokio::test]
+async fn newsletters_skip_suppressed_subscribers() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let email = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    app.test_user.login(&app).await;
+    app.post_suppressions(&email).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    let delivery = sqlx::query!("SELECT outcome FROM issue_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(delivery.outcome, "skipped");
+}
+
+#[tokio::test]
+async fn spam_reports_suppress_the_address() {
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    let email = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+
+    app.post_email_events(&serde_json::json!([{
+        "email": email,
+        "timestamp": 1700000000,
+        "event": "spamreport",
+        "sg_event_id": Uuid::new_v4().to_string(),
+    }]))
+    .await;
+
+    let saved = sqlx::query!("SELECT pattern, reason FROM suppressions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.pattern, email.to_lowercase());
+    assert_eq!(saved.reason, "complained");
+}
@@ -0,0 +1,131 @@
+//! tests/api/tracking.rs
+
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app, TestApp};
+
+/// Publish an issue to a single confirmed subscriber and return the HTML they received.
+async fn publish_and_deliver(app: &TestApp, track_engagement: bool) -> (Uuid, String) {
+    create_confirmed_subscriber(app).await;
+    app.test_user.login(app).await;
+    Mock::given(path("/v3/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let mut body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": r#"<html><body><a href="https://example.com/post?a=1&amp;b=2">Read more</a></body></html>"#,
+        "idempotency_key": Uuid::new_v4().to_string(),
+    });
+    if track_engagement {
+        body["track_engagement"] = true.into();
+    }
+    app.post_publish_newsletter(&body).await;
+    app.dispatch_all_pending_emails().await;
+
+    let newsletter_issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let html = email["content"][0]["value"].as_str().unwrap().to_string();
+    return (newsletter_issue_id, html);
+}
+
+/// The value of the first `attribute="..."` in `html`, as a request URL against `app`.
+fn tracking_url(app: &TestApp, html: &str, attribute: &str) -> String {
+    let marker = format!(r#"{}=""#, attribute);
+    let start = html.find(&marker).unwrap() + marker.len();
+    let end = start + html[start..].find('"').unwrap();
+    let url = htmlescape::decode_html(&html[start..end]).unwrap();
+    let url = reqwest::Url::parse(&url).unwrap();
+    return format!("{}{}?{}", app.address, url.path(), url.query().unwrap());
+}
+
+#[tokio::test]
+async fn tracked_issues_record_opens_and_clicks() {
+    let app = spawn_app().await;
+    let (newsletter_issue_id, html) = publish_and_deliver(&app, true).await;
+
+    let click_url = tracking_url(&app, &html, "href");
+    assert!(click_url.contains("/track/click?"));
+    for _ in 0..2 {
+        let response = app.api_client.get(&click_url).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 302);
+        assert_eq!(
+            response.headers()["Location"],
+            "https://example.com/post?a=1&b=2"
+        );
+    }
+
+    let open_url = tracking_url(&app, &html, "src");
+    let response = app.api_client.get(&open_url).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Content-Type"], "image/gif");
+
+    let html_page = app.get_admin_issue_html(newsletter_issue_id).await;
+    assert!(html_page.contains("<p>Delivered: 1</p>"));
+    assert!(html_page.contains("<p>Unique opens: 1</p>"));
+    assert!(html_page.contains("<p>Click-through rate: 100.0%</p>"));
+    assert!(html_page.contains(
+        "<td>https://example.com/post?a=1&amp;b=2</td>\n        <td>2</td>\n        <td>1</td>"
+    ));
+}
+
+#[tokio::test]
+async fn untracked_issues_are_sent_unchanged() {
+    let app = spawn_app().await;
+    let (newsletter_issue_id, html) = publish_and_deliver(&app, false).await;
+
+    assert!(html.contains(r#"href="https://example.com/post?a=1&amp;b=2""#));
+    assert!(!html.contains("/track/"));
+
+    let html_page = app.get_admin_issue_html(newsletter_issue_id).await;
+    assert!(html_page.contains("Open and click tracking was not enabled for this issue."));
+}
+
+#[tokio::test]
+async fn tampered_click_links_are_rejected() {
+    let app = spawn_app().await;
+    let (_, html) = publish_and_deliver(&app, true).await;
+
+    let click_url = tracking_url(&app, &html, "href").replace("example.com", "evil.com");
+    let response = app.api_client.get(&click_url).send().await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response.headers().get("Location").is_none());
+    let n_engagements = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM issue_engagements")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_engagements, 0);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_issue_stats() {
+    let app = spawn_app().await;
+
+    let response = app
+        .api_client
+        .get(format!("{}/admin/issues", app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+}
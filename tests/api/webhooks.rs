@@ -0,0 +1,165 @@
+//! tests/api/webhooks.rs
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use crate::helpers::{
+    assert_is_redirect_to, create_confirmed_subscriber, create_unconfirmed_subscriber, spawn_app,
+};
+
+fn header(request: &wiremock::Request, name: &str) -> String {
+    return request.headers[&name.into()].last().as_str().to_string();
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_webhooks() {
+    let app = spawn_app().await;
+
+    let response = app.get_webhooks().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app
+        .post_webhooks("https://example.com/hook", &["subscriber.created"])
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn webhook_endpoints_must_have_a_valid_url() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    let response = app
+        .post_webhooks("not a url", &["subscriber.created"])
+        .await;
+    assert_is_redirect_to(&response, "/admin/webhooks");
+
+    let html_page = app.get_webhooks_html().await;
+    assert!(html_page.contains("<p><i>The webhook URL must be a valid http(s) URL</i></p>"));
+}
+
+#[tokio::test]
+async fn new_subscribers_trigger_a_signed_webhook() {
+    let app = spawn_app().await;
+    let webhook_server = MockServer::start().await;
+    app.test_user.login(&app).await;
+    let url = format!("{}/hook", webhook_server.uri());
+    let secret = app
+        .create_webhook_endpoint(&url, &["subscriber.created"])
+        .await;
+    assert!(app.get_webhooks_html().await.contains(&url));
+
+    Mock::given(path("/hook"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&webhook_server)
+        .await;
+    create_unconfirmed_subscriber(&app).await;
+    app.dispatch_all_pending_webhooks().await;
+
+    let request = &webhook_server.received_requests().await.unwrap()[0];
+    assert_eq!(header(request, "X-Webhook-Event"), "subscriber.created");
+    let timestamp = header(request, "X-Webhook-Timestamp");
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(&request.body);
+    let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+    assert_eq!(header(request, "X-Webhook-Signature"), expected);
+
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(body["type"], "subscriber.created");
+    assert_eq!(body["id"], header(request, "X-Webhook-Id"));
+    assert!(body["data"]["email"].is_string());
+}
+
+#[tokio::test]
+async fn endpoints_only_receive_the_events_they_subscribed_to() {
+    let app = spawn_app().await;
+    let webhook_server = MockServer::start().await;
+    app.test_user.login(&app).await;
+    app.create_webhook_endpoint(
+        &format!("{}/hook", webhook_server.uri()),
+        &["subscriber.confirmed"],
+    )
+    .await;
+
+    Mock::given(path("/hook"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&webhook_server)
+        .await;
+    create_confirmed_subscriber(&app).await;
+    app.dispatch_all_pending_webhooks().await;
+
+    let request = &webhook_server.received_requests().await.unwrap()[0];
+    assert_eq!(header(request, "X-Webhook-Event"), "subscriber.confirmed");
+}
+
+#[tokio::test]
+async fn failed_webhook_deliveries_are_retried_later() {
+    let app = spawn_app().await;
+    let webhook_server = MockServer::start().await;
+    app.test_user.login(&app).await;
+    app.create_webhook_endpoint(
+        &format!("{}/hook", webhook_server.uri()),
+        &["subscriber.created"],
+    )
+    .await;
+
+    Mock::given(path("/hook"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&webhook_server)
+        .await;
+    create_unconfirmed_subscriber(&app).await;
+    app.dispatch_all_pending_webhooks().await;
+
+    let task = sqlx::query!(
+        "SELECT n_retries, execute_after > now() AS \"scheduled_later!\" FROM webhook_delivery_queue"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("The failed delivery should still be queued");
+    assert_eq!(task.n_retries, 1);
+    assert!(task.scheduled_later);
+    // Mock verifies on Drop that the retry was not attempted straight away
+}
+
+#[tokio::test]
+async fn deleting_an_endpoint_drops_its_pending_deliveries() {
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    app.create_webhook_endpoint("https://example.com/hook", &["subscriber.created"])
+        .await;
+    create_unconfirmed_subscriber(&app).await;
+    let webhook_endpoint_id = sqlx::query!("SELECT webhook_endpoint_id FROM webhook_endpoints")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .webhook_endpoint_id;
+
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/admin/webhooks/{}/delete",
+            app.address, webhook_endpoint_id
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/admin/webhooks");
+
+    let html_page = app.get_webhooks_html().await;
+    assert!(html_page.contains("<p><i>The webhook endpoint has been deleted</i></p>"));
+    let pending = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM webhook_delivery_queue")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(pending.count, 0);
+}